@@ -1,45 +1,76 @@
 use bevy::{math::vec2, prelude::*};
-use rand::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    archetypes::{StructureRegistry, UnitRegistry},
     construction::PlaceConstructionSite,
+    currency::Energy,
+    diplomacy::Diplomacy,
+    map::{Map, MapLoaded},
+    match_setup::{MatchEntity, MatchRng},
     movement::{Formation, Moving, SetUnitPosition},
+    nav_agent::PathFailed,
+    orders::ClearOrders,
     producer::{AttemptProductionIncrease, ProductionType},
     schedule::InGameSet,
     selectable::{BoxSelection, SelectedStructures, SelectedUnits},
     structure::StructureType,
-    teams::TeamType,
-    ui::{PRODUCER_COST, SIMPLE_SHRINE_COST},
+    teams::{Players, TeamType},
     unit::Unit,
 };
 
-const AI_STEP_COOLDOWN: f32 = 5.0;
 const AI_FORCE_FORWARD: f32 = 30.0;
 const AI_FINAL_PHASE: usize = 8;
 const AI_RESET_PHASE: usize = 7;
 
-const CORNER_OFFSET: Vec2 = Vec2::new(5000., 5000.);
+//  layouts are written for the SW start, then rotated about the map center onto each AI's start
+const LAYOUT_START: Vec2 = Vec2::new(600., 600.);
 
 const GENERATOR_1_POSITION: Vec2 = Vec2::new(400., 400.);
 const GENERATOR_2_POSITION: Vec2 = Vec2::new(400., 300.);
 const GENERATOR_3_POSITION: Vec2 = Vec2::new(300., 400.);
 const GENERATOR_4_POSITION: Vec2 = Vec2::new(300., 300.);
 const PRODUCER_1_POSITION: Vec2 = Vec2::new(800., 800.);
-const ENEMY_BASE_POSITION: Vec2 = Vec2::new(700., 700.);
+const ENEMY_BASE_OFFSET: f32 = 100.0;
+
+//  the structures the build order puts up, by their id in the structure file
+const GENERATOR: &str = "SimpleShrine";
+const PRODUCER: &str = "Producer";
 
 pub struct AIPlugin;
 
 impl Plugin for AIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, load_instructions)
-            .add_systems(Update, run_instruction.in_set(InGameSet::AIInput))
-            .add_event::<RunNextInstruction>()
-            .insert_resource(AIInstructionSets {
-                current_phase: 0,
-                cooldown: AI_STEP_COOLDOWN,
-                force_forward: AI_FORCE_FORWARD,
-                sets: Vec::new(),
-            });
+        app.add_systems(
+            Update,
+            (
+                load_instructions.run_if(on_event::<MapLoaded>()),
+                (forget_stranded_units, run_instruction)
+                    .chain()
+                    .in_set(InGameSet::AIInput),
+            ),
+        )
+        .add_event::<RunNextInstruction>()
+        .insert_resource(AIDifficulty::Normal);
+    }
+}
+
+/// How quickly every AI works through its instructions
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum AIDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl AIDifficulty {
+    pub fn step_cooldown(&self) -> f32 {
+        match self {
+            AIDifficulty::Easy => 8.0,
+            AIDifficulty::Normal => 5.0,
+            AIDifficulty::Hard => 3.0,
+        }
     }
 }
 
@@ -51,6 +82,7 @@ pub struct Dependancy {
     pub expectation: DependancyExpectation,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub enum DependancyExpectation {
     Idle(bool),
     Moving(bool),
@@ -59,11 +91,11 @@ pub enum DependancyExpectation {
 #[derive(Debug)]
 pub enum AIInstructionType {
     Selection(Rect),
-    Movement(Vec2),
+    //  each point is queued up behind the last
+    Movement(Vec<Vec2>),
     Build {
         position: Vec2,
         structure: StructureType,
-        cost: f32,
     },
     Produce {
         production: ProductionType,
@@ -93,354 +125,433 @@ impl Default for AIInstructionSet {
     }
 }
 
-#[derive(Resource)]
+#[derive(Component)]
 pub struct AIInstructionSets {
+    pub team: TeamType,
     pub current_phase: usize,
     pub sets: Vec<AIInstructionSet>,
     pub cooldown: f32,
+    pub step_cooldown: f32,
     pub force_forward: f32,
 }
 
 #[derive(Event)]
 pub struct RunNextInstruction {}
 
-fn load_instructions(mut instruction_sets: ResMut<AIInstructionSets>) {
-    *instruction_sets = AIInstructionSets {
-        current_phase: 0,
-        cooldown: AI_STEP_COOLDOWN,
-        force_forward: AI_FORCE_FORWARD,
-        sets: vec![
-            AIInstructionSet {
-                name: "build 1st generator".into(),
-                phase: 0,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - vec2(590., 590.),
-                        CORNER_OFFSET - vec2(610., 610.),
-                    )),
-                    AIInstructionType::Build {
-                        position: CORNER_OFFSET - GENERATOR_1_POSITION,
-                        structure: StructureType::SimpleShrine,
-                        cost: SIMPLE_SHRINE_COST,
-                    },
-                ],
-                ..default()
-            },
-            AIInstructionSet {
-                name: "build 2nd generator".into(),
-                phase: 1,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - GENERATOR_1_POSITION - vec2(80., 80.),
-                        CORNER_OFFSET - GENERATOR_1_POSITION + vec2(80., 80.),
-                    )),
-                    AIInstructionType::Build {
-                        position: CORNER_OFFSET - GENERATOR_2_POSITION,
-                        structure: StructureType::SimpleShrine,
-                        cost: SIMPLE_SHRINE_COST,
-                    },
-                ],
-                ..default()
-            },
-            AIInstructionSet {
-                name: "build 1st producer".into(),
-                phase: 2,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - GENERATOR_2_POSITION - vec2(80., 80.),
-                        CORNER_OFFSET - GENERATOR_2_POSITION + vec2(80., 80.),
-                    )),
-                    AIInstructionType::Build {
-                        position: CORNER_OFFSET - PRODUCER_1_POSITION,
-                        structure: StructureType::Producer,
-                        cost: PRODUCER_COST,
-                    },
-                ],
-                ..default()
-            },
-            AIInstructionSet {
-                name: "produce 1st worker".into(),
-                phase: 3,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - PRODUCER_1_POSITION,
-                        CORNER_OFFSET - PRODUCER_1_POSITION,
-                    )),
-                    AIInstructionType::Produce {
-                        production: ProductionType::Worker,
-                        count: 1,
-                    },
-                ],
-                ..default()
-            },
-            AIInstructionSet {
-                name: "build 3rd generator".into(),
-                phase: 4,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - PRODUCER_1_POSITION - vec2(80., 80.),
-                        CORNER_OFFSET - PRODUCER_1_POSITION + vec2(80., 80.),
-                    )),
-                    AIInstructionType::Build {
-                        position: CORNER_OFFSET - GENERATOR_3_POSITION,
-                        structure: StructureType::SimpleShrine,
-                        cost: SIMPLE_SHRINE_COST,
-                    },
-                ],
-                ..default()
-            },
-            AIInstructionSet {
-                name: "build 4th generator".into(),
-                phase: 5,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - GENERATOR_3_POSITION - vec2(80., 80.),
-                        CORNER_OFFSET - GENERATOR_3_POSITION + vec2(80., 80.),
-                    )),
-                    AIInstructionType::Build {
-                        position: CORNER_OFFSET - GENERATOR_4_POSITION,
-                        structure: StructureType::SimpleShrine,
-                        cost: SIMPLE_SHRINE_COST,
-                    },
-                ],
-                ..default()
-            },
-            AIInstructionSet {
-                name: "create 3 warriors".into(),
-                phase: 6,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - PRODUCER_1_POSITION,
-                        CORNER_OFFSET - PRODUCER_1_POSITION,
-                    )),
-                    AIInstructionType::Produce {
-                        production: ProductionType::Warrior,
-                        count: 3,
-                    },
-                ],
-                ..default()
-            },
-            AIInstructionSet {
-                name: "send troops to enemy base".into(),
-                phase: 7,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - PRODUCER_1_POSITION - vec2(100., 100.),
-                        CORNER_OFFSET - PRODUCER_1_POSITION + vec2(100., 100.),
-                    )),
-                    AIInstructionType::Movement(ENEMY_BASE_POSITION),
-                ],
-                ..default()
-            },
-            AIInstructionSet {
-                name: "create 5 warriors".into(),
-                phase: 7,
-                steps: vec![
-                    AIInstructionType::Selection(Rect::from_corners(
-                        CORNER_OFFSET - PRODUCER_1_POSITION,
-                        CORNER_OFFSET - PRODUCER_1_POSITION,
-                    )),
-                    AIInstructionType::Produce {
-                        production: ProductionType::Warrior,
-                        count: 5,
-                    },
-                ],
-                ..default()
+fn load_instructions(
+    mut commands: Commands,
+    map: Res<Map>,
+    players: Res<Players>,
+    diplomacy: Res<Diplomacy>,
+    difficulty: Res<AIDifficulty>,
+) {
+    let center = map.size / 2.0;
+
+    for team in players.cpu_teams() {
+        let Some(start) = map.starting_points.iter().find(|point| point.team == team) else {
+            continue;
+        };
+        let Some(enemy) = map.starting_points.iter().find(|point| {
+            players.contains(&point.team) && diplomacy.is_hostile(&team, &point.team)
+        }) else {
+            continue;
+        };
+
+        let rotation =
+            Vec2::from_angle((LAYOUT_START - center).angle_between(start.position - center));
+        let at = |position: Vec2| rotation.rotate(position - center) + center;
+        let enemy_base = enemy.position + (center - enemy.position).signum() * ENEMY_BASE_OFFSET;
+
+        commands.spawn((
+            AIInstructionSets {
+                team,
+                current_phase: 0,
+                cooldown: difficulty.step_cooldown(),
+                step_cooldown: difficulty.step_cooldown(),
+                force_forward: AI_FORCE_FORWARD,
+                sets: instruction_sets(at, enemy_base),
             },
-        ],
-    };
+            MatchEntity,
+            Name::new("AI"),
+        ));
+    }
+}
+
+fn instruction_sets(at: impl Fn(Vec2) -> Vec2, enemy_base: Vec2) -> Vec<AIInstructionSet> {
+    vec![
+        AIInstructionSet {
+            name: "build 1st generator".into(),
+            phase: 0,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(LAYOUT_START - vec2(10., 10.)),
+                    at(LAYOUT_START + vec2(10., 10.)),
+                )),
+                AIInstructionType::Build {
+                    position: at(GENERATOR_1_POSITION),
+                    structure: GENERATOR.into(),
+                },
+            ],
+            ..default()
+        },
+        AIInstructionSet {
+            name: "build 2nd generator".into(),
+            phase: 1,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(GENERATOR_1_POSITION - vec2(80., 80.)),
+                    at(GENERATOR_1_POSITION + vec2(80., 80.)),
+                )),
+                AIInstructionType::Build {
+                    position: at(GENERATOR_2_POSITION),
+                    structure: GENERATOR.into(),
+                },
+            ],
+            ..default()
+        },
+        AIInstructionSet {
+            name: "build 1st producer".into(),
+            phase: 2,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(GENERATOR_2_POSITION - vec2(80., 80.)),
+                    at(GENERATOR_2_POSITION + vec2(80., 80.)),
+                )),
+                AIInstructionType::Build {
+                    position: at(PRODUCER_1_POSITION),
+                    structure: PRODUCER.into(),
+                },
+            ],
+            ..default()
+        },
+        AIInstructionSet {
+            name: "produce 1st worker".into(),
+            phase: 3,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(PRODUCER_1_POSITION),
+                    at(PRODUCER_1_POSITION),
+                )),
+                AIInstructionType::Produce {
+                    production: ProductionType::Worker,
+                    count: 1,
+                },
+            ],
+            ..default()
+        },
+        AIInstructionSet {
+            name: "build 3rd generator".into(),
+            phase: 4,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(PRODUCER_1_POSITION - vec2(80., 80.)),
+                    at(PRODUCER_1_POSITION + vec2(80., 80.)),
+                )),
+                AIInstructionType::Build {
+                    position: at(GENERATOR_3_POSITION),
+                    structure: GENERATOR.into(),
+                },
+            ],
+            ..default()
+        },
+        AIInstructionSet {
+            name: "build 4th generator".into(),
+            phase: 5,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(GENERATOR_3_POSITION - vec2(80., 80.)),
+                    at(GENERATOR_3_POSITION + vec2(80., 80.)),
+                )),
+                AIInstructionType::Build {
+                    position: at(GENERATOR_4_POSITION),
+                    structure: GENERATOR.into(),
+                },
+            ],
+            ..default()
+        },
+        AIInstructionSet {
+            name: "create 3 warriors".into(),
+            phase: 6,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(PRODUCER_1_POSITION),
+                    at(PRODUCER_1_POSITION),
+                )),
+                AIInstructionType::Produce {
+                    production: ProductionType::Warrior,
+                    count: 3,
+                },
+            ],
+            ..default()
+        },
+        AIInstructionSet {
+            name: "send troops to enemy base".into(),
+            phase: 7,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(PRODUCER_1_POSITION - vec2(100., 100.)),
+                    at(PRODUCER_1_POSITION + vec2(100., 100.)),
+                )),
+                AIInstructionType::Movement(vec![
+                    at(PRODUCER_1_POSITION).lerp(enemy_base, 0.5),
+                    enemy_base,
+                ]),
+            ],
+            ..default()
+        },
+        AIInstructionSet {
+            name: "create 5 warriors".into(),
+            phase: 7,
+            steps: vec![
+                AIInstructionType::Selection(Rect::from_corners(
+                    at(PRODUCER_1_POSITION),
+                    at(PRODUCER_1_POSITION),
+                )),
+                AIInstructionType::Produce {
+                    production: ProductionType::Warrior,
+                    count: 5,
+                },
+            ],
+            ..default()
+        },
+    ]
 }
 
 fn run_instruction(
     time: Res<Time>,
-    mut instruction_sets: ResMut<AIInstructionSets>,
+    mut ai_query: Query<&mut AIInstructionSets>,
     mut box_selection: EventWriter<BoxSelection>,
     mut set_unit_position: EventWriter<SetUnitPosition>,
+    mut clear_orders: EventWriter<ClearOrders>,
     mut place_construction_site: EventWriter<PlaceConstructionSite>,
     selected_units: Res<SelectedUnits>,
     selected_structures: Res<SelectedStructures>,
     mut idlers_query: Query<&mut Idle, With<Idle>>,
     mut movers_query: Query<&mut Moving, With<Unit>>,
     mut attempt_production_event: EventWriter<AttemptProductionIncrease>,
+    registry: Res<UnitRegistry>,
+    structures: Res<StructureRegistry>,
+    energy: Res<Energy>,
+    mut rng: ResMut<MatchRng>,
 ) {
-    //  allow some time between instructions
     let delta = time.delta_seconds();
-    instruction_sets.cooldown -= delta;
-    instruction_sets.force_forward -= delta;
 
-    if instruction_sets.cooldown > 0.0 {
-        return;
-    }
+    for mut instruction_sets in ai_query.iter_mut() {
+        let team = instruction_sets.team;
 
-    instruction_sets.cooldown = AI_STEP_COOLDOWN;
-    let forward_countdown = instruction_sets.force_forward;
-    let mut forwarded = false;
-
-    //  store high scope info
-    let current = instruction_sets.current_phase.clone();
-    let mut phase_ongoing = false;
-
-    for set in instruction_sets.sets.iter_mut() {
-        if !set.complete && set.phase == current {
-            phase_ongoing = true;
-            // info!("running set: {:?}", set.name);
-
-            //  perform step if no remaining dependants
-            if set.dependants.is_empty() {
-                //  execute step
-                let index = &set.current_step;
-                let step = &set.steps[*index];
-                // info!("running ai step: {:?}", step);
-
-                match &step {
-                    AIInstructionType::Selection(rect) => {
-                        box_selection.send(BoxSelection {
-                            rect: *rect,
-                            team: TeamType::CPU,
-                        });
-                    }
-                    AIInstructionType::Movement(position) => {
-                        //  set move orders
-                        set_unit_position.send(SetUnitPosition {
-                            position: *position + rand_adjustment(),
-                            direction: Vec2::ZERO,
-                            formation: Formation::Ringed,
-                            team: TeamType::CPU,
-                            ..default()
-                        });
-
-                        //  add dependants
-                        establish_moving_dependants(
-                            selected_units.entities.cpu.clone(),
-                            set,
-                            &mut movers_query,
-                        );
-                    }
-                    AIInstructionType::Build {
-                        position,
-                        structure,
-                        cost,
-                    } => {
-                        //  place site
-                        // info!("building at: {:?}", *position);
-                        place_construction_site.send(PlaceConstructionSite {
-                            structure_type: structure.clone(),
-                            position: *position,
-                            team: TeamType::CPU,
-                            effort: *cost,
-                        });
-
-                        //  ensure units move to build
-                        set_unit_position.send(SetUnitPosition {
-                            position: *position,
-                            direction: Vec2::ONE * 40.0,
-                            formation: Formation::Ringed,
-                            team: TeamType::CPU,
-                        });
-
-                        //  add dependants
-                        establish_idle_dependants(
-                            selected_units.entities.cpu.clone(),
-                            set,
-                            &mut idlers_query,
-                        );
-                    }
-                    AIInstructionType::Produce { production, count } => {
-                        let mut producing: bool = false;
-
-                        // info!(
-                        //     "selected structures: {:?}",
-                        //     selected_structures.entities.len()
-                        // );
-                        for _ in 0..*count {
-                            producing = true;
-
-                            attempt_production_event.send(AttemptProductionIncrease {
-                                production_type: production.clone(),
-                                team: TeamType::CPU,
+        //  allow some time between instructions
+        instruction_sets.cooldown -= delta;
+        instruction_sets.force_forward -= delta;
+
+        if instruction_sets.cooldown > 0.0 {
+            continue;
+        }
+
+        instruction_sets.cooldown = instruction_sets.step_cooldown;
+        let forward_countdown = instruction_sets.force_forward;
+        let mut forwarded = false;
+
+        //  store high scope info
+        let current = instruction_sets.current_phase.clone();
+        let mut phase_ongoing = false;
+
+        for set in instruction_sets.sets.iter_mut() {
+            if !set.complete && set.phase == current {
+                phase_ongoing = true;
+                // info!("running set: {:?}", set.name);
+
+                //  perform step if no remaining dependants
+                if set.dependants.is_empty() {
+                    //  execute step
+                    let index = &set.current_step;
+                    let step = &set.steps[*index];
+                    // info!("running ai step: {:?}", step);
+
+                    match &step {
+                        AIInstructionType::Selection(rect) => {
+                            box_selection.send(BoxSelection { rect: *rect, team });
+                        }
+                        AIInstructionType::Movement(points) => {
+                            let units = selected_units.entities.to_vec(&team);
+
+                            //  set move orders, spreading out only at the end of the route
+                            clear_orders.send(ClearOrders {
+                                units: units.clone(),
                             });
+                            for (index, position) in points.iter().enumerate() {
+                                let last = index + 1 == points.len();
+                                set_unit_position.send(SetUnitPosition {
+                                    position: if last {
+                                        *position + rand_adjustment(&mut rng)
+                                    } else {
+                                        *position
+                                    },
+                                    direction: Vec2::ZERO,
+                                    formation: Formation::Ringed,
+                                    team,
+                                    queued: index > 0,
+                                    //  armies fight their way to wherever they are sent
+                                    attack_move: true,
+                                });
+                            }
+
+                            //  add dependants
+                            establish_moving_dependants(units, set, &mut movers_query);
                         }
+                        AIInstructionType::Build {
+                            position,
+                            structure,
+                        } => {
+                            //  place site
+                            // info!("building at: {:?}", *position);
+                            place_construction_site.send(PlaceConstructionSite {
+                                structure_type: structure.clone(),
+                                position: *position,
+                                team,
+                                effort: structures.cost(structure),
+                                //	the ai has never paid for what it builds
+                                cost: 0.0,
+                            });
+
+                            //  ensure units move to build
+                            set_unit_position.send(SetUnitPosition {
+                                position: *position,
+                                direction: Vec2::ONE * 40.0,
+                                formation: Formation::Ringed,
+                                team,
+                                queued: false,
+                                attack_move: false,
+                            });
 
-                        if producing {
                             //  add dependants
                             establish_idle_dependants(
-                                selected_structures.entities.cpu.clone(),
+                                selected_units.entities.to_vec(&team),
                                 set,
                                 &mut idlers_query,
                             );
                         }
+                        AIInstructionType::Produce { production, count } => {
+                            let mut producing: bool = false;
+
+                            //  wait on the step until at least one can be paid for
+                            let cost = registry.cost(production);
+                            let affordable = if cost > 0.0 {
+                                (energy.get(&team) / cost).floor() as usize
+                            } else {
+                                *count
+                            };
+                            if affordable == 0 {
+                                continue;
+                            }
+
+                            // info!(
+                            //     "selected structures: {:?}",
+                            //     selected_structures.entities.len()
+                            // );
+                            for _ in 0..(*count).min(affordable) {
+                                producing = true;
+
+                                attempt_production_event.send(AttemptProductionIncrease {
+                                    production_type: production.clone(),
+                                    team,
+                                });
+                            }
+
+                            if producing {
+                                //  add dependants
+                                establish_idle_dependants(
+                                    selected_structures.entities.to_vec(&team),
+                                    set,
+                                    &mut idlers_query,
+                                );
+                            }
+                        }
                     }
                 }
-            }
 
-            //  check dependants
-            let mut removing: Vec<usize> = Vec::new();
-            for (index, dependancy) in set.dependants.iter().enumerate() {
-                let remove = match dependancy.expectation {
-                    DependancyExpectation::Idle(b) => {
-                        if let Ok(idle) = idlers_query.get(dependancy.entity) {
-                            idle.0 == b
-                        } else {
-                            false
+                //  check dependants
+                let mut removing: Vec<usize> = Vec::new();
+                for (index, dependancy) in set.dependants.iter().enumerate() {
+                    let remove = match dependancy.expectation {
+                        DependancyExpectation::Idle(b) => {
+                            if let Ok(idle) = idlers_query.get(dependancy.entity) {
+                                idle.0 == b
+                            } else {
+                                false
+                            }
                         }
-                    }
-                    DependancyExpectation::Moving(b) => {
-                        if let Ok(moving) = movers_query.get(dependancy.entity) {
-                            moving.0 == b
-                        } else {
-                            false
+                        DependancyExpectation::Moving(b) => {
+                            if let Ok(moving) = movers_query.get(dependancy.entity) {
+                                moving.0 == b
+                            } else {
+                                false
+                            }
                         }
+                    };
+
+                    if remove {
+                        removing.push(index);
                     }
-                };
+                }
 
-                if remove {
-                    removing.push(index);
+                let mut index_adjustment: usize = 0;
+                for &index in removing.iter() {
+                    set.dependants.swap_remove(index - index_adjustment);
+                    index_adjustment += 1;
                 }
-            }
 
-            let mut index_adjustment: usize = 0;
-            for &index in removing.iter() {
-                set.dependants.swap_remove(index - index_adjustment);
-                index_adjustment += 1;
-            }
+                if set.dependants.is_empty() || forward_countdown < 0.0 {
+                    //  next step or leave
+                    set.current_step += 1;
+                    if set.current_step == set.steps.len() {
+                        set.complete = true;
+                    }
 
-            if set.dependants.is_empty() || forward_countdown < 0.0 {
-                //  next step or leave
-                set.current_step += 1;
-                if set.current_step == set.steps.len() {
-                    set.complete = true;
+                    forwarded = true;
                 }
-
-                forwarded = true;
             }
         }
-    }
 
-    if !phase_ongoing {
-        instruction_sets.current_phase += 1;
+        if !phase_ongoing {
+            instruction_sets.current_phase += 1;
 
-        //  final instructions loop endlessly
-        if instruction_sets.current_phase == AI_FINAL_PHASE {
-            instruction_sets.current_phase = AI_RESET_PHASE;
+            //  final instructions loop endlessly
+            if instruction_sets.current_phase == AI_FINAL_PHASE {
+                instruction_sets.current_phase = AI_RESET_PHASE;
 
-            for set in instruction_sets.sets.iter_mut() {
-                set.complete = false;
-                set.current_step = 0;
+                for set in instruction_sets.sets.iter_mut() {
+                    set.complete = false;
+                    set.current_step = 0;
+                }
             }
         }
+
+        if forwarded {
+            instruction_sets.force_forward = AI_FORCE_FORWARD;
+        }
     }
+}
 
-    if forwarded {
-        instruction_sets.force_forward = AI_FORCE_FORWARD;
+//	a unit that cannot get where it was sent would otherwise hold up its step until forced forward
+fn forget_stranded_units(
+    mut failed: EventReader<PathFailed>,
+    mut ai_query: Query<&mut AIInstructionSets>,
+) {
+    for failure in failed.read() {
+        for mut instruction_sets in ai_query.iter_mut() {
+            for set in instruction_sets.sets.iter_mut() {
+                set.dependants
+                    .retain(|dependancy| dependancy.entity != failure.entity);
+            }
+        }
     }
 }
 
-fn rand_adjustment() -> Vec2 {
-    let mut rnd = rand::thread_rng();
+fn rand_adjustment(rng: &mut MatchRng) -> Vec2 {
     let range = 1000.0;
     vec2(
-        (rnd.gen::<f32>() - 0.5) * range,
-        (rnd.gen::<f32>() - 0.5) * range,
+        (rng.rng.gen::<f32>() - 0.5) * range,
+        (rng.rng.gen::<f32>() - 0.5) * range,
     )
 }
 
@@ -0,0 +1,528 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    combat::Turret,
+    conversion::FaithAura,
+    producer::{ProductionType, Supply},
+    structure::StructureType,
+    teams::{Players, TeamType},
+};
+
+pub const UNIT_ARCHETYPES_PATH: &str = "archetypes/default.units.ron";
+pub const STRUCTURE_ARCHETYPES_PATH: &str = "archetypes/default.structures.ron";
+pub const BALANCE_PATH: &str = "archetypes/default.balance.ron";
+
+pub struct ArchetypePlugin;
+
+impl Plugin for ArchetypePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<UnitArchetypes>()
+            .init_asset::<StructureArchetypes>()
+            .init_asset::<Balance>()
+            .init_asset_loader::<ArchetypeLoader<UnitArchetypes>>()
+            .init_asset_loader::<ArchetypeLoader<StructureArchetypes>>()
+            .init_asset_loader::<ArchetypeLoader<Balance>>()
+            .add_systems(Startup, load_archetypes)
+            .add_systems(
+                Update,
+                (
+                    apply_unit_archetypes,
+                    apply_structure_archetypes,
+                    apply_balance,
+                ),
+            )
+            .add_event::<UnitArchetypesChanged>()
+            .add_event::<StructureArchetypesChanged>()
+            .insert_resource(UnitRegistry::default())
+            .insert_resource(StructureRegistry::default())
+            .insert_resource(AppliedArchetypes::default())
+            .insert_resource(Balance::default());
+    }
+}
+
+/// The local player's units look good and everyone else's look evil,
+/// unless the archetype gives every team in the match a sprite of its own
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnitSprites {
+    pub local: String,
+    pub other: String,
+    #[serde(default)]
+    pub teams: Vec<String>,
+}
+
+impl UnitSprites {
+    pub fn path(&self, team: &TeamType, players: &Players) -> String {
+        if self.teams.len() >= players.list.len() {
+            if let Some(path) = self.teams.get(team.0) {
+                return path.clone();
+            }
+        }
+
+        if players.is_local(team) {
+            self.local.clone()
+        } else {
+            self.other.clone()
+        }
+    }
+}
+
+/// Everything needed to spawn and produce one kind of unit.
+/// A unit gets the role components for whichever of effort, strength and persuasion it has.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnitArchetype {
+    pub kind: ProductionType,
+    pub name: String,
+    pub sprites: UnitSprites,
+    pub size: Vec2,
+    pub health: f32,
+    pub faith: f32,
+    pub speed: f32,
+    #[serde(default)]
+    pub cost: f32,
+    #[serde(default)]
+    pub build_time: f32,
+    /// whether producers offer it, rather than it only coming with the map
+    pub producible: bool,
+    #[serde(default)]
+    pub sight: Option<f32>,
+    #[serde(default)]
+    pub detection: Option<f32>,
+    #[serde(default)]
+    pub effort: Option<f32>,
+    #[serde(default)]
+    pub strength: Option<f32>,
+    #[serde(default)]
+    pub persuasion: Option<f32>,
+}
+
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone, Debug)]
+pub struct UnitArchetypes {
+    pub units: Vec<UnitArchetype>,
+}
+
+impl ArchetypeFile for UnitArchetypes {
+    const EXTENSIONS: &'static [&'static str] = &["units.ron"];
+
+    fn validate(&self) -> Result<(), ArchetypeError> {
+        for (index, unit) in self.units.iter().enumerate() {
+            if self.units[..index].iter().any(|u| u.kind == unit.kind) {
+                return Err(ArchetypeError::Duplicate(unit.name.clone()));
+            }
+            if unit.producible && unit.build_time <= 0.0 {
+                return Err(ArchetypeError::BuildTime(unit.name.clone()));
+            }
+            if unit.kind == ProductionType::None {
+                return Err(ArchetypeError::NoKind(unit.name.clone()));
+            }
+            if unit.sprites.local.is_empty() || unit.sprites.other.is_empty() {
+                return Err(ArchetypeError::Sprites(unit.name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Everything needed to construct one kind of structure.
+/// A structure gets the components for whichever of the optional behaviours it has.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StructureArchetype {
+    pub kind: StructureType,
+    pub name: String,
+    pub sprite: String,
+    /// the footprint, used for selection, placement and the navmesh obstacle
+    pub size: Vec2,
+    pub health: f32,
+    /// paid up front, and worked off by the builders as construction effort
+    pub cost: f32,
+    /// whether workers can place it
+    pub buildable: bool,
+    #[serde(default)]
+    pub sight: Option<f32>,
+    #[serde(default)]
+    pub generator: bool,
+    #[serde(default)]
+    pub producer: bool,
+    /// how quickly a producer works through build time
+    #[serde(default = "default_production_rate")]
+    pub production_rate: f32,
+    #[serde(default)]
+    pub turret: Option<Turret>,
+    #[serde(default)]
+    pub faith_aura: Option<FaithAura>,
+    #[serde(default)]
+    pub supply: Option<Supply>,
+}
+
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone, Debug)]
+pub struct StructureArchetypes {
+    pub structures: Vec<StructureArchetype>,
+}
+
+impl ArchetypeFile for StructureArchetypes {
+    const EXTENSIONS: &'static [&'static str] = &["structures.ron"];
+
+    fn validate(&self) -> Result<(), ArchetypeError> {
+        for (index, structure) in self.structures.iter().enumerate() {
+            if self.structures[..index]
+                .iter()
+                .any(|s| s.kind == structure.kind)
+            {
+                return Err(ArchetypeError::Duplicate(structure.name.clone()));
+            }
+            if structure.kind.0.is_empty() {
+                return Err(ArchetypeError::NoKind(structure.name.clone()));
+            }
+            if structure.size.x <= 0.0 || structure.size.y <= 0.0 {
+                return Err(ArchetypeError::Footprint(structure.name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn default_production_rate() -> f32 {
+    1.0
+}
+
+/// Tuning that applies to every unit and structure, rather than to one archetype
+#[derive(Asset, TypePath, Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct Balance {
+    pub attack_range: f32,
+    /// seconds between attacks, for warriors and turrets alike
+    pub attack_rate: f32,
+    pub conversion_range: f32,
+    /// seconds between persuasions
+    pub conversion_rate: f32,
+    /// construction effort each working unit puts in every second
+    pub construction_boost: f32,
+    /// how many units a team can field before it needs supply structures
+    pub base_supply: usize,
+    pub starting_energy: f32,
+}
+
+impl Default for Balance {
+    fn default() -> Self {
+        Self {
+            attack_range: 40.0,
+            attack_rate: 0.4,
+            conversion_range: 60.0,
+            conversion_rate: 0.4,
+            construction_boost: 20.5,
+            base_supply: 30,
+            starting_energy: 100.0,
+        }
+    }
+}
+
+impl ArchetypeFile for Balance {
+    const EXTENSIONS: &'static [&'static str] = &["balance.ron"];
+
+    fn validate(&self) -> Result<(), ArchetypeError> {
+        if self.attack_rate <= 0.0 || self.conversion_rate <= 0.0 {
+            return Err(ArchetypeError::Rate);
+        }
+
+        Ok(())
+    }
+}
+
+/// A file of archetypes that is checked as it loads
+pub trait ArchetypeFile: Asset + DeserializeOwned {
+    const EXTENSIONS: &'static [&'static str];
+
+    fn validate(&self) -> Result<(), ArchetypeError>;
+}
+
+#[derive(Error, Debug)]
+pub enum ArchetypeError {
+    #[error("{0} is defined more than once")]
+    Duplicate(String),
+    #[error("{0} can be produced but has no build time")]
+    BuildTime(String),
+    #[error("{0} has no kind")]
+    NoKind(String),
+    #[error("{0} has no sprites")]
+    Sprites(String),
+    #[error("{0} has no footprint")]
+    Footprint(String),
+    #[error("attack and conversion rates must be above zero")]
+    Rate,
+}
+
+#[derive(Error, Debug)]
+pub enum ArchetypeLoaderError {
+    #[error("could not read archetype file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse archetype file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid archetype: {0}")]
+    Invalid(#[from] ArchetypeError),
+}
+
+pub struct ArchetypeLoader<A: ArchetypeFile>(PhantomData<A>);
+
+impl<A: ArchetypeFile> Default for ArchetypeLoader<A> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<A: ArchetypeFile> AssetLoader for ArchetypeLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = ArchetypeLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let archetypes: A = ron::de::from_bytes(&bytes)?;
+        archetypes.validate()?;
+
+        Ok(archetypes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        A::EXTENSIONS
+    }
+}
+
+/// Every unit archetype, copied out of the archetype file once it has loaded
+#[derive(Resource, Default)]
+pub struct UnitRegistry {
+    archetypes: Vec<UnitArchetype>,
+}
+
+impl UnitRegistry {
+    #[cfg(test)]
+    pub fn new(archetypes: Vec<UnitArchetype>) -> Self {
+        Self { archetypes }
+    }
+
+    pub fn get(&self, kind: &ProductionType) -> Option<&UnitArchetype> {
+        self.archetypes
+            .iter()
+            .find(|archetype| archetype.kind == *kind)
+    }
+
+    /// in the order they are listed in the file, which is also the order of the ui buttons
+    pub fn producible(&self) -> impl Iterator<Item = &UnitArchetype> {
+        self.archetypes
+            .iter()
+            .filter(|archetype| archetype.producible)
+    }
+
+    pub fn cost(&self, kind: &ProductionType) -> f32 {
+        self.get(kind).map_or(0.0, |archetype| archetype.cost)
+    }
+}
+
+/// Every structure archetype, copied out of the archetype file once it has loaded
+#[derive(Resource, Default)]
+pub struct StructureRegistry {
+    archetypes: Vec<StructureArchetype>,
+}
+
+impl StructureRegistry {
+    #[cfg(test)]
+    pub fn new(archetypes: Vec<StructureArchetype>) -> Self {
+        Self { archetypes }
+    }
+
+    pub fn get(&self, kind: &StructureType) -> Option<&StructureArchetype> {
+        self.archetypes
+            .iter()
+            .find(|archetype| archetype.kind == *kind)
+    }
+
+    /// in the order they are listed in the file, which is also the order of the ui buttons
+    pub fn buildable(&self) -> impl Iterator<Item = &StructureArchetype> {
+        self.archetypes
+            .iter()
+            .filter(|archetype| archetype.buildable)
+    }
+
+    pub fn cost(&self, kind: &StructureType) -> f32 {
+        self.get(kind).map_or(0.0, |archetype| archetype.cost)
+    }
+}
+
+#[derive(Resource)]
+pub struct ArchetypeHandles {
+    pub units: Handle<UnitArchetypes>,
+    pub structures: Handle<StructureArchetypes>,
+    pub balance: Handle<Balance>,
+}
+
+impl ArchetypeHandles {
+    pub fn is_loaded(&self, asset_server: &AssetServer) -> bool {
+        asset_server.is_loaded_with_dependencies(&self.units)
+            && asset_server.is_loaded_with_dependencies(&self.structures)
+            && asset_server.is_loaded_with_dependencies(&self.balance)
+    }
+}
+
+/// Which archetype files have been copied into their registries,
+/// which happens a frame after the asset server reports them loaded
+#[derive(Resource, Default)]
+pub struct AppliedArchetypes {
+    units: bool,
+    structures: bool,
+    balance: bool,
+}
+
+/// Whether everything a match is spawned from has loaded and been applied
+pub fn archetypes_ready(
+    handles: Option<Res<ArchetypeHandles>>,
+    asset_server: Res<AssetServer>,
+    applied: Res<AppliedArchetypes>,
+) -> bool {
+    handles.is_some_and(|handles| handles.is_loaded(&asset_server))
+        && applied.units
+        && applied.structures
+        && applied.balance
+}
+
+/// Sent whenever the unit registry is replaced, carrying what it held before
+/// so live units can be rescaled against their old archetype
+#[derive(Event)]
+pub struct UnitArchetypesChanged {
+    pub previous: Vec<UnitArchetype>,
+}
+
+/// Sent whenever the structure registry is replaced, carrying what it held before
+#[derive(Event)]
+pub struct StructureArchetypesChanged {
+    pub previous: Vec<StructureArchetype>,
+}
+
+impl UnitArchetypesChanged {
+    pub fn previous(&self, kind: &ProductionType) -> Option<&UnitArchetype> {
+        self.previous
+            .iter()
+            .find(|archetype| archetype.kind == *kind)
+    }
+}
+
+impl StructureArchetypesChanged {
+    pub fn previous(&self, kind: &StructureType) -> Option<&StructureArchetype> {
+        self.previous
+            .iter()
+            .find(|archetype| archetype.kind == *kind)
+    }
+}
+
+/// scales a live value by how much its archetype value changed,
+/// so damage taken or faith lost carries over a reload
+pub fn rescale(value: f32, previous: f32, current: f32) -> f32 {
+    if previous == 0.0 {
+        current
+    } else {
+        value * current / previous
+    }
+}
+
+//  the file watcher reports edits as modified, so treat them the same as the first load
+fn is_loaded<A: Asset>(event: &AssetEvent<A>, handle: &Handle<A>) -> bool {
+    match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == handle.id()
+        }
+        _ => false,
+    }
+}
+
+fn load_archetypes(asset_server: Res<AssetServer>, mut commands: Commands) {
+    commands.insert_resource(ArchetypeHandles {
+        units: asset_server.load(UNIT_ARCHETYPES_PATH),
+        structures: asset_server.load(STRUCTURE_ARCHETYPES_PATH),
+        balance: asset_server.load(BALANCE_PATH),
+    });
+}
+
+fn apply_unit_archetypes(
+    mut asset_events: EventReader<AssetEvent<UnitArchetypes>>,
+    handles: Option<Res<ArchetypeHandles>>,
+    archetypes: Res<Assets<UnitArchetypes>>,
+    mut registry: ResMut<UnitRegistry>,
+    mut changed: EventWriter<UnitArchetypesChanged>,
+    mut applied: ResMut<AppliedArchetypes>,
+) {
+    let Some(handles) = handles else {
+        return;
+    };
+
+    for event in asset_events.read() {
+        if !is_loaded(event, &handles.units) {
+            continue;
+        }
+
+        if let Some(loaded) = archetypes.get(&handles.units) {
+            let previous = std::mem::replace(&mut registry.archetypes, loaded.units.clone());
+            changed.send(UnitArchetypesChanged { previous });
+            applied.units = true;
+        }
+    }
+}
+
+fn apply_structure_archetypes(
+    mut asset_events: EventReader<AssetEvent<StructureArchetypes>>,
+    handles: Option<Res<ArchetypeHandles>>,
+    archetypes: Res<Assets<StructureArchetypes>>,
+    mut registry: ResMut<StructureRegistry>,
+    mut changed: EventWriter<StructureArchetypesChanged>,
+    mut applied: ResMut<AppliedArchetypes>,
+) {
+    let Some(handles) = handles else {
+        return;
+    };
+
+    for event in asset_events.read() {
+        if !is_loaded(event, &handles.structures) {
+            continue;
+        }
+
+        if let Some(loaded) = archetypes.get(&handles.structures) {
+            let previous = std::mem::replace(&mut registry.archetypes, loaded.structures.clone());
+            changed.send(StructureArchetypesChanged { previous });
+            applied.structures = true;
+        }
+    }
+}
+
+fn apply_balance(
+    mut asset_events: EventReader<AssetEvent<Balance>>,
+    handles: Option<Res<ArchetypeHandles>>,
+    balances: Res<Assets<Balance>>,
+    mut balance: ResMut<Balance>,
+    mut applied: ResMut<AppliedArchetypes>,
+) {
+    let Some(handles) = handles else {
+        return;
+    };
+
+    for event in asset_events.read() {
+        if !is_loaded(event, &handles.balance) {
+            continue;
+        }
+
+        if let Some(loaded) = balances.get(&handles.balance) {
+            *balance = loaded.clone();
+            applied.balance = true;
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use bevy::prelude::*;
-
-const ATTACK_RANGE: f32 = 40.0;
-const ATTACK_RATE: f32 = 0.4;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    movement::{Formation, SetUnitPosition},
+    archetypes::Balance,
+    diplomacy::Diplomacy,
+    movement::{
+        abandon_unreachable_prey, set_moveable_location, Formation, Pursuit, SetUnitPosition,
+    },
+    orders::Holding,
     schedule::InGameSet,
     teams::Team,
     warrior::Warrior,
@@ -18,7 +21,16 @@ impl Plugin for CombatPlugin {
             Update,
             (
                 assign_attackers,
-                (break_attack_pursuit, pursue_prey).chain(),
+                (
+                    (
+                        unassign_attackers,
+                        break_attack_pursuit,
+                        abandon_unreachable_prey::<AttackPursuit>,
+                    ),
+                    pursue_prey.before(set_moveable_location),
+                )
+                    .chain(),
+                fire_turrets,
                 attack_unit,
             )
                 .in_set(InGameSet::EntityUpdates),
@@ -45,9 +57,24 @@ pub struct AttackPursuit {
     pub prey: Entity,
 }
 
+impl Pursuit for AttackPursuit {
+    fn prey(&self) -> Entity {
+        self.prey
+    }
+}
+
 #[derive(Component)]
 pub struct Health(pub f32);
 
+/// Fires on the closest enemy in range without ever moving, for defensive structures
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Turret {
+    pub strength: f32,
+    pub range: f32,
+    #[serde(skip)]
+    pub cooldown: f32,
+}
+
 #[derive(Event)]
 pub struct Attack {
     pub victim: Entity,
@@ -84,6 +111,22 @@ pub fn assign_attackers(mut assignments: EventReader<AssignAttackPursuit>, mut c
     }
 }
 
+fn unassign_attackers(
+    predators: Query<(Entity, &AttackPursuit, &Team), With<AttackPursuit>>,
+    victims: Query<&Team, With<Health>>,
+    diplomacy: Res<Diplomacy>,
+    mut commands: Commands,
+) {
+    for (entity, attack_pursuit, predator_team) in predators.iter() {
+        if let Ok(prey_team) = victims.get(attack_pursuit.prey) {
+            //	stop attacking anyone who was converted or has made a truce
+            if !diplomacy.is_hostile(&predator_team.0, &prey_team.0) {
+                commands.entity(entity).remove::<AttackPursuit>();
+            }
+        }
+    }
+}
+
 fn break_attack_pursuit(mut event: EventReader<BreakAttackPursuit>, mut commands: Commands) {
     for break_attack in event.read() {
         for &entity in break_attack.entities.iter() {
@@ -92,18 +135,25 @@ fn break_attack_pursuit(mut event: EventReader<BreakAttackPursuit>, mut commands
     }
 }
 
+type PredatorData = (
+    Entity,
+    &'static mut AttackPursuit,
+    &'static Transform,
+    &'static Warrior,
+    &'static Team,
+    Has<Holding>,
+);
+
 fn pursue_prey(
-    mut predators: Query<
-        (Entity, &mut AttackPursuit, &Transform, &Warrior, &Team),
-        With<AttackPursuit>,
-    >,
+    mut predators: Query<PredatorData, With<AttackPursuit>>,
     victims: Query<&Transform, With<Health>>,
+    balance: Res<Balance>,
     time: Res<Time>,
     mut movement_writer: EventWriter<SetUnitPosition>,
     mut attack_events: EventWriter<Attack>,
     mut commands: Commands,
 ) {
-    for (predetor_entity, mut attack_pursuit, predator_transform, warrior, team) in
+    for (predetor_entity, mut attack_pursuit, predator_transform, warrior, team, holding) in
         predators.iter_mut()
     {
         attack_pursuit.cooldown -= time.delta_seconds();
@@ -114,7 +164,10 @@ fn pursue_prey(
                     .translation
                     .distance(victim_transform.translation);
 
-                if dist > ATTACK_RANGE {
+                if dist > balance.attack_range && holding {
+                    //	holding units let anything out of reach go
+                    commands.entity(predetor_entity).remove::<AttackPursuit>();
+                } else if dist > balance.attack_range {
                     let attack_direction = (predator_transform.translation
                         - victim_transform.translation)
                         .normalize()
@@ -123,7 +176,9 @@ fn pursue_prey(
                         position: victim_transform.translation.xy(),
                         direction: attack_direction,
                         formation: Formation::Ringed,
-                        team: team.0.clone(),
+                        team: team.0,
+                        queued: false,
+                        attack_move: true,
                     });
                 } else {
                     attack_events.send(Attack {
@@ -132,7 +187,7 @@ fn pursue_prey(
                     });
                 }
 
-                attack_pursuit.cooldown = ATTACK_RATE;
+                attack_pursuit.cooldown = balance.attack_rate;
             } else {
                 //  If we cannot find prey, then it is most likely dead
                 commands.entity(predetor_entity).remove::<AttackPursuit>();
@@ -141,6 +196,42 @@ fn pursue_prey(
     }
 }
 
+fn fire_turrets(
+    mut turrets: Query<(Entity, &mut Turret, &GlobalTransform, &Team)>,
+    targets: Query<(Entity, &GlobalTransform, &Team), With<Health>>,
+    diplomacy: Res<Diplomacy>,
+    balance: Res<Balance>,
+    time: Res<Time>,
+    mut attack_events: EventWriter<Attack>,
+) {
+    for (turret_entity, mut turret, turret_transform, turret_team) in turrets.iter_mut() {
+        turret.cooldown -= time.delta_seconds();
+
+        if turret.cooldown > 0.0 {
+            continue;
+        }
+
+        let position = turret_transform.translation().xy();
+        let closest = targets
+            .iter()
+            .filter(|(entity, _, team)| {
+                *entity != turret_entity && diplomacy.is_hostile(&turret_team.0, &team.0)
+            })
+            .map(|(entity, transform, _)| (entity, transform.translation().xy().distance(position)))
+            .filter(|(_, distance)| *distance <= turret.range)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((victim, _)) = closest {
+            attack_events.send(Attack {
+                victim,
+                value: turret.strength,
+            });
+
+            turret.cooldown = balance.attack_rate;
+        }
+    }
+}
+
 fn attack_unit(mut attack_events: EventReader<Attack>, mut victim_health: Query<&mut Health>) {
     for attack in attack_events.read() {
         if let Ok(mut health) = victim_health.get_mut(attack.victim) {
@@ -156,3 +247,70 @@ fn destroy_unhealthy_units(query: Query<(Entity, &Health), With<Health>>, mut co
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        nav_agent::PathFailed,
+        producer::ProductionType,
+        teams::TeamType,
+        testing::{Scenario, TestApp},
+    };
+
+    #[test]
+    fn warrior_kills_worker_in_range() {
+        let mut test = TestApp::new(CombatPlugin);
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Warrior, TeamType(0), Vec2::new(0.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(20.0, 0.0)),
+            ],
+            ..default()
+        });
+        let (warrior, worker) = (spawned.units[0], spawned.units[1]);
+
+        test.send(AssignAttackPursuit {
+            predators: vec![warrior],
+            prey: worker,
+        });
+        test.step_seconds(1.0);
+        test.assert_health_below(worker, 24.0);
+
+        //	24 health at 2.5 a hit, with a hit every 0.4 seconds
+        test.step_seconds(4.0);
+        test.assert_despawned(worker);
+    }
+
+    #[test]
+    fn only_a_failed_chase_gives_up_the_prey() {
+        let mut test = TestApp::new(CombatPlugin);
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Warrior, TeamType(0), Vec2::new(0.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(20.0, 0.0)),
+            ],
+            ..default()
+        });
+        let (warrior, worker) = (spawned.units[0], spawned.units[1]);
+
+        test.send(AssignAttackPursuit {
+            predators: vec![warrior],
+            prey: worker,
+        });
+        test.step(1);
+        test.send(PathFailed {
+            entity: warrior,
+            location: Vec2::new(2000.0, 0.0),
+        });
+        test.step(1);
+        assert!(test.app.world().get::<AttackPursuit>(warrior).is_some());
+
+        test.send(PathFailed {
+            entity: warrior,
+            location: Vec2::new(50.0, 0.0),
+        });
+        test.step(1);
+        assert!(test.app.world().get::<AttackPursuit>(warrior).is_none());
+    }
+}
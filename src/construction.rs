@@ -1,27 +1,27 @@
 use bevy::{
+    ecs::entity::EntityHashSet,
     math::bounding::{Aabb2d, IntersectsVolume},
     prelude::*,
     render::primitives::Aabb,
 };
-use vleue_navigator::prelude::PrimitiveObstacle;
 
 use crate::{
     ai::Idle,
+    archetypes::{Balance, StructureArchetype, StructureRegistry},
     currency::Energy,
+    fog::Sight,
     inputs::{BuildSelection, MousePosition},
+    match_setup::MatchEntity,
     movement::{Formation, SetUnitPosition},
     nav_agent::Obstacle,
     schedule::InGameSet,
     selectable::{Selectable, SelectedUnits, SelectionStateChanged, SelectionType},
-    structure::{
-        PlaceStructure, StructureType, PRODUCER_ASSET_PATH, SELECTION_SIZE,
-        SIMPLE_SHRINE_ASSET_PATH,
-    },
-    teams::TeamType,
+    spatial::SpatialIndex,
+    structure::{footprint_obstacle, PlaceStructure, StructureType},
+    teams::{Team, TeamType},
     unit::Unit,
 };
 
-const CONSTRUCTION_BOOST: f32 = 20.5;
 pub const CONSTRUCTION_RANGE: f32 = 90.;
 const BUILD_APPROVED_COLOR: Color = Color::linear_rgba(0.1, 0.7, 0.0, 0.4);
 const BUILD_DENIED_COLOR: Color = Color::linear_rgba(0.7, 0.1, 0.0, 0.4);
@@ -34,7 +34,7 @@ impl Plugin for ConstructionPlugin {
             Update,
             (
                 move_construction_silhouette,
-                (attempt_construction_placement, place_construction_site).chain(),
+                place_construction_site,
                 assign_new_workers,
                 (set_assigned_units, set_working_units).chain(),
                 increment_effort,
@@ -44,6 +44,11 @@ impl Plugin for ConstructionPlugin {
                 .chain()
                 .in_set(InGameSet::EntityUpdates),
         )
+        //	turning a click into a site is part of the player's command, so it is settled before replays record it
+        .add_systems(
+            Update,
+            attempt_construction_placement.in_set(InGameSet::UserInput),
+        )
         .add_event::<AttemptSitePlacement>()
         .add_event::<PlaceConstructionSite>()
         .add_event::<AssignConstructionWorkers>();
@@ -51,18 +56,21 @@ impl Plugin for ConstructionPlugin {
 }
 
 #[derive(Component)]
-pub struct ConstructionSilhouette {}
+pub struct ConstructionSilhouette {
+    size: Vec2,
+}
 
 #[derive(Component)]
 pub struct Intersects(pub bool);
 
 #[derive(Component)]
 pub struct ConstructionSite {
-    structure_type: StructureType,
-    team: TeamType,
-    effort: f32,
-    assigned_units: Vec<Entity>,
-    working_units: Vec<Entity>,
+    pub structure_type: StructureType,
+    pub team: TeamType,
+    /// work left before the structure is finished
+    pub effort: f32,
+    pub assigned_units: Vec<Entity>,
+    pub working_units: Vec<Entity>,
 }
 
 #[derive(Event)]
@@ -77,6 +85,8 @@ pub struct PlaceConstructionSite {
     pub team: TeamType,
     pub position: Vec2,
     pub effort: f32,
+    /// taken from the team's energy as the site is placed
+    pub cost: f32,
 }
 
 #[derive(Event)]
@@ -89,6 +99,7 @@ fn display_construction_silhouette(
     mut selection_state_updated: EventReader<SelectionStateChanged>,
     mut silhouettes: Query<Entity, With<ConstructionSilhouette>>,
     build_selection: Res<BuildSelection>,
+    structures: Res<StructureRegistry>,
     asset_server: Res<AssetServer>,
     mut commands: Commands,
 ) {
@@ -99,22 +110,25 @@ fn display_construction_silhouette(
         }
 
         if event.new_type == SelectionType::Construction {
-            let texture: Handle<Image> = asset_server.load(match build_selection.structure_type {
-                StructureType::SimpleShrine => SIMPLE_SHRINE_ASSET_PATH,
-                StructureType::Producer => PRODUCER_ASSET_PATH,
-            });
+            let Some(archetype) = structures.get(&build_selection.structure_type) else {
+                continue;
+            };
 
             commands.spawn((
                 SpriteBundle {
-                    texture,
+                    texture: asset_server.load(&archetype.sprite),
                     sprite: Sprite {
                         color: Color::linear_rgba(0.1, 0.1, 0.1, 0.2),
+                        custom_size: Some(archetype.size),
                         ..default()
                     },
                     ..default()
                 },
-                ConstructionSilhouette {},
+                ConstructionSilhouette {
+                    size: archetype.size,
+                },
                 Intersects(false),
+                MatchEntity,
             ));
         }
     }
@@ -131,15 +145,17 @@ fn move_construction_silhouette(
 
 //  docs: https://bevyengine.org/examples-webgpu/2d-rendering/bounding-2d/
 fn display_site_validity(
-    mut silhouettes: Query<
-        (&mut Sprite, &mut Intersects, &GlobalTransform),
-        With<ConstructionSilhouette>,
-    >,
+    mut silhouettes: Query<(
+        &mut Sprite,
+        &mut Intersects,
+        &GlobalTransform,
+        &ConstructionSilhouette,
+    )>,
     obstacles: Query<(&GlobalTransform, &Aabb), With<Obstacle>>,
 ) {
-    for (mut sprite, mut intersects, silhouette_transform) in silhouettes.iter_mut() {
+    for (mut sprite, mut intersects, silhouette_transform, silhouette) in silhouettes.iter_mut() {
         let center = silhouette_transform.translation().xy();
-        let silhouette_aabb2d = Aabb2d::new(center, SELECTION_SIZE / 2.0);
+        let silhouette_aabb2d = Aabb2d::new(center, silhouette.size / 2.0);
 
         let mut hits: bool = false;
         for (obstacle_transform, obstacle_aabb) in obstacles.iter() {
@@ -167,7 +183,7 @@ fn display_site_validity(
 fn attempt_construction_placement(
     mut placement_attempt: EventReader<AttemptSitePlacement>,
     silhouettes: Query<&Intersects, With<ConstructionSilhouette>>,
-    mut energy: ResMut<Energy>,
+    energy: Res<Energy>,
     build_selection: Res<BuildSelection>,
     mut place_construction_site: EventWriter<PlaceConstructionSite>,
     mut movement_writer: EventWriter<SetUnitPosition>,
@@ -175,13 +191,12 @@ fn attempt_construction_placement(
     for attempt in placement_attempt.read() {
         for intersects in silhouettes.iter() {
             if !intersects.0 && energy.get(&attempt.team) > build_selection.cost {
-                energy.add(&attempt.team, -build_selection.cost);
-
                 place_construction_site.send(PlaceConstructionSite {
-                    team: attempt.team.clone(),
+                    team: attempt.team,
                     structure_type: build_selection.structure_type.clone(),
                     position: attempt.position,
                     effort: build_selection.cost,
+                    cost: build_selection.cost,
                 });
 
                 //  ensure units move to build
@@ -189,57 +204,82 @@ fn attempt_construction_placement(
                     position: attempt.position,
                     direction: Vec2::ONE * (CONSTRUCTION_RANGE - 50.0),
                     formation: Formation::Ringed,
-                    team: attempt.team.clone(),
+                    team: attempt.team,
+                    queued: false,
+                    attack_move: false,
                 });
             }
         }
     }
 }
 
+/// Spawns an unfinished structure, which becomes the real one once its effort is worked off
+pub fn spawn_construction_site(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    archetype: &StructureArchetype,
+    position: Vec2,
+    site: ConstructionSite,
+) -> Entity {
+    let team = site.team;
+    let mut spawned = commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load(&archetype.sprite),
+            sprite: Sprite {
+                color: Color::linear_rgba(0.0, 0.1, 0.1, 0.6),
+                custom_size: Some(archetype.size),
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(0.0)),
+            ..default()
+        },
+        footprint_obstacle(archetype.size),
+        site,
+        Selectable {
+            size: archetype.size,
+        },
+        Team(team),
+        MatchEntity,
+        Name::new("ConstructionSite"),
+    ));
+
+    if let Some(range) = archetype.sight {
+        spawned.insert(Sight { range });
+    }
+
+    spawned.id()
+}
+
 fn place_construction_site(
     mut placement_reader: EventReader<PlaceConstructionSite>,
     selected_units: Res<SelectedUnits>,
+    structures: Res<StructureRegistry>,
     asset_server: Res<AssetServer>,
+    mut energy: ResMut<Energy>,
     mut commands: Commands,
 ) {
     for placement in placement_reader.read() {
-        let texture: Handle<Image> = asset_server.load(match placement.structure_type {
-            StructureType::SimpleShrine => SIMPLE_SHRINE_ASSET_PATH,
-            StructureType::Producer => PRODUCER_ASSET_PATH,
-        });
+        let Some(archetype) = structures.get(&placement.structure_type) else {
+            warn!("no archetype for {:?}", placement.structure_type);
+            continue;
+        };
 
-        let pos_3d = placement.position.extend(0.0);
+        energy.add(&placement.team, -placement.cost);
 
         // info!("assigning workers: {:?}", selected_units.entities.len());
-        commands.spawn((
-            SpriteBundle {
-                texture,
-                sprite: Sprite {
-                    color: Color::linear_rgba(0.0, 0.1, 0.1, 0.6),
-                    ..default()
-                },
-                transform: Transform::from_translation(pos_3d),
-                ..default()
-            },
-            PrimitiveObstacle::Rectangle(Rectangle::from_corners(
-                Vec2::new(-SELECTION_SIZE.x, -SELECTION_SIZE.y) / 2.0,
-                Vec2::new(SELECTION_SIZE.x, SELECTION_SIZE.y) / 2.0,
-            )),
+        spawn_construction_site(
+            &mut commands,
+            &asset_server,
+            archetype,
+            placement.position,
             ConstructionSite {
                 structure_type: placement.structure_type.clone(),
-                team: placement.team.clone(),
+                team: placement.team,
                 effort: placement.effort,
-                assigned_units: match placement.team {
-                    TeamType::Human => selected_units.entities.human.clone(),
-                    TeamType::CPU => selected_units.entities.cpu.clone(),
-                },
+                assigned_units: selected_units.entities.to_vec(&placement.team),
                 working_units: Vec::new(),
             },
-            Selectable {
-                size: SELECTION_SIZE,
-            },
-            Name::new("ConstructionSite"),
-        ));
+        );
     }
 }
 
@@ -258,21 +298,19 @@ fn assign_new_workers(
 
 fn set_assigned_units(
     mut sites: Query<(&Transform, &mut ConstructionSite)>,
-    units: Query<&Transform, With<Unit>>,
+    units: Query<(), With<Unit>>,
+    spatial: Res<SpatialIndex>,
 ) {
     for (site_transform, mut site) in sites.iter_mut() {
         let mut swapping: Vec<(usize, Entity)> = vec![];
+        let nearby: EntityHashSet = spatial
+            .within(site_transform.translation.xy(), CONSTRUCTION_RANGE)
+            .collect();
 
         //	store all the working units far enough to swap
         for (index, &entity) in site.working_units.iter().enumerate() {
-            if let Ok(unit_transform) = units.get(entity) {
-                if site_transform
-                    .translation
-                    .distance(unit_transform.translation)
-                    > CONSTRUCTION_RANGE
-                {
-                    swapping.push((index, entity));
-                }
+            if units.contains(entity) && !nearby.contains(&entity) {
+                swapping.push((index, entity));
             }
         }
 
@@ -286,21 +324,19 @@ fn set_assigned_units(
 
 fn set_working_units(
     mut sites: Query<(&Transform, &mut ConstructionSite)>,
-    units: Query<&Transform, With<Unit>>,
+    units: Query<(), With<Unit>>,
+    spatial: Res<SpatialIndex>,
 ) {
     for (site_transform, mut site) in sites.iter_mut() {
         let mut swapping: Vec<(usize, Entity)> = vec![];
+        let nearby: EntityHashSet = spatial
+            .within(site_transform.translation.xy(), CONSTRUCTION_RANGE)
+            .collect();
 
         //	store all the assigned units close enough to swap
         for (index, &entity) in site.assigned_units.iter().enumerate() {
-            if let Ok(unit_transform) = units.get(entity) {
-                if site_transform
-                    .translation
-                    .distance(unit_transform.translation)
-                    < CONSTRUCTION_RANGE
-                {
-                    swapping.push((index, entity));
-                }
+            if units.contains(entity) && nearby.contains(&entity) {
+                swapping.push((index, entity));
             }
         }
 
@@ -312,9 +348,14 @@ fn set_working_units(
     }
 }
 
-fn increment_effort(mut sites: Query<&mut ConstructionSite>, time: Res<Time>) {
+fn increment_effort(
+    mut sites: Query<&mut ConstructionSite>,
+    balance: Res<Balance>,
+    time: Res<Time>,
+) {
     for mut site in &mut sites.iter_mut() {
-        site.effort -= time.delta_seconds() * CONSTRUCTION_BOOST * site.working_units.len() as f32;
+        site.effort -=
+            time.delta_seconds() * balance.construction_boost * site.working_units.len() as f32;
     }
 }
 
@@ -330,7 +371,7 @@ fn place_structure(
             build_event.send(PlaceStructure {
                 structure_type: site.structure_type.clone(),
                 position: transform.translation(),
-                team: site.team.clone(),
+                team: site.team,
             });
 
             //  set the workers to idle
@@ -349,3 +390,39 @@ fn place_structure(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        producer::ProductionType,
+        structure::{Structure, StructurePlugin},
+        testing::{Scenario, TestApp},
+    };
+
+    #[test]
+    fn construction_site_becomes_structure() {
+        let mut test = TestApp::new((ConstructionPlugin, StructurePlugin));
+        let site = Vec2::new(100.0, 100.0);
+        let spawned = test.spawn(&Scenario {
+            units: vec![(ProductionType::Worker, TeamType(0), Vec2::new(140.0, 100.0))],
+            ..default()
+        });
+
+        test.select_units(TeamType(0), &spawned.units);
+        test.send(PlaceConstructionSite {
+            structure_type: "SimpleShrine".into(),
+            team: TeamType(0),
+            position: site,
+            effort: 60.0,
+            cost: 0.0,
+        });
+
+        //	one worker puts in 20.5 effort a second
+        test.step_seconds(4.0);
+        let structures = test.find::<Structure>(TeamType(0));
+        assert_eq!(structures.len(), 1);
+        test.assert_near(structures[0], site, 1.0);
+        assert!(test.find::<ConstructionSite>(TeamType(0)).is_empty());
+    }
+}
@@ -1,16 +1,19 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    movement::{Formation, SetUnitPosition},
+    archetypes::Balance,
+    diplomacy::Diplomacy,
+    movement::{
+        abandon_unreachable_prey, set_moveable_location, Formation, Pursuit, SetUnitPosition,
+    },
+    orders::Holding,
     priest::Priest,
     schedule::InGameSet,
     teams::{Team, TeamType},
     unit::Unit,
 };
 
-const CONVERSION_RANGE: f32 = 60.0;
-const CONVERSION_RATE: f32 = 0.4;
-
 pub struct ConversionPlugin;
 
 impl Plugin for ConversionPlugin {
@@ -19,8 +22,16 @@ impl Plugin for ConversionPlugin {
             Update,
             (
                 assign_converters,
-                ((unassign_converters, break_conversion_pursuit), pursue_prey).chain(),
-                persuade_unit,
+                (
+                    (
+                        unassign_converters,
+                        break_conversion_pursuit,
+                        abandon_unreachable_prey::<ConvertPursuit>,
+                    ),
+                    pursue_prey.before(set_moveable_location),
+                )
+                    .chain(),
+                (persuade_unit, restore_faith).chain(),
             )
                 .in_set(InGameSet::EntityUpdates),
         )
@@ -46,16 +57,32 @@ pub struct ConvertPursuit {
     pub prey: Entity,
 }
 
+impl Pursuit for ConvertPursuit {
+    fn prey(&self) -> Entity {
+        self.prey
+    }
+}
+
 #[derive(Component)]
 pub struct Faith {
     pub base: f32,
     pub current: f32,
+    /// the last team to persuade this entity, who will gain it upon conversion
+    pub persuader: Option<TeamType>,
+}
+
+/// Steadily restores the faith of nearby allies, undoing the work of enemy priests
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct FaithAura {
+    pub range: f32,
+    pub rate: f32,
 }
 
 #[derive(Event)]
 pub struct Convert {
     pub victim: Entity,
     pub value: f32,
+    pub team: TeamType,
 }
 
 #[derive(Event)]
@@ -94,12 +121,13 @@ pub fn assign_converters(
 fn unassign_converters(
     predators: Query<(Entity, &ConvertPursuit, &Team), With<ConvertPursuit>>,
     victims: Query<&Team, With<Faith>>,
+    diplomacy: Res<Diplomacy>,
     mut commands: Commands,
 ) {
     for (entity, convert_pursuit, predator_team) in predators.iter() {
         if let Ok(prey_team) = victims.get(convert_pursuit.prey) {
-            //	if they just converted, they need no more persuation
-            if predator_team.0 == prey_team.0 {
+            //	if they just converted, or a truce was made, they need no more persuation
+            if !diplomacy.is_hostile(&predator_team.0, &prey_team.0) {
                 commands.entity(entity).remove::<ConvertPursuit>();
             }
         }
@@ -114,14 +142,27 @@ fn break_conversion_pursuit(mut event: EventReader<BreakConvertPursuit>, mut com
     }
 }
 
+type PredatorData = (
+    Entity,
+    &'static mut ConvertPursuit,
+    &'static Transform,
+    &'static Priest,
+    &'static Team,
+    Has<Holding>,
+);
+
 fn pursue_prey(
-    mut predators: Query<(&mut ConvertPursuit, &Transform, &Priest, &Team), With<ConvertPursuit>>,
+    mut predators: Query<PredatorData, With<ConvertPursuit>>,
     victims: Query<&Transform, With<Faith>>,
+    balance: Res<Balance>,
     time: Res<Time>,
     mut movement_writer: EventWriter<SetUnitPosition>,
     mut convert_events: EventWriter<Convert>,
+    mut commands: Commands,
 ) {
-    for (mut convert_pursuit, predator_transform, priest, team) in predators.iter_mut() {
+    for (predator_entity, mut convert_pursuit, predator_transform, priest, team, holding) in
+        predators.iter_mut()
+    {
         convert_pursuit.cooldown -= time.delta_seconds();
 
         if convert_pursuit.cooldown < 0.0 {
@@ -130,7 +171,10 @@ fn pursue_prey(
                     .translation
                     .distance(victim_transform.translation);
 
-                if dist > CONVERSION_RANGE {
+                if dist > balance.conversion_range && holding {
+                    //	holding units let anything out of reach go
+                    commands.entity(predator_entity).remove::<ConvertPursuit>();
+                } else if dist > balance.conversion_range {
                     let convert_direction = (predator_transform.translation
                         - victim_transform.translation)
                         .normalize()
@@ -139,16 +183,19 @@ fn pursue_prey(
                         position: victim_transform.translation.xy(),
                         direction: convert_direction,
                         formation: Formation::Ringed,
-                        team: team.0.clone(),
+                        team: team.0,
+                        queued: false,
+                        attack_move: true,
                     });
                 } else {
                     convert_events.send(Convert {
                         victim: convert_pursuit.prey,
                         value: priest.persuation,
+                        team: team.0,
                     });
                 }
 
-                convert_pursuit.cooldown = CONVERSION_RATE;
+                convert_pursuit.cooldown = balance.conversion_rate;
             }
         }
     }
@@ -161,6 +208,29 @@ fn persuade_unit(
     for convert in convert_events.read() {
         if let Ok(mut faith) = victim_faith.get_mut(convert.victim) {
             faith.current -= convert.value;
+            faith.persuader = Some(convert.team);
+        }
+    }
+}
+
+fn restore_faith(
+    auras: Query<(&FaithAura, &GlobalTransform, &Team)>,
+    mut faithful: Query<(&mut Faith, &GlobalTransform, &Team)>,
+    diplomacy: Res<Diplomacy>,
+    time: Res<Time>,
+) {
+    for (aura, aura_transform, aura_team) in auras.iter() {
+        for (mut faith, transform, team) in faithful.iter_mut() {
+            if !diplomacy.is_allied(&aura_team.0, &team.0)
+                || transform
+                    .translation()
+                    .distance(aura_transform.translation())
+                    > aura.range
+            {
+                continue;
+            }
+
+            faith.current = (faith.current + aura.rate * time.delta_seconds()).min(faith.base);
         }
     }
 }
@@ -168,12 +238,44 @@ fn persuade_unit(
 fn convert_unfaithful_units(mut query: Query<(&mut Faith, &mut Team), With<Faith>>) {
     for (mut faith, mut team) in query.iter_mut() {
         if faith.current < 0.0 {
-            team.0 = match team.0.clone() {
-                TeamType::Human => TeamType::CPU,
-                TeamType::CPU => TeamType::Human,
-            };
+            if let Some(persuader) = faith.persuader.take() {
+                team.0 = persuader;
+            }
 
             faith.current = faith.base;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        producer::ProductionType,
+        testing::{Scenario, TestApp},
+    };
+
+    #[test]
+    fn priest_converts_worker() {
+        let mut test = TestApp::new(ConversionPlugin);
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Priest, TeamType(0), Vec2::new(0.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(30.0, 0.0)),
+            ],
+            ..default()
+        });
+        let (priest, worker) = (spawned.units[0], spawned.units[1]);
+
+        test.send(AssignConvertPursuit {
+            predators: vec![priest],
+            prey: worker,
+        });
+
+        //	44 faith at 3 a persuasion, with one every 0.4 seconds
+        test.step_seconds(7.0);
+        test.assert_team(worker, TeamType(0));
+        test.assert_faith(worker, 44.0);
+        test.assert_team(priest, TeamType(0));
+    }
+}
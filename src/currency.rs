@@ -1,42 +1,45 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 
-use crate::teams::TeamType;
-
-const CURRENCY_START: f32 = 100.0;
+use crate::{
+    archetypes::Balance,
+    map::MapLoaded,
+    teams::{Players, TeamType},
+};
 
 pub struct CurrencyPlugin;
 
 impl Plugin for CurrencyPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Energy {
-            human: CURRENCY_START,
-            cpu: CURRENCY_START,
-        });
+        app.add_systems(Update, setup_energy.run_if(on_event::<MapLoaded>()))
+            .insert_resource(Energy {
+                values: Default::default(),
+            });
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct Energy {
-    pub human: f32,
-    pub cpu: f32,
+    values: HashMap<TeamType, f32>,
 }
 
 impl Energy {
     pub fn get(&self, team: &TeamType) -> f32 {
-        match team {
-            TeamType::Human => self.human,
-            TeamType::CPU => self.cpu,
-        }
+        self.values.get(team).copied().unwrap_or(0.0)
     }
 
     pub fn add(&mut self, team: &TeamType, value: f32) {
-        match team {
-            TeamType::Human => {
-                self.human += value;
-            }
-            TeamType::CPU => {
-                self.cpu += value;
-            }
-        }
+        *self.values.entry(*team).or_default() += value;
+    }
+
+    pub fn set(&mut self, team: &TeamType, value: f32) {
+        self.values.insert(*team, value);
+    }
+}
+
+fn setup_energy(players: Res<Players>, balance: Res<Balance>, mut energy: ResMut<Energy>) {
+    energy.values.clear();
+
+    for player in players.list.iter() {
+        energy.set(&player.team, balance.starting_energy);
     }
 }
@@ -1,10 +1,16 @@
 use bevy::prelude::*;
 
 use crate::{
+    archetypes::Balance,
     combat::{assign_attackers, AssignAttackPursuit, AttackPursuit, Health},
     conversion::{assign_converters, AssignConvertPursuit, ConvertPursuit, Faith},
+    diplomacy::Diplomacy,
+    movement::Moveable,
+    nav_agent::Path,
+    orders::Holding,
     priest::Priest,
     schedule::InGameSet,
+    spatial::SpatialIndex,
     teams::Team,
     warrior::Warrior,
 };
@@ -29,22 +35,78 @@ pub struct Detector {
     pub range: f32,
 }
 
+impl Detector {
+    /// how far the detector looks, which for a holding unit is only as far as it can reach
+    fn sight(&self, holding: bool, reach: f32) -> f32 {
+        if holding {
+            self.range.min(reach)
+        } else {
+            self.range
+        }
+    }
+}
+
+type DetectorData = (
+    Entity,
+    &'static GlobalTransform,
+    &'static Team,
+    &'static Detector,
+    Has<Holding>,
+    &'static Moveable,
+    Has<Path>,
+);
+
+type TargetData = (
+    Entity,
+    &'static GlobalTransform,
+    &'static Team,
+    Option<&'static AttackPursuit>,
+    Option<&'static ConvertPursuit>,
+);
+
+/// whether the target is attacking or converting the detector
+fn threatens(
+    attacking: Option<&AttackPursuit>,
+    converting: Option<&ConvertPursuit>,
+    detector: Entity,
+) -> bool {
+    attacking.is_some_and(|pursuit| pursuit.prey == detector)
+        || converting.is_some_and(|pursuit| pursuit.prey == detector)
+}
+
 fn detect_attack_target(
-    detectors: Query<
-        (Entity, &GlobalTransform, &Team, &Detector),
-        (With<Warrior>, Without<AttackPursuit>),
-    >,
-    targets: Query<(Entity, &GlobalTransform, &Team), With<Health>>,
+    detectors: Query<DetectorData, (With<Warrior>, Without<AttackPursuit>)>,
+    targets: Query<TargetData, With<Health>>,
+    diplomacy: Res<Diplomacy>,
+    balance: Res<Balance>,
+    spatial: Res<SpatialIndex>,
     mut event: EventWriter<AssignAttackPursuit>,
 ) {
-    for (target_entity, target_transform, target_team) in targets.iter() {
+    //  only detectors close enough to see the furthest are worth checking
+    let reach = detectors
+        .iter()
+        .map(|(_, _, _, detection, holding, ..)| detection.sight(holding, balance.attack_range))
+        .fold(0.0, f32::max);
+
+    for (target_entity, target_transform, target_team, attacking, converting) in targets.iter() {
         let mut pursuers: Vec<Entity> = Vec::new();
+        let target_position = target_transform.translation().xy();
 
-        for (detector_entity, detector_transform, detector_team, detector_detection) in
-            detectors.iter()
+        for (detector_entity, detector_transform, detector_team, detector_detection, holding, ..) in
+            spatial
+                .within(target_position, reach)
+                .filter_map(|entity| detectors.get(entity).ok())
+                //  a plain move walks past anything it sees until it gets there, unless it is set upon
+                .filter(|(detector_entity, .., moveable, pathing)| {
+                    !pathing
+                        || moveable.attack_move
+                        || threatens(attacking, converting, *detector_entity)
+                })
         {
-            //  detector should not "detect" itself or it's own team
-            if detector_entity == target_entity || detector_team.0 == target_team.0 {
+            //  detector should not "detect" itself or anyone it isn't hostile towards
+            if detector_entity == target_entity
+                || !diplomacy.is_hostile(&detector_team.0, &target_team.0)
+            {
                 continue;
             }
 
@@ -52,7 +114,7 @@ fn detect_attack_target(
                 .translation()
                 .distance(target_transform.translation());
 
-            if distance < detector_detection.range {
+            if distance < detector_detection.sight(holding, balance.attack_range) {
                 pursuers.push(detector_entity);
             }
         }
@@ -67,21 +129,38 @@ fn detect_attack_target(
 }
 
 fn detect_convert_target(
-    detectors: Query<
-        (Entity, &GlobalTransform, &Team, &Detector),
-        (With<Priest>, Without<ConvertPursuit>),
-    >,
-    targets: Query<(Entity, &GlobalTransform, &Team), With<Faith>>,
+    detectors: Query<DetectorData, (With<Priest>, Without<ConvertPursuit>)>,
+    targets: Query<TargetData, With<Faith>>,
+    diplomacy: Res<Diplomacy>,
+    balance: Res<Balance>,
+    spatial: Res<SpatialIndex>,
     mut event: EventWriter<AssignConvertPursuit>,
 ) {
-    for (target_entity, target_transform, target_team) in targets.iter() {
+    //  only detectors close enough to see the furthest are worth checking
+    let reach = detectors
+        .iter()
+        .map(|(_, _, _, detection, holding, ..)| detection.sight(holding, balance.conversion_range))
+        .fold(0.0, f32::max);
+
+    for (target_entity, target_transform, target_team, attacking, converting) in targets.iter() {
         let mut pursuers: Vec<Entity> = Vec::new();
+        let target_position = target_transform.translation().xy();
 
-        for (detector_entity, detector_transform, detector_team, detector_detection) in
-            detectors.iter()
+        for (detector_entity, detector_transform, detector_team, detector_detection, holding, ..) in
+            spatial
+                .within(target_position, reach)
+                .filter_map(|entity| detectors.get(entity).ok())
+                //  a plain move walks past anything it sees until it gets there, unless it is set upon
+                .filter(|(detector_entity, .., moveable, pathing)| {
+                    !pathing
+                        || moveable.attack_move
+                        || threatens(attacking, converting, *detector_entity)
+                })
         {
-            //  detector should not "detect" itself or it's own team
-            if detector_entity == target_entity || detector_team.0 == target_team.0 {
+            //  detector should not "detect" itself or anyone it isn't hostile towards
+            if detector_entity == target_entity
+                || !diplomacy.is_hostile(&detector_team.0, &target_team.0)
+            {
                 continue;
             }
 
@@ -89,7 +168,7 @@ fn detect_convert_target(
                 .translation()
                 .distance(target_transform.translation());
 
-            if distance < detector_detection.range {
+            if distance < detector_detection.sight(holding, balance.conversion_range) {
                 pursuers.push(detector_entity);
             }
         }
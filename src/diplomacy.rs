@@ -0,0 +1,94 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{schedule::InGameSet, teams::TeamType};
+
+pub struct DiplomacyPlugin;
+
+impl Plugin for DiplomacyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, change_relations.in_set(InGameSet::SelectionState))
+            .add_event::<ChangeRelation>()
+            .insert_resource(Diplomacy::free_for_all());
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Relation {
+    Allied,
+    Neutral,
+    Hostile,
+}
+
+/// How every pair of teams regards each other, where unlisted pairs are hostile
+#[derive(Resource, Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Diplomacy {
+    relations: HashMap<(TeamType, TeamType), Relation>,
+}
+
+impl Diplomacy {
+    pub fn free_for_all() -> Self {
+        Self::default()
+    }
+
+    /// every team in a group is allied to the rest of its group, and hostile to all others
+    pub fn alliances(groups: &[Vec<TeamType>]) -> Self {
+        let mut diplomacy = Self::default();
+
+        for group in groups.iter() {
+            for (index, a) in group.iter().enumerate() {
+                for b in group.iter().skip(index + 1) {
+                    diplomacy.set(a, b, Relation::Allied);
+                }
+            }
+        }
+
+        diplomacy
+    }
+
+    pub fn relation(&self, a: &TeamType, b: &TeamType) -> Relation {
+        if a == b {
+            return Relation::Allied;
+        }
+
+        *self
+            .relations
+            .get(&Self::key(a, b))
+            .unwrap_or(&Relation::Hostile)
+    }
+
+    pub fn set(&mut self, a: &TeamType, b: &TeamType, relation: Relation) {
+        if a != b {
+            self.relations.insert(Self::key(a, b), relation);
+        }
+    }
+
+    pub fn is_allied(&self, a: &TeamType, b: &TeamType) -> bool {
+        self.relation(a, b) == Relation::Allied
+    }
+
+    pub fn is_hostile(&self, a: &TeamType, b: &TeamType) -> bool {
+        self.relation(a, b) == Relation::Hostile
+    }
+
+    fn key(a: &TeamType, b: &TeamType) -> (TeamType, TeamType) {
+        if a.0 < b.0 {
+            (*a, *b)
+        } else {
+            (*b, *a)
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct ChangeRelation {
+    pub a: TeamType,
+    pub b: TeamType,
+    pub relation: Relation,
+}
+
+fn change_relations(mut events: EventReader<ChangeRelation>, mut diplomacy: ResMut<Diplomacy>) {
+    for change in events.read() {
+        diplomacy.set(&change.a, &change.b, change.relation);
+    }
+}
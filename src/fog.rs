@@ -0,0 +1,275 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+    utils::HashMap,
+};
+
+use crate::{
+    construction::ConstructionSite,
+    detection::Detector,
+    map::{Map, MapLoaded},
+    match_setup::MatchEntity,
+    schedule::InGameSet,
+    state::GameState,
+    structure::Structure,
+    teams::{Players, Team, TeamType},
+    unit::Unit,
+};
+
+const CELL_SIZE: f32 = 50.0;
+const FOG_LAYER: f32 = 10.0;
+
+const UNEXPLORED_ALPHA: u8 = 255;
+const EXPLORED_ALPHA: u8 = 150;
+const VISIBLE_ALPHA: u8 = 0;
+
+pub struct FogOfWarPlugin;
+
+impl Plugin for FogOfWarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (setup_fog_of_war, spawn_fog_overlay)
+                .chain()
+                .run_if(on_event::<MapLoaded>()),
+        )
+        .add_systems(
+            Update,
+            (update_vision, (hide_unseen_enemies, draw_fog_overlay))
+                .chain()
+                .in_set(InGameSet::EntityUpdates),
+        )
+        .add_systems(OnEnter(GameState::MapEditor), show_fog_overlay::<false>)
+        .add_systems(OnExit(GameState::MapEditor), show_fog_overlay::<true>)
+        .insert_resource(FogOfWar::default());
+    }
+}
+
+/// How far an entity without a `Detector` can see
+#[derive(Component)]
+pub struct Sight {
+    pub range: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CellVisibility {
+    Unexplored,
+    Explored,
+    Visible,
+}
+
+/// A grid over the map for every team, tracking which cells its entities can currently see
+#[derive(Resource, Default)]
+pub struct FogOfWar {
+    columns: usize,
+    rows: usize,
+    cells: HashMap<TeamType, Vec<CellVisibility>>,
+}
+
+impl FogOfWar {
+    pub fn new(size: Vec2, teams: impl Iterator<Item = TeamType>) -> Self {
+        let columns = (size.x / CELL_SIZE).ceil().max(1.0) as usize;
+        let rows = (size.y / CELL_SIZE).ceil().max(1.0) as usize;
+
+        Self {
+            columns,
+            rows,
+            cells: teams
+                .map(|team| (team, vec![CellVisibility::Unexplored; columns * rows]))
+                .collect(),
+        }
+    }
+
+    pub fn visibility(&self, team: &TeamType, position: Vec2) -> CellVisibility {
+        self.index(position)
+            .and_then(|index| self.cells.get(team).map(|cells| cells[index]))
+            .unwrap_or(CellVisibility::Unexplored)
+    }
+
+    pub fn is_visible(&self, team: &TeamType, position: Vec2) -> bool {
+        self.visibility(team, position) == CellVisibility::Visible
+    }
+
+    fn index(&self, position: Vec2) -> Option<usize> {
+        let cell = (position / CELL_SIZE).floor();
+        if cell.x < 0.0 || cell.y < 0.0 {
+            return None;
+        }
+
+        let (column, row) = (cell.x as usize, cell.y as usize);
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+
+        Some(row * self.columns + column)
+    }
+
+    /// everything seen last update is only remembered now
+    fn fade(&mut self) {
+        for cells in self.cells.values_mut() {
+            for cell in cells.iter_mut() {
+                if *cell == CellVisibility::Visible {
+                    *cell = CellVisibility::Explored;
+                }
+            }
+        }
+    }
+
+    fn reveal(&mut self, team: &TeamType, center: Vec2, range: f32) {
+        let Some(cells) = self.cells.get_mut(team) else {
+            return;
+        };
+
+        let min = ((center - range) / CELL_SIZE).floor().max(Vec2::ZERO);
+        let max = ((center + range) / CELL_SIZE).ceil();
+        let max_column = (max.x as usize).min(self.columns);
+        let max_row = (max.y as usize).min(self.rows);
+
+        for row in (min.y as usize)..max_row {
+            for column in (min.x as usize)..max_column {
+                let cell_center = (Vec2::new(column as f32, row as f32) + 0.5) * CELL_SIZE;
+                if cell_center.distance(center) <= range {
+                    cells[row * self.columns + column] = CellVisibility::Visible;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct FogOverlay;
+
+fn setup_fog_of_war(map: Res<Map>, players: Res<Players>, mut fog: ResMut<FogOfWar>) {
+    *fog = FogOfWar::new(map.size, players.list.iter().map(|player| player.team));
+}
+
+fn spawn_fog_overlay(
+    fog: Res<FogOfWar>,
+    map: Res<Map>,
+    overlays: Query<Entity, With<FogOverlay>>,
+    mut images: ResMut<Assets<Image>>,
+    mut commands: Commands,
+) {
+    for entity in overlays.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    //	one pixel per cell, stretched over the map and smoothed by the sampler
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: fog.columns as u32,
+            height: fog.rows as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, UNEXPLORED_ALPHA],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::linear();
+
+    commands.spawn((
+        SpriteBundle {
+            texture: images.add(image),
+            sprite: Sprite {
+                custom_size: Some(map.size),
+                ..default()
+            },
+            transform: Transform::from_translation((map.size / 2.0).extend(FOG_LAYER)),
+            ..default()
+        },
+        FogOverlay,
+        MatchEntity,
+        Name::new("Fog of War"),
+    ));
+}
+
+//	the editor needs to see the whole map
+fn show_fog_overlay<const SHOW: bool>(mut overlays: Query<&mut Visibility, With<FogOverlay>>) {
+    for mut visibility in overlays.iter_mut() {
+        *visibility = if SHOW {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+type VisionSourceData = (
+    &'static GlobalTransform,
+    &'static Team,
+    Option<&'static Sight>,
+    Option<&'static Detector>,
+);
+
+/// Everything that clears the fog of war around it
+type VisionSources = Or<(With<Sight>, With<Detector>)>;
+
+fn update_vision(sources: Query<VisionSourceData, VisionSources>, mut fog: ResMut<FogOfWar>) {
+    fog.fade();
+
+    for (transform, team, sight, detector) in sources.iter() {
+        let range = sight
+            .map(|sight| sight.range)
+            .or(detector.map(|detector| detector.range))
+            .unwrap_or(0.0);
+
+        fog.reveal(&team.0, transform.translation().xy(), range);
+    }
+}
+
+/// Everything the fog of war can hide
+type Fogged = Or<(With<Unit>, With<Structure>, With<ConstructionSite>)>;
+
+fn hide_unseen_enemies(
+    mut entities: Query<(&GlobalTransform, &Team, &mut Visibility), Fogged>,
+    fog: Res<FogOfWar>,
+    players: Res<Players>,
+) {
+    for (transform, team, mut visibility) in entities.iter_mut() {
+        let seen = players.is_local(&team.0)
+            || fog.is_visible(&players.local, transform.translation().xy());
+
+        let wanted = if seen {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+}
+
+fn draw_fog_overlay(
+    fog: Res<FogOfWar>,
+    players: Res<Players>,
+    overlays: Query<&Handle<Image>, With<FogOverlay>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Some(cells) = fog.cells.get(&players.local) else {
+        return;
+    };
+
+    for handle in overlays.iter() {
+        let Some(image) = images.get_mut(handle) else {
+            continue;
+        };
+
+        //	image rows run top to bottom, the grid runs bottom to top
+        for (index, cell) in cells.iter().enumerate() {
+            let (column, row) = (index % fog.columns, index / fog.columns);
+            let pixel = ((fog.rows - 1 - row) * fog.columns + column) * 4;
+
+            image.data[pixel + 3] = match cell {
+                CellVisibility::Unexplored => UNEXPLORED_ALPHA,
+                CellVisibility::Explored => EXPLORED_ALPHA,
+                CellVisibility::Visible => VISIBLE_ALPHA,
+            };
+        }
+    }
+}
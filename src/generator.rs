@@ -1,11 +1,14 @@
-use bevy::prelude::*;
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
 
 use crate::{
-    currency::Energy, schedule::InGameSet, structure::Structure, teams::Team, worker::Worker,
+    currency::Energy, map::ResourceNode, schedule::InGameSet, spatial::SpatialIndex,
+    structure::Structure, teams::Team, worker::Worker,
 };
 
 const GENERATOR_BASE_RATE: f32 = 1.0;
 const WORKING_RANGE: f32 = 60.0;
+//	how far past the edge of a resource node a generator can still draw from it
+const NODE_RANGE: f32 = 100.0;
 
 pub struct GeneratorPlugin;
 
@@ -15,6 +18,7 @@ impl Plugin for GeneratorPlugin {
             Update,
             (
                 (
+                    attach_resource_nodes,
                     assign_new_workers,
                     (set_assigned_workers, set_working_workers).chain(),
                 ),
@@ -35,6 +39,7 @@ pub struct Generator {
     pub added_rate: f32,
     pub assigned_workers: Vec<Entity>,
     pub working_workers: Vec<Entity>,
+    pub node: Option<Entity>,
 }
 
 impl Default for Generator {
@@ -46,6 +51,7 @@ impl Default for Generator {
             added_rate: 0.0,
             assigned_workers: Vec::new(),
             working_workers: Vec::new(),
+            node: None,
         }
     }
 }
@@ -60,6 +66,37 @@ pub struct AssignGeneratorWorkers {
     pub workers: Vec<Entity>,
 }
 
+//	generators without a node keep looking, since the map editor respawns the nodes
+fn attach_resource_nodes(
+    mut generator_query: Query<(&Transform, &mut Generator)>,
+    nodes: Query<(Entity, &Transform, &ResourceNode)>,
+) {
+    for (generator_transform, mut generator) in generator_query.iter_mut() {
+        if generator.node.is_some_and(|node| nodes.contains(node)) {
+            continue;
+        }
+        let position = generator_transform.translation.xy();
+
+        //	draw from the closest node in reach, or sit idle
+        let node = nodes
+            .iter()
+            .filter(|(_, transform, node)| {
+                !node.is_empty()
+                    && transform.translation.xy().distance(position) <= node.radius() + NODE_RANGE
+            })
+            .min_by(|(_, a, _), (_, b, _)| {
+                let a = a.translation.xy().distance(position);
+                let b = b.translation.xy().distance(position);
+                a.total_cmp(&b)
+            })
+            .map(|(entity, _, _)| entity);
+        if node != generator.node {
+            generator.node = node;
+            generator.is_running = node.is_some();
+        }
+    }
+}
+
 fn assign_new_workers(
     mut assignment_event: EventReader<AssignGeneratorWorkers>,
     mut generator_query: Query<&mut Generator>,
@@ -75,21 +112,19 @@ fn assign_new_workers(
 
 fn set_assigned_workers(
     mut generator_query: Query<(&Transform, &mut Generator)>,
-    workers: Query<&Transform, With<Worker>>,
+    workers: Query<(), With<Worker>>,
+    spatial: Res<SpatialIndex>,
 ) {
     for (generator_transform, mut generator) in generator_query.iter_mut() {
         let mut swapping: Vec<(usize, Entity)> = vec![];
+        let nearby: EntityHashSet = spatial
+            .within(generator_transform.translation.xy(), WORKING_RANGE)
+            .collect();
 
         //	store all the working workers far enough to swap
         for (index, &entity) in generator.working_workers.iter().enumerate() {
-            if let Ok(worker_transform) = workers.get(entity) {
-                if generator_transform
-                    .translation
-                    .distance(worker_transform.translation)
-                    > WORKING_RANGE
-                {
-                    swapping.push((index, entity));
-                }
+            if workers.contains(entity) && !nearby.contains(&entity) {
+                swapping.push((index, entity));
             }
         }
 
@@ -103,21 +138,19 @@ fn set_assigned_workers(
 
 fn set_working_workers(
     mut generator_query: Query<(&Transform, &mut Generator)>,
-    workers: Query<&Transform, With<Worker>>,
+    workers: Query<(), With<Worker>>,
+    spatial: Res<SpatialIndex>,
 ) {
     for (generator_transform, mut generator) in generator_query.iter_mut() {
         let mut swapping: Vec<(usize, Entity)> = vec![];
+        let nearby: EntityHashSet = spatial
+            .within(generator_transform.translation.xy(), WORKING_RANGE)
+            .collect();
 
         //	store all the assigned workers close enough to swap
         for (index, &entity) in generator.assigned_workers.iter().enumerate() {
-            if let Ok(worker_transform) = workers.get(entity) {
-                if generator_transform
-                    .translation
-                    .distance(worker_transform.translation)
-                    < WORKING_RANGE
-                {
-                    swapping.push((index, entity));
-                }
+            if workers.contains(entity) && nearby.contains(&entity) {
+                swapping.push((index, entity));
             }
         }
 
@@ -146,19 +179,29 @@ fn get_worker_effort(mut generator_query: Query<&mut Generator>, worker_query: Q
 fn generate(
     time: Res<Time>,
     mut energy: ResMut<Energy>,
-    query: Query<(&Generator, &Team), With<Structure>>,
+    mut query: Query<(&mut Generator, &Team), With<Structure>>,
+    mut nodes: Query<&mut ResourceNode>,
 ) {
     let delta_time = time.delta_seconds();
 
-    for (generator, team) in query.iter() {
-        if generator.is_running {
-            match generator.gen_type {
-                GeneratorType::Energy => {
-                    energy.add(
-                        &team.0,
-                        (generator.base_rate + generator.added_rate) * delta_time,
-                    );
-                }
+    for (mut generator, team) in query.iter_mut() {
+        if !generator.is_running {
+            continue;
+        }
+
+        let Some(mut node) = generator.node.and_then(|node| nodes.get_mut(node).ok()) else {
+            generator.is_running = false;
+            continue;
+        };
+
+        let drawn = node.drain((generator.base_rate + generator.added_rate) * delta_time);
+        if node.is_empty() {
+            generator.is_running = false;
+        }
+
+        match generator.gen_type {
+            GeneratorType::Energy => {
+                energy.add(&team.0, drawn);
             }
         }
     }
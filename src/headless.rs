@@ -0,0 +1,387 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    ecs::system::SystemParam,
+    gizmos::{config::GizmoConfigStore, gizmos::GizmoStorage},
+    hierarchy::HierarchyPlugin,
+    log::LogPlugin,
+    prelude::*,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+    transform::TransformPlugin,
+};
+use thiserror::Error;
+
+use crate::{
+    ai::AIDifficulty,
+    archetypes::ArchetypeHandles,
+    currency::Energy,
+    fog::FogOfWar,
+    inputs::{BuildSelection, MousePosition, ProducerSelection, UnitAim},
+    map::{MapLoaded, MapSource},
+    map_generator::GeneratorSettings,
+    match_setup::{MatchMode, MatchRng, MatchSettings, StartMatch, TeamSetup},
+    replay::{Replay, ReplayError, ReplayPlayer, ReplayRecorder},
+    state::GameState,
+    structure::{Structure, StructureType},
+    teams::{Players, Team},
+    unit::{Hero, Unit},
+    victory::{MatchResult, MatchRules},
+    GameplayPlugins,
+};
+
+//	sixty simulated frames to the second, however quickly they actually run
+const HEADLESS_TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+const DEFAULT_TIME_LIMIT: f32 = 3600.0;
+
+/// How a headless AI-vs-AI match is set up, as read from the command line
+#[derive(Resource, Clone, Debug)]
+pub struct HeadlessSettings {
+    pub seed: u64,
+    pub map: MapSource,
+    pub players: usize,
+    pub difficulty: AIDifficulty,
+    /// simulated seconds before the match is called a draw
+    pub time_limit: f32,
+    /// where to write a replay of the match
+    pub record: Option<String>,
+    /// a recorded match to play out again instead of letting the AI play
+    pub replay: Option<Replay>,
+}
+
+impl Default for HeadlessSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            map: MapSource::default(),
+            players: 2,
+            difficulty: AIDifficulty::Normal,
+            time_limit: DEFAULT_TIME_LIMIT,
+            record: None,
+            replay: None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum HeadlessArgsError {
+    #[error("unknown argument {0}")]
+    Unknown(String),
+    #[error("{0} needs a value")]
+    Missing(String),
+    #[error("could not read {flag} from {value}")]
+    Invalid { flag: String, value: String },
+    #[error("{0}")]
+    Replay(#[from] ReplayError),
+}
+
+impl HeadlessSettings {
+    /// reads `--seed`, `--map`, `--players`, `--difficulty`, `--time-limit`, `--record` and `--replay`,
+    /// where a map of `generated` builds one from the seed and a replay brings its own setup
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, HeadlessArgsError> {
+        let mut settings = HeadlessSettings::default();
+        let mut generated = false;
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| HeadlessArgsError::Missing(flag.clone()))?;
+            let invalid = || HeadlessArgsError::Invalid {
+                flag: flag.clone(),
+                value: value.clone(),
+            };
+
+            match flag.as_str() {
+                "--seed" => settings.seed = value.parse().map_err(|_| invalid())?,
+                "--players" => settings.players = value.parse().map_err(|_| invalid())?,
+                "--time-limit" => settings.time_limit = value.parse().map_err(|_| invalid())?,
+                "--difficulty" => {
+                    settings.difficulty = match value.as_str() {
+                        "easy" => AIDifficulty::Easy,
+                        "normal" => AIDifficulty::Normal,
+                        "hard" => AIDifficulty::Hard,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "--map" if value == "generated" => generated = true,
+                "--map" => settings.map = MapSource::File(value),
+                "--record" => settings.record = Some(value),
+                "--replay" => settings.replay = Some(Replay::load(&value)?),
+                _ => return Err(HeadlessArgsError::Unknown(flag)),
+            }
+        }
+
+        //	the seed may come after the map, so the generator is only set up once both are known
+        if generated {
+            settings.map = MapSource::Generated(GeneratorSettings {
+                seed: settings.seed,
+                ..default()
+            });
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Plays a match between AIs without a window, as fast as the machine allows,
+/// then prints how it went
+pub fn run(settings: HeadlessSettings) -> AppExit {
+    App::new()
+        .add_plugins((
+            MinimalPlugins,
+            LogPlugin {
+                level: bevy::log::Level::WARN,
+                ..default()
+            },
+            TransformPlugin,
+            HierarchyPlugin,
+            StatesPlugin,
+            bevy::input::InputPlugin,
+            AssetPlugin {
+                //	an edit halfway through would make the match impossible to repeat
+                watch_for_changes_override: Some(false),
+                ..default()
+            },
+        ))
+        .add_plugins(GameplayPlugins)
+        .add_plugins(HeadlessPlugin { settings })
+        .run()
+}
+
+pub struct HeadlessPlugin {
+    pub settings: HeadlessSettings,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        //	sprites and map meshes still get handles, but nothing is ever drawn
+        app.init_asset::<Image>()
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .init_asset_loader::<NoTextureLoader>();
+
+        //	the navigator always brings its debug drawing along, so gizmos exist but are switched off
+        let mut gizmos = GizmoConfigStore::default();
+        gizmos.insert(
+            GizmoConfig {
+                enabled: false,
+                ..default()
+            },
+            DefaultGizmoConfigGroup,
+        );
+        app.insert_resource(gizmos)
+            .init_resource::<GizmoStorage<DefaultGizmoConfigGroup, ()>>();
+
+        //	resources the gameplay systems share with the windowed input and fog plugins
+        app.insert_resource(FogOfWar::default())
+            .insert_resource(BuildSelection {
+                is_selected: false,
+                structure_type: StructureType::default(),
+                cost: 0.,
+            })
+            .insert_resource(ProducerSelection { is_selected: false })
+            .insert_resource(UnitAim {
+                aiming: false,
+                start: Vec2::ZERO,
+                current: Vec2::ZERO,
+            })
+            .insert_resource(MousePosition(Vec2::ZERO));
+
+        app.add_systems(
+            Update,
+            (
+                start_when_loaded.run_if(in_state(GameState::MainMenu)),
+                start_clock.run_if(on_event::<MapLoaded>()),
+                tick_clock.run_if(in_state(GameState::InGame)),
+                report_match,
+            )
+                .chain(),
+        )
+        .insert_resource(TimeUpdateStrategy::ManualDuration(HEADLESS_TIMESTEP))
+        .insert_resource(ReplayRecorder::new(self.settings.record.clone()))
+        .insert_resource(self.settings.clone())
+        .insert_resource(HeadlessClock::default());
+
+        match &self.settings.replay {
+            Some(replay) => {
+                app.insert_resource(replay.settings())
+                    .insert_resource(ReplayPlayer::new(replay.clone()));
+            }
+            None => {
+                app.insert_resource(MatchSettings {
+                    map: self.settings.map.clone(),
+                    players: self.settings.players,
+                    teams: TeamSetup::FreeForAll,
+                    difficulty: self.settings.difficulty,
+                    rules: MatchRules::default(),
+                    mode: MatchMode::Spectate,
+                    seed: Some(self.settings.seed),
+                });
+            }
+        }
+    }
+}
+
+/// Stands in for the image loader, so sprites get a handle without a texture ever being decoded
+#[derive(Default)]
+pub struct NoTextureLoader;
+
+impl AssetLoader for NoTextureLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        _reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        Ok(Image::default())
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["png"]
+    }
+}
+
+/// Simulated frames and seconds since the map loaded, which is when the match really begins
+#[derive(Resource, Default)]
+pub struct HeadlessClock {
+    pub frames: u64,
+    pub running: bool,
+    //	a replay brings its own frame times, so the seconds are counted rather than worked out
+    seconds: f32,
+    started: Option<Instant>,
+}
+
+impl HeadlessClock {
+    pub fn elapsed(&self) -> f32 {
+        self.seconds
+    }
+}
+
+//	the archetypes decide the cost and stats of everything, so they have to be in before the map
+fn start_when_loaded(
+    handles: Option<Res<ArchetypeHandles>>,
+    asset_server: Res<AssetServer>,
+    mut started: Local<bool>,
+    mut start_match: EventWriter<StartMatch>,
+) {
+    let Some(handles) = handles else {
+        return;
+    };
+
+    if !*started && handles.is_loaded(&asset_server) {
+        *started = true;
+        start_match.send(StartMatch);
+    }
+}
+
+fn start_clock(mut clock: ResMut<HeadlessClock>) {
+    clock.frames = 0;
+    clock.seconds = 0.0;
+    clock.running = true;
+    clock.started = Some(Instant::now());
+}
+
+fn tick_clock(time: Res<Time>, mut clock: ResMut<HeadlessClock>) {
+    if clock.running {
+        clock.frames += 1;
+        clock.seconds += time.delta_seconds();
+    }
+}
+
+/// How the match went and what it was played on
+#[derive(SystemParam)]
+struct MatchOutcome<'w> {
+    state: Res<'w, State<GameState>>,
+    result: Res<'w, MatchResult>,
+    map: Res<'w, MapSource>,
+    rng: Res<'w, MatchRng>,
+}
+
+/// What every team has left
+#[derive(SystemParam)]
+struct Standings<'w, 's> {
+    players: Res<'w, Players>,
+    energy: Res<'w, Energy>,
+    units: Query<'w, 's, &'static Team, With<Unit>>,
+    heroes: Query<'w, 's, &'static Team, With<Hero>>,
+    structures: Query<'w, 's, &'static Team, With<Structure>>,
+}
+
+fn report_match(
+    clock: Res<HeadlessClock>,
+    settings: Res<HeadlessSettings>,
+    player: Option<Res<ReplayPlayer>>,
+    mut recorder: ResMut<ReplayRecorder>,
+    outcome: MatchOutcome,
+    standings: Standings,
+    mut exit: EventWriter<AppExit>,
+) {
+    let timed_out = clock.running && clock.elapsed() >= settings.time_limit;
+    let replayed = clock.running && player.is_some_and(|player| player.is_finished());
+    if *outcome.state.get() != GameState::GameOver && !timed_out && !replayed {
+        return;
+    }
+    let MatchOutcome {
+        result, map, rng, ..
+    } = outcome;
+    let Standings {
+        players,
+        energy,
+        units,
+        heroes,
+        structures,
+    } = standings;
+
+    let outcome = if replayed {
+        "end of replay reached".to_string()
+    } else if timed_out {
+        "time limit reached".to_string()
+    } else if result.winners.is_empty() {
+        "draw".to_string()
+    } else {
+        let winners = result
+            .winners
+            .iter()
+            .map(|team| format!("team {}", team.0))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("won by {}", winners)
+    };
+
+    println!("seed {}, {:?}", rng.seed, *map);
+    println!(
+        "{} after {} frames, {:.1} simulated seconds in {:.2} real seconds",
+        outcome,
+        clock.frames,
+        clock.elapsed(),
+        clock
+            .started
+            .map_or(0.0, |start| start.elapsed().as_secs_f32()),
+    );
+    for player in players.list.iter() {
+        let team = player.team;
+        let owned = |t: &&Team| t.0 == team;
+
+        println!(
+            "  team {}: {} units, {} structures, {:.0} energy{}",
+            team.0,
+            units.iter().filter(owned).count(),
+            structures.iter().filter(owned).count(),
+            energy.get(&team),
+            if heroes.iter().any(|t| t.0 == team) {
+                ", hero standing"
+            } else {
+                ""
+            },
+        );
+    }
+
+    //	a match cut short never reaches game over, where the replay is otherwise saved
+    recorder.finish();
+    exit.send(AppExit::Success);
+}
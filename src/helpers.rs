@@ -1,7 +1,7 @@
 use bevy::{
     color::palettes::{
         // self,
-        tailwind::{GRAY_100, GREEN_300},
+        tailwind::{BLUE_300, GRAY_100, GREEN_300, ORANGE_300, RED_300, YELLOW_300},
     },
     math::vec2,
     prelude::*,
@@ -16,8 +16,10 @@ use crate::{
     currency::Energy,
     inputs::{BoxSelector, BuildSelection, ProducerSelection, UnitAim},
     movement::Formation,
-    selectable::{SelectionState, SelectionType},
-    structure::StructureType,
+    nav_agent::Path,
+    orders::{Order, OrderKind, Orders},
+    selectable::{SelectedUnits, SelectionState, SelectionType},
+    teams::Players,
     ui::{CurrentUI, UIType},
 };
 
@@ -31,6 +33,7 @@ impl Plugin for HelperPlugin {
                 (
                     draw_box_selection,
                     draw_unit_aim,
+                    draw_queued_orders,
                     debug_text,
                     // display_mesh,
                     // display_navigator_path,
@@ -60,6 +63,60 @@ fn draw_unit_aim(unit_aim: Res<UnitAim>, mut gizmos: Gizmos) {
     }
 }
 
+//	the waypoints each selected unit still has queued, joined up in the order they will be visited
+fn draw_queued_orders(
+    selected_units: Res<SelectedUnits>,
+    players: Res<Players>,
+    units: Query<(&GlobalTransform, &Orders, Option<&Path>)>,
+    transforms: Query<&GlobalTransform>,
+    mut gizmos: Gizmos,
+) {
+    for &entity in selected_units.entities.iter(&players.local) {
+        let Ok((transform, orders, path)) = units.get(entity) else {
+            continue;
+        };
+        if orders.queued.is_empty() {
+            continue;
+        }
+
+        //	whatever the unit is doing now is where the queue starts from
+        let mut from = orders
+            .current
+            .and_then(|order| order.position(&transforms))
+            .or_else(|| {
+                path.and_then(|path| transforms.get(path.target).ok())
+                    .map(|target| target.translation().xy())
+            })
+            .unwrap_or(transform.translation().xy());
+        gizmos.line_2d(transform.translation().xy(), from, GREEN_300);
+
+        for order in orders.queued.iter() {
+            let Some(to) = order.position(&transforms) else {
+                continue;
+            };
+            let color = match order {
+                Order::Move {
+                    attack_move: true, ..
+                } => ORANGE_300,
+                Order::Move { .. } => GREEN_300,
+                Order::Target {
+                    kind: OrderKind::Attack,
+                    ..
+                } => RED_300,
+                Order::Target {
+                    kind: OrderKind::Convert,
+                    ..
+                } => YELLOW_300,
+                Order::Target { .. } => BLUE_300,
+            };
+
+            gizmos.line_2d(from, to, color);
+            gizmos.circle_2d(to, 4.0, color);
+            from = to;
+        }
+    }
+}
+
 #[derive(Component)]
 struct DebugText;
 
@@ -98,6 +155,7 @@ fn debug_text(
     build_selection: Res<BuildSelection>,
     producer_selection: Res<ProducerSelection>,
     energy: Res<Energy>,
+    players: Res<Players>,
     current_ui: Res<CurrentUI>,
     selection_state: Res<SelectionState>,
     construction_silhouettes: Query<&Intersects, With<ConstructionSilhouette>>,
@@ -112,11 +170,7 @@ fn debug_text(
         })
         .into();
 
-        text.sections[3].value = (match build_selection.structure_type {
-            StructureType::SimpleShrine => "SimpleShrine - ",
-            StructureType::Producer => "WorkerProducer - ",
-        })
-        .into();
+        text.sections[3].value = format!("{} - ", build_selection.structure_type.0);
 
         text.sections[4].value = (match build_selection.is_selected {
             true => "is selected",
@@ -130,7 +184,7 @@ fn debug_text(
         })
         .into();
 
-        text.sections[8].value = energy.human.to_string();
+        text.sections[8].value = energy.get(&players.local).to_string();
 
         text.sections[10].value = current_ui.focused.to_string();
 
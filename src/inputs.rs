@@ -1,19 +1,24 @@
 use std::cmp::Ordering;
 
-use bevy::{input::mouse::MouseWheel, math::vec3, prelude::*};
+use bevy::{
+    ecs::system::SystemParam, input::mouse::MouseWheel, math::vec3, prelude::*,
+    ui::RelativeCursorPosition,
+};
 
 use crate::{
     camera::CameraDirection,
     construction::AttemptSitePlacement,
     currency::Energy,
+    minimap::{cursor_over_minimap, MinimapPanel},
     movement::{Formation, SetUnitPosition},
+    orders::{HoldPosition, PatrolUnits, StopUnits},
     producer::{PostSpawnMarker, Producer},
     schedule::InGameSet,
     selectable::{
         BoxSelection, SelectedStructures, SelectionState, SelectionStateChanged, SelectionType,
     },
     structure::StructureType,
-    teams::TeamType,
+    teams::{Players, TeamType},
     unit::UnitAction,
 };
 
@@ -33,11 +38,19 @@ impl Plugin for InputPlugin {
         .add_systems(
             Update,
             (
-                (handle_click, handle_mouse_wheel, handle_keys),
-                set_selection_state,
+                (
+                    (
+                        (handle_click, place_patrol_points).chain(),
+                        handle_mouse_wheel,
+                        handle_keys,
+                    ),
+                    set_selection_state,
+                )
+                    .run_if(mouse_is_hovered_over::<true>)
+                    .chain(),
+                //	hotkeys work wherever the cursor is, including over the command buttons
+                handle_command_keys.before(place_patrol_points),
             )
-                .run_if(mouse_is_hovered_over::<true>)
-                .chain()
                 .in_set(InGameSet::UserInput),
         )
         .insert_resource(BoxSelector {
@@ -53,10 +66,14 @@ impl Plugin for InputPlugin {
         })
         .insert_resource(BuildSelection {
             is_selected: false,
-            structure_type: StructureType::SimpleShrine,
+            structure_type: StructureType::default(),
             cost: 0.,
         })
         .insert_resource(ProducerSelection { is_selected: false })
+        .insert_resource(PatrolSelection {
+            is_selected: false,
+            points: Vec::new(),
+        })
         .insert_resource(MousePosition(Vec2::ZERO));
     }
 }
@@ -91,16 +108,26 @@ pub struct ProducerSelection {
     pub is_selected: bool,
 }
 
+/// A patrol waiting on right clicks for its points, from the patrol hotkey or button
+#[derive(Resource)]
+pub struct PatrolSelection {
+    pub is_selected: bool,
+    pub points: Vec<Vec2>,
+}
+
 /// check if cursor is in the game world
-pub fn mouse_is_hovered_over<const WORLD: bool>(windows: Query<&Window>) -> bool {
+pub fn mouse_is_hovered_over<const WORLD: bool>(
+    windows: Query<&Window>,
+    minimap: Query<&RelativeCursorPosition, With<MinimapPanel>>,
+) -> bool {
     if let Some(pos) = windows.single().cursor_position() {
-        WORLD == (pos.y < (WINDOW_HEIGHT - UI_BASE_HEIGHT))
+        WORLD == (pos.y < (WINDOW_HEIGHT - UI_BASE_HEIGHT) && !cursor_over_minimap(&minimap))
     } else {
         false
     }
 }
 
-fn store_mouse_position(
+pub fn store_mouse_position(
     camera: Query<(&Camera, &GlobalTransform)>,
     windows: Query<&Window>,
     mut mouse_position: ResMut<MousePosition>,
@@ -116,21 +143,74 @@ fn store_mouse_position(
     }
 }
 
-fn handle_click(
-    mouse_position: Res<MousePosition>,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
+/// Where the cursor is and which buttons and keys are held down
+#[derive(SystemParam)]
+pub struct PointerInput<'w> {
+    mouse_position: Res<'w, MousePosition>,
+    mouse_button_input: Res<'w, ButtonInput<MouseButton>>,
+    keys: Res<'w, ButtonInput<KeyCode>>,
+}
+
+/// What a click means, given what is selected and whether a patrol is being marked out
+#[derive(SystemParam)]
+pub struct ClickMode<'w> {
+    selection_state: Res<'w, SelectionState>,
+    patrol_selection: Res<'w, PatrolSelection>,
+}
+
+/// What a click can tell the local player's selection to do
+#[derive(SystemParam)]
+pub struct ClickOrders<'w> {
+    players: Res<'w, Players>,
+    movement_writer: EventWriter<'w, SetUnitPosition>,
+    attempt_placement: EventWriter<'w, AttemptSitePlacement>,
+    unit_action: EventWriter<'w, UnitAction>,
+}
+
+/// The selected producers, whose rally point a right click moves
+#[derive(SystemParam)]
+pub struct RallyPoints<'w, 's> {
+    selected_structures: Res<'w, SelectedStructures>,
+    producers: Query<'w, 's, (&'static mut Producer, &'static Children)>,
+    post_spawn_markers: Query<'w, 's, &'static mut PostSpawnMarker>,
+}
+
+pub fn handle_click(
+    input: PointerInput,
     mut box_selector: ResMut<BoxSelector>,
     mut unit_aim: ResMut<UnitAim>,
     box_selection_writer: EventWriter<BoxSelection>,
-    mut movement_writer: EventWriter<SetUnitPosition>,
-    mut attempt_placement: EventWriter<AttemptSitePlacement>,
-    selected_structures: Res<SelectedStructures>,
-    mut producers: Query<(&mut Producer, &Children)>,
-    mut post_spawn_markers: Query<&mut PostSpawnMarker>,
-    selection_state: Res<SelectionState>,
-    mut unit_action: EventWriter<UnitAction>,
+    mode: ClickMode,
+    orders: ClickOrders,
+    rally: RallyPoints,
 ) {
+    let PointerInput {
+        mouse_position,
+        mouse_button_input,
+        keys,
+    } = input;
+    let ClickMode {
+        selection_state,
+        patrol_selection,
+    } = mode;
+    let ClickOrders {
+        players,
+        mut movement_writer,
+        mut attempt_placement,
+        mut unit_action,
+    } = orders;
+    let RallyPoints {
+        selected_structures,
+        mut producers,
+        mut post_spawn_markers,
+    } = rally;
+
     let pos = mouse_position.0;
+    let team = players.local;
+    //	holding shift adds to what the selection was already told to do
+    let queued = keys.pressed(KeyCode::ShiftLeft);
+    //	holding control has the selection fight whatever it comes across on the way
+    let attack_move = keys.pressed(KeyCode::ControlLeft);
 
     match selection_state.0 {
         SelectionType::None => {
@@ -139,6 +219,7 @@ fn handle_click(
                 &mouse_button_input,
                 &mut box_selector,
                 box_selection_writer,
+                team,
             );
         }
         SelectionType::Unit
@@ -150,10 +231,16 @@ fn handle_click(
                 &mouse_button_input,
                 &mut box_selector,
                 box_selection_writer,
+                team,
             );
 
-            if mouse_button_input.just_pressed(MouseButton::Right) {
-                unit_action.send(UnitAction { position: pos });
+            //	while a patrol is being set, right clicks mark its points instead
+            if mouse_button_input.just_pressed(MouseButton::Right) && !patrol_selection.is_selected
+            {
+                unit_action.send(UnitAction {
+                    position: pos,
+                    queued,
+                });
 
                 unit_aim.aiming = true;
                 unit_aim.start = pos;
@@ -164,7 +251,9 @@ fn handle_click(
                     position: unit_aim.start,
                     direction: unit_aim.current - unit_aim.start,
                     formation: box_selector.formation.clone(),
-                    team: TeamType::Human,
+                    team,
+                    queued,
+                    attack_move,
                 });
 
                 unit_aim.aiming = false;
@@ -176,7 +265,7 @@ fn handle_click(
             if mouse_button_input.just_released(MouseButton::Left) {
                 attempt_placement.send(AttemptSitePlacement {
                     position: pos,
-                    team: TeamType::Human,
+                    team,
                 });
             }
         }
@@ -186,11 +275,12 @@ fn handle_click(
                 &mouse_button_input,
                 &mut box_selector,
                 box_selection_writer,
+                team,
             );
         }
         SelectionType::Producer => {
             if mouse_button_input.just_pressed(MouseButton::Right) {
-                for &entity in selected_structures.entities.iter(&TeamType::Human) {
+                for &entity in selected_structures.entities.iter(&team) {
                     if let Ok((mut producer, children)) = producers.get_mut(entity) {
                         producer.post_spawn_location = vec3(pos.x, pos.y, 0.1);
 
@@ -207,12 +297,79 @@ fn handle_click(
                     &mouse_button_input,
                     &mut box_selector,
                     box_selection_writer,
+                    team,
                 );
             }
         }
     }
 }
 
+fn handle_command_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    selection_state: Res<SelectionState>,
+    mut patrol_selection: ResMut<PatrolSelection>,
+    mut stop_writer: EventWriter<StopUnits>,
+    mut hold_writer: EventWriter<HoldPosition>,
+    players: Res<Players>,
+) {
+    let commanding_units = matches!(
+        selection_state.0,
+        SelectionType::Unit
+            | SelectionType::Worker
+            | SelectionType::Priest
+            | SelectionType::Warrior
+    );
+    if !commanding_units {
+        patrol_selection.is_selected = false;
+        patrol_selection.points.clear();
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::KeyX) {
+        stop_writer.send(StopUnits {
+            team: players.local,
+        });
+    }
+    if keys.just_pressed(KeyCode::KeyH) {
+        hold_writer.send(HoldPosition {
+            team: players.local,
+        });
+    }
+    if keys.just_pressed(KeyCode::KeyP) {
+        patrol_selection.is_selected = true;
+        patrol_selection.points.clear();
+    }
+}
+
+fn place_patrol_points(
+    mouse_position: Res<MousePosition>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut patrol_selection: ResMut<PatrolSelection>,
+    mut patrol_writer: EventWriter<PatrolUnits>,
+    players: Res<Players>,
+) {
+    if !patrol_selection.is_selected {
+        return;
+    }
+
+    if mouse_button_input.just_pressed(MouseButton::Left) {
+        patrol_selection.is_selected = false;
+        patrol_selection.points.clear();
+    } else if mouse_button_input.just_pressed(MouseButton::Right) {
+        patrol_selection.points.push(mouse_position.0);
+
+        //	holding shift keeps adding points to the route
+        if !keys.pressed(KeyCode::ShiftLeft) {
+            patrol_writer.send(PatrolUnits {
+                team: players.local,
+                points: std::mem::take(&mut patrol_selection.points),
+            });
+            patrol_selection.is_selected = false;
+        }
+    }
+}
+
 fn set_selection_state(
     mouse_button_input: Res<ButtonInput<MouseButton>>,
     keys: Res<ButtonInput<KeyCode>>,
@@ -220,18 +377,19 @@ fn set_selection_state(
     mut selection_state_changed: EventWriter<SelectionStateChanged>,
     energy: Res<Energy>,
     build_selection: ResMut<BuildSelection>,
+    players: Res<Players>,
 ) {
     match selection_state.0 {
         SelectionType::Construction => {
             let deselect_construction = mouse_button_input.just_released(MouseButton::Right);
             let place_structure = mouse_button_input.just_released(MouseButton::Left)
-                && energy.human > build_selection.cost
+                && energy.get(&players.local) > build_selection.cost
                 && !keys.pressed(KeyCode::ShiftLeft);
 
             if deselect_construction || place_structure {
                 selection_state_changed.send(SelectionStateChanged {
                     new_type: SelectionType::Worker,
-                    team: TeamType::Human,
+                    team: players.local,
                 });
             }
         }
@@ -241,7 +399,7 @@ fn set_selection_state(
             {
                 selection_state_changed.send(SelectionStateChanged {
                     new_type: SelectionType::None,
-                    team: TeamType::Human,
+                    team: players.local,
                 });
             }
         }
@@ -267,7 +425,7 @@ fn handle_mouse_wheel(
     }
 }
 
-fn handle_keys(
+pub fn handle_keys(
     keys: Res<ButtonInput<KeyCode>>,
     mut camera_direction: Query<&mut CameraDirection, With<Camera2d>>,
 ) {
@@ -295,6 +453,7 @@ fn click_selection(
     mouse_button_input: &Res<ButtonInput<MouseButton>>,
     box_selector: &mut BoxSelector,
     mut box_selection_writer: EventWriter<BoxSelection>,
+    team: TeamType,
 ) {
     if mouse_button_input.pressed(MouseButton::Left) {
         if box_selector.selecting == false {
@@ -306,7 +465,7 @@ fn click_selection(
     } else if mouse_button_input.just_released(MouseButton::Left) && box_selector.selecting {
         let event = BoxSelection {
             rect: Rect::from_corners(box_selector.start, box_selector.current),
-            team: TeamType::Human,
+            team,
         };
         box_selection_writer.send(event);
 
@@ -1,32 +1,50 @@
 mod ai;
+mod archetypes;
 mod camera;
 mod combat;
 mod construction;
 mod conversion;
 mod currency;
 mod detection;
+mod diplomacy;
+mod fog;
 mod generator;
+mod headless;
 mod helpers;
 mod inputs;
 mod map;
+mod map_editor;
+mod map_generator;
+mod match_setup;
+mod menu;
+mod minimap;
 mod movement;
 mod nav_agent;
+mod network;
+mod orders;
 mod priest;
 mod producer;
+mod replay;
+mod save;
 mod schedule;
 mod selectable;
+mod spatial;
 mod state;
 mod structure;
 mod teams;
 mod test_scene;
+#[cfg(test)]
+mod testing;
 mod ui;
 mod unit;
+mod victory;
 mod visual_feedback;
 mod warrior;
 mod worker;
 
 use ai::AIPlugin;
-use bevy::{log::LogPlugin, prelude::*, window::WindowMode};
+use archetypes::ArchetypePlugin;
+use bevy::{app::PluginGroupBuilder, log::LogPlugin, prelude::*, window::WindowMode};
 
 use camera::CameraPlugin;
 use combat::CombatPlugin;
@@ -34,20 +52,34 @@ use construction::ConstructionPlugin;
 use conversion::ConversionPlugin;
 use currency::CurrencyPlugin;
 use detection::DetectionPlugin;
+use diplomacy::DiplomacyPlugin;
+use fog::FogOfWarPlugin;
 use generator::GeneratorPlugin;
+use headless::HeadlessSettings;
 use helpers::HelperPlugin;
 use inputs::InputPlugin;
 use map::MapPlugin;
+use map_editor::MapEditorPlugin;
+use match_setup::MatchSetupPlugin;
+use menu::MenuPlugin;
+use minimap::MinimapPlugin;
 use movement::MovementPlugin;
 use nav_agent::NavAgentPlugin;
+use network::{NetworkPlugin, NetworkSettings};
+use orders::OrdersPlugin;
 use producer::ProducerPlugin;
+use replay::ReplayPlugin;
+use save::SavePlugin;
 use schedule::SchedulePlugin;
 use selectable::SelectablePlugin;
+use spatial::SpatialPlugin;
 use state::StatePlugin;
 use structure::StructurePlugin;
+use teams::TeamPlugin;
 // use test_scene::TestScenePlugin;
 use ui::UIPlugin;
 use unit::UnitPlugin;
+use victory::VictoryPlugin;
 use visual_feedback::VisualFeedbackPlugin;
 use vleue_navigator::{
     prelude::{NavmeshUpdaterPlugin, PrimitiveObstacle},
@@ -55,61 +87,106 @@ use vleue_navigator::{
 };
 use worker::WorkerPlugin;
 
-fn main() {
-    App::new()
-        .add_plugins(
-            DefaultPlugins
-                .set(ImagePlugin::default_nearest())
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        title: "Animancer".into(),
-                        resizable: false,
-                        mode: WindowMode::BorderlessFullscreen,
-                        ..default()
-                    }),
-                    ..default()
-                })
-                .set(LogPlugin {
-                    level: bevy::log::Level::INFO,
-                    ..default()
-                })
-                .build(),
-        )
-        .add_plugins((
-            VleueNavigatorPlugin,
+/// Everything a match needs to play out, with or without a window to show it in
+pub struct GameplayPlugins;
+
+impl PluginGroup for GameplayPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(VleueNavigatorPlugin)
             // Auto update the navmesh.
             // Obstacles will be entities with the `Obstacle` marker component,
             // and use the `Aabb` component as the obstacle data source.
-            NavmeshUpdaterPlugin::<PrimitiveObstacle>::default(),
-            NavAgentPlugin,
-        ))
-        .add_plugins((
-            StatePlugin,
-            SchedulePlugin,
-            CameraPlugin,
-            MapPlugin,
-            UIPlugin,
-            InputPlugin,
-            AIPlugin,
-            VisualFeedbackPlugin,
-        ))
-        .add_plugins((
-            CurrencyPlugin,
-            UnitPlugin,
-            SelectablePlugin,
-            MovementPlugin,
-            ConstructionPlugin,
-            StructurePlugin,
-            GeneratorPlugin,
-            ProducerPlugin,
-            WorkerPlugin,
-            CombatPlugin,
-            ConversionPlugin,
-            DetectionPlugin,
-        ))
-        .add_plugins((
-            // TestScenePlugin,
-            HelperPlugin,
-        ))
-        .run();
+            .add(NavmeshUpdaterPlugin::<PrimitiveObstacle>::default())
+            .add(NavAgentPlugin)
+            .add(StatePlugin)
+            .add(SchedulePlugin)
+            .add(MatchSetupPlugin)
+            .add(TeamPlugin)
+            .add(DiplomacyPlugin)
+            .add(ArchetypePlugin)
+            .add(MapPlugin)
+            .add(AIPlugin)
+            .add(CurrencyPlugin)
+            .add(UnitPlugin)
+            .add(SpatialPlugin)
+            .add(SelectablePlugin)
+            .add(MovementPlugin)
+            .add(OrdersPlugin)
+            .add(ConstructionPlugin)
+            .add(StructurePlugin)
+            .add(GeneratorPlugin)
+            .add(ProducerPlugin)
+            .add(WorkerPlugin)
+            .add(CombatPlugin)
+            .add(ConversionPlugin)
+            .add(DetectionPlugin)
+            .add(VictoryPlugin)
+            .add(ReplayPlugin)
+            .add(SavePlugin)
+    }
+}
+
+fn main() {
+    //  `--headless` plays AI against AI without a window, see `HeadlessSettings` for the options
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if(|arg| arg == "--headless").is_some() {
+        match HeadlessSettings::from_args(args) {
+            Ok(settings) => {
+                headless::run(settings);
+            }
+            Err(error) => eprintln!("{}", error),
+        }
+        return;
+    }
+
+    //  `--host <port>` or `--join <address>` plays against other humans, see `NetworkSettings`
+    let network = match NetworkSettings::from_args(args) {
+        Ok(network) => network,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(ImagePlugin::default_nearest())
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "Animancer".into(),
+                    resizable: false,
+                    mode: WindowMode::BorderlessFullscreen,
+                    ..default()
+                }),
+                ..default()
+            })
+            .set(LogPlugin {
+                level: bevy::log::Level::INFO,
+                ..default()
+            })
+            .build(),
+    )
+    .add_plugins(GameplayPlugins)
+    .add_plugins((
+        MenuPlugin,
+        CameraPlugin,
+        MapEditorPlugin,
+        UIPlugin,
+        MinimapPlugin,
+        InputPlugin,
+        VisualFeedbackPlugin,
+        FogOfWarPlugin,
+    ))
+    .add_plugins((
+        // TestScenePlugin,
+        HelperPlugin,
+    ));
+
+    if let Some(settings) = network {
+        app.add_plugins(NetworkPlugin { settings });
+    }
+
+    app.run();
 }
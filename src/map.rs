@@ -1,16 +1,29 @@
 use std::f32::consts::PI;
 
 use bevy::{
-    math::vec2,
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
     prelude::*,
     sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::HashSet,
 };
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use vleue_navigator::prelude::PrimitiveObstacle;
 
-use crate::{nav_agent::spawn_navmesh, teams::TeamType, unit::spawn_hero};
+use crate::{
+    archetypes::UnitRegistry,
+    map_generator::{generate_map, GeneratorSettings},
+    match_setup::MatchEntity,
+    nav_agent::spawn_navmesh,
+    schedule::InGameSet,
+    teams::{Players, TeamType},
+    unit::spawn_hero,
+};
+
+pub const DEFAULT_MAP_PATH: &str = "maps/four_corners.map.ron";
 
 const RESOURCE_SIZE_REDUCER: f32 = 10.0;
-const OBSTACLE_WIDTH: f32 = 15.0;
+pub const OBSTACLE_WIDTH: f32 = 15.0;
 
 const GROUND_COLOR: Color = Color::linear_rgba(0.0, 0.4, 0.2, 0.1);
 const RESOURCES_COLOR: Color = Color::linear_rgba(0.8, 0.0, 0.1, 0.3);
@@ -20,21 +33,41 @@ pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (build_map, render_map.after(spawn_navmesh)))
+        app.init_asset::<Map>()
+            .init_asset_loader::<MapLoader>()
+            .add_systems(
+                Update,
+                (
+                    apply_loaded_map,
+                    (spawn_navmesh, render_map)
+                        .chain()
+                        .run_if(on_event::<MapLoaded>()),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                shrink_resource_nodes.in_set(InGameSet::EntityUpdates),
+            )
+            .add_event::<MapLoaded>()
+            .init_resource::<MapSource>()
             .insert_resource(Map { ..default() });
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PlayerStart {
     pub position: Vec2,
     pub team: TeamType,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MapResource {
     pub position: Vec2,
     pub amount: f32,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MapObstacle {
     pub start: Vec2,
     pub end: Vec2,
@@ -42,7 +75,31 @@ pub struct MapObstacle {
     pub segments: usize,
 }
 
-#[derive(Resource)]
+impl MapObstacle {
+    /// The points along the obstacle, from start to end, one more than its segments
+    pub fn points(&self) -> Vec<Vec2> {
+        if self.segments <= 1 {
+            return vec![self.start, self.end];
+        }
+
+        let base_vector = self.end - self.start;
+        let half_base = base_vector / 2.0;
+        let height = (self.radius * self.radius - half_base.length_squared())
+            .max(0.0)
+            .sqrt();
+        let origin = self.start + half_base - base_vector.perp().normalize() * height;
+        let end_angle = (self.end - origin).to_angle();
+        let full_angle = (self.end - origin).angle_between(self.start - origin);
+        let segment_angle = full_angle / (self.segments as f32);
+
+        (0..=self.segments)
+            .rev()
+            .map(|i| origin + Vec2::from_angle(end_angle + i as f32 * segment_angle) * self.radius)
+            .collect()
+    }
+}
+
+#[derive(Resource, Asset, TypePath, Serialize, Deserialize, Clone, Debug)]
 pub struct Map {
     pub size: Vec2,
     pub starting_points: Vec<PlayerStart>,
@@ -61,242 +118,253 @@ impl Default for Map {
     }
 }
 
-fn build_map(mut map: ResMut<Map>) {
-    *map = Map {
-        size: vec2(5000., 5000.),
-        starting_points: vec![
-            //	SW corner
-            PlayerStart {
-                position: vec2(600., 600.),
-                team: TeamType::Human,
-            },
-            //	NE corner
-            PlayerStart {
-                position: vec2(4400., 4400.),
-                team: TeamType::CPU,
-            },
-        ],
-        resources: vec![
-            //	corners
-            MapResource {
-                position: vec2(300., 300.),
-                amount: 1800.0,
-            },
-            MapResource {
-                position: vec2(4700., 300.),
-                amount: 1800.0,
-            },
-            MapResource {
-                position: vec2(4700., 4700.),
-                amount: 1800.0,
-            },
-            MapResource {
-                position: vec2(300., 4700.),
-                amount: 1800.0,
-            },
-            //	middle lanes
-            MapResource {
-                position: vec2(2500., 800.),
-                amount: 1200.0,
-            },
-            MapResource {
-                position: vec2(4200., 2500.),
-                amount: 1200.0,
-            },
-            MapResource {
-                position: vec2(2500., 4200.),
-                amount: 1200.0,
-            },
-            MapResource {
-                position: vec2(800., 2500.),
-                amount: 1200.0,
-            },
-            //	central
-            MapResource {
-                position: vec2(2500., 2500.),
-                amount: 4600.0,
-            },
-        ],
-        obstacles: vec![
-            //	SW inner corner
-            MapObstacle {
-                start: vec2(0., 1400.),
-                end: vec2(300., 1400.),
-                radius: 1.,
-                segments: 1,
-            },
-            MapObstacle {
-                start: vec2(500., 1400.),
-                end: vec2(1000., 1200.),
-                radius: 1400.,
-                segments: 12,
-            },
-            MapObstacle {
-                start: vec2(1200., 1000.),
-                end: vec2(1400., 500.),
-                radius: 1400.,
-                segments: 12,
-            },
-            MapObstacle {
-                start: vec2(1400., 300.),
-                end: vec2(1400., 0.),
-                radius: 1.,
-                segments: 1,
-            },
-            //	SW outer corner
-            MapObstacle {
-                start: vec2(1200., 2200.),
-                end: vec2(400., 2200.),
-                radius: 1800.,
-                segments: 12,
-            },
-            MapObstacle {
-                start: vec2(1500., 2000.),
-                end: vec2(2000.0, 1500.),
-                radius: 700.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(2200., 400.),
-                end: vec2(2200., 1200.),
-                radius: 1800.,
-                segments: 12,
-            },
-            //	SE inner corner
-            MapObstacle {
-                start: vec2(3600., 0.),
-                end: vec2(3600., 300.),
-                radius: 1.,
-                segments: 1,
-            },
-            MapObstacle {
-                start: vec2(3600., 500.),
-                end: vec2(3800., 1000.),
-                radius: 1400.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(4000., 1200.),
-                end: vec2(4500., 1400.),
-                radius: 1400.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(4700., 1400.),
-                end: vec2(5000., 1400.),
-                radius: 1.,
-                segments: 1,
-            },
-            //	SE outer corner
-            MapObstacle {
-                start: vec2(2800., 1200.),
-                end: vec2(2800., 400.),
-                radius: 2400.,
-                segments: 12,
-            },
-            MapObstacle {
-                start: vec2(3000., 1500.),
-                end: vec2(3500.0, 2000.),
-                radius: 700.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(4600., 2200.),
-                end: vec2(3800., 2200.),
-                radius: 2400.,
-                segments: 12,
-            },
-            //	NE inner corner
-            MapObstacle {
-                start: vec2(5000., 3600.),
-                end: vec2(4700., 3600.),
-                radius: 1.,
-                segments: 1,
-            },
-            MapObstacle {
-                start: vec2(4500., 3600.),
-                end: vec2(4000., 3800.),
-                radius: 1400.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(3800., 4000.),
-                end: vec2(3600., 4500.),
-                radius: 1400.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(3600., 4700.),
-                end: vec2(3600., 5000.),
-                radius: 1.,
-                segments: 1,
-            },
-            //	NE outer corner
-            MapObstacle {
-                start: vec2(3800., 2800.),
-                end: vec2(4600., 2800.),
-                radius: 2400.,
-                segments: 12,
-            },
-            MapObstacle {
-                start: vec2(3500., 3000.),
-                end: vec2(3000.0, 3500.),
-                radius: 700.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(2800., 4600.),
-                end: vec2(2800., 3800.),
-                radius: 2400.,
-                segments: 12,
-            },
-            //	NW inner corner
-            MapObstacle {
-                start: vec2(1400., 5000.),
-                end: vec2(1400., 4700.),
-                radius: 1.,
-                segments: 1,
-            },
-            MapObstacle {
-                start: vec2(1400., 4500.),
-                end: vec2(1200., 4000.),
-                radius: 1400.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(1000., 3800.),
-                end: vec2(500., 3600.),
-                radius: 1400.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(300., 3600.),
-                end: vec2(0., 3600.),
-                radius: 1.,
-                segments: 1,
-            },
-            //	NW outer corner
-            MapObstacle {
-                start: vec2(2200., 3800.),
-                end: vec2(2200., 4600.),
-                radius: 2400.,
-                segments: 12,
-            },
-            MapObstacle {
-                start: vec2(2000., 3500.),
-                end: vec2(1500., 3000.),
-                radius: 700.,
-                segments: 16,
-            },
-            MapObstacle {
-                start: vec2(400., 2800.),
-                end: vec2(1200., 2800.),
-                radius: 2400.,
-                segments: 12,
-            },
-        ],
-        ..default()
+impl Map {
+    /// catch the layouts that would otherwise break rendering or the navmesh
+    pub fn validate(&self) -> Result<(), MapError> {
+        if self.size.x <= 0.0 || self.size.y <= 0.0 {
+            return Err(MapError::InvalidSize(self.size));
+        }
+
+        if self.starting_points.is_empty() {
+            return Err(MapError::NoStartingPoints);
+        }
+
+        let bounds = Rect::from_corners(Vec2::ZERO, self.size);
+        let mut teams: HashSet<TeamType> = HashSet::new();
+        for (index, point) in self.starting_points.iter().enumerate() {
+            if !bounds.contains(point.position) {
+                return Err(MapError::OutOfBounds {
+                    kind: "starting point",
+                    index,
+                    position: point.position,
+                });
+            }
+            if !teams.insert(point.team) {
+                return Err(MapError::DuplicateStart(point.team.0));
+            }
+        }
+
+        for (index, resource) in self.resources.iter().enumerate() {
+            if !bounds.contains(resource.position) {
+                return Err(MapError::OutOfBounds {
+                    kind: "resource",
+                    index,
+                    position: resource.position,
+                });
+            }
+            if resource.amount <= 0.0 {
+                return Err(MapError::EmptyResource {
+                    index,
+                    amount: resource.amount,
+                });
+            }
+        }
+
+        for (index, obstacle) in self.obstacles.iter().enumerate() {
+            let half_chord = obstacle.start.distance(obstacle.end) / 2.0;
+
+            if half_chord == 0.0 {
+                return Err(MapError::ZeroLengthObstacle { index });
+            }
+            if obstacle.segments == 0 {
+                return Err(MapError::NoSegments { index });
+            }
+            //	an arc cannot span a chord wider than its own diameter
+            if obstacle.segments > 1 && obstacle.radius < half_chord {
+                return Err(MapError::ArcTooTight {
+                    index,
+                    radius: obstacle.radius,
+                    half_chord,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MapError {
+    #[error("map size must be positive, found {0}")]
+    InvalidSize(Vec2),
+    #[error("map has no starting points")]
+    NoStartingPoints,
+    #[error("{kind} {index} at {position} lies outside the map")]
+    OutOfBounds {
+        kind: &'static str,
+        index: usize,
+        position: Vec2,
+    },
+    #[error("team {0} has more than one starting point")]
+    DuplicateStart(usize),
+    #[error("resource {index} has an amount of {amount}, but must hold something")]
+    EmptyResource { index: usize, amount: f32 },
+    #[error("obstacle {index} starts and ends at the same point")]
+    ZeroLengthObstacle { index: usize },
+    #[error("obstacle {index} needs at least one segment")]
+    NoSegments { index: usize },
+    #[error(
+        "obstacle {index} has a radius of {radius}, smaller than its half chord of {half_chord}"
+    )]
+    ArcTooTight {
+        index: usize,
+        radius: f32,
+        half_chord: f32,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum MapLoaderError {
+    #[error("could not read map file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse map file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid map: {0}")]
+    Invalid(#[from] MapError),
+}
+
+#[derive(Default)]
+pub struct MapLoader;
+
+impl AssetLoader for MapLoader {
+    type Asset = Map;
+    type Settings = ();
+    type Error = MapLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let map: Map = ron::de::from_bytes(&bytes)?;
+        map.validate()?;
+
+        Ok(map)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
+}
+
+#[derive(Component)]
+pub struct MapElement;
+
+/// A map resource that generators built next to it draw their energy from
+#[derive(Component)]
+pub struct ResourceNode {
+    pub capacity: f32,
+    pub remaining: f32,
+}
+
+impl ResourceNode {
+    pub fn new(amount: f32) -> Self {
+        Self {
+            capacity: amount,
+            remaining: amount,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.remaining / RESOURCE_SIZE_REDUCER
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining <= 0.0
+    }
+
+    /// takes up to the given amount, returning what was actually taken
+    pub fn drain(&mut self, amount: f32) -> f32 {
+        let taken = amount.min(self.remaining).max(0.0);
+        self.remaining -= taken;
+        taken
+    }
+}
+
+/// Where the next match takes its map from
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub enum MapSource {
+    File(String),
+    Generated(GeneratorSettings),
+    /// the map of a saved match, kept whole in case the file it came from has changed since
+    Saved(Map),
+}
+
+impl Default for MapSource {
+    fn default() -> Self {
+        MapSource::File(DEFAULT_MAP_PATH.into())
+    }
+}
+
+#[derive(Resource)]
+pub struct MapHandle(pub Handle<Map>);
+
+/// Sent once the map file has loaded and been copied into the `Map` resource
+#[derive(Event)]
+pub struct MapLoaded;
+
+/// Starts loading the map of the next match, which sends `MapLoaded` once it is ready
+pub fn load_map(
+    source: Res<MapSource>,
+    asset_server: Res<AssetServer>,
+    maps: Res<Assets<Map>>,
+    mut map: ResMut<Map>,
+    mut map_loaded: EventWriter<MapLoaded>,
+    mut commands: Commands,
+) {
+    match source.clone() {
+        MapSource::File(path) => {
+            let handle: Handle<Map> = asset_server.load(path);
+
+            //	a restart reuses the map from last time, which won't send another load event
+            if let Some(loaded) = maps.get(&handle) {
+                *map = loaded.clone();
+                map_loaded.send(MapLoaded);
+            }
+
+            commands.insert_resource(MapHandle(handle));
+        }
+        MapSource::Generated(settings) => match generate_map(&settings) {
+            Ok(generated) => {
+                *map = generated;
+                map_loaded.send(MapLoaded);
+            }
+            Err(error) => error!("could not generate map: {}", error),
+        },
+        MapSource::Saved(saved) => {
+            *map = saved;
+            map_loaded.send(MapLoaded);
+        }
+    }
+}
+
+fn apply_loaded_map(
+    mut asset_events: EventReader<AssetEvent<Map>>,
+    map_handle: Option<Res<MapHandle>>,
+    maps: Res<Assets<Map>>,
+    mut map: ResMut<Map>,
+    mut map_loaded: EventWriter<MapLoaded>,
+) {
+    let Some(map_handle) = map_handle else {
+        return;
     };
+
+    for event in asset_events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } = event {
+            if *id != map_handle.0.id() {
+                continue;
+            }
+
+            if let Some(loaded) = maps.get(*id) {
+                *map = loaded.clone();
+                map_loaded.send(MapLoaded);
+            }
+        }
+    }
 }
 
 fn render_map(
@@ -305,6 +373,35 @@ fn render_map(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
+    registry: Res<UnitRegistry>,
+    players: Res<Players>,
+) {
+    spawn_map_elements(&map, &mut commands, &mut meshes, &mut materials);
+    spawn_resource_nodes(&map, &mut commands, &mut meshes, &mut materials);
+
+    //  set starting pos, leaving unused starts empty
+    for point in map.starting_points.iter() {
+        if !players.contains(&point.team) {
+            continue;
+        }
+
+        spawn_hero(
+            &mut commands,
+            &asset_server,
+            &registry,
+            point.position,
+            point.team,
+            &players,
+        );
+    }
+}
+
+/// Spawns the ground and obstacles, each tagged with a `MapElement`
+pub fn spawn_map_elements(
+    map: &Map,
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
 ) {
     //	render ground
     commands.spawn((
@@ -314,10 +411,43 @@ fn render_map(
             transform: Transform::from_translation((map.size / 2.0).extend(0.0)),
             ..default()
         },
+        MapElement,
+        MatchEntity,
         Name::new("Ground"),
     ));
 
-    //	render resources
+    //	render obstacles, one capsule between each pair of points along them
+    for obstacle in map.obstacles.iter() {
+        for pair in obstacle.points().windows(2) {
+            let base_vector = pair[1] - pair[0];
+            let mut transform =
+                Transform::from_translation((pair[0] + base_vector / 2.0).extend(0.0));
+            transform.rotate_z(base_vector.to_angle() - PI / 2.0);
+
+            let capsule = Capsule2d::new(OBSTACLE_WIDTH, base_vector.length());
+            commands.spawn((
+                MaterialMesh2dBundle {
+                    mesh: Mesh2dHandle(meshes.add(capsule)),
+                    material: materials.add(OBSTACLE_COLOR),
+                    transform,
+                    ..default()
+                },
+                PrimitiveObstacle::Capsule(capsule),
+                MapElement,
+                MatchEntity,
+                Name::new("Map Obstacle"),
+            ));
+        }
+    }
+}
+
+/// Spawns the resources, which generators find again when the editor respawns them
+pub fn spawn_resource_nodes(
+    map: &Map,
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+) {
     for resource in map.resources.iter() {
         commands.spawn((
             MaterialMesh2dBundle {
@@ -328,74 +458,130 @@ fn render_map(
                 transform: Transform::from_translation(resource.position.extend(0.0)),
                 ..default()
             },
+            ResourceNode::new(resource.amount),
+            MatchEntity,
             Name::new("Resource"),
         ));
     }
+}
 
-    //	render obstacles
-    for obstacle in map.obstacles.iter() {
-        //	get shared values
-        let base_vector = obstacle.end - obstacle.start;
-        let half_base = base_vector / 2.0;
-        let mut transform = Transform::from_translation((half_base + obstacle.start).extend(0.0));
-        transform.rotate_z(base_vector.to_angle() - PI / 2.0);
+fn shrink_resource_nodes(mut nodes: Query<(&ResourceNode, &mut Transform), Changed<ResourceNode>>) {
+    for (node, mut transform) in nodes.iter_mut() {
+        let scale = node.remaining / node.capacity;
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+}
 
-        //	this will need to be somewhat complex
-        if obstacle.segments == 1 {
-            let capsule = Capsule2d::new(OBSTACLE_WIDTH, base_vector.length());
-            commands.spawn((
-                MaterialMesh2dBundle {
-                    mesh: Mesh2dHandle(meshes.add(capsule)),
-                    material: materials.add(OBSTACLE_COLOR),
-                    transform,
-                    ..default()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_map() -> Map {
+        Map {
+            size: Vec2::splat(1000.0),
+            starting_points: vec![
+                PlayerStart {
+                    position: Vec2::splat(100.0),
+                    team: TeamType(0),
                 },
-                PrimitiveObstacle::Capsule(capsule),
-                Name::new("Map Obstacle"),
-            ));
-        } else {
-            //	calculate the angles per middle segment
-            let height = (obstacle.radius * obstacle.radius - half_base.length_squared()).sqrt();
-            let origin = obstacle.start + half_base - base_vector.perp().normalize() * height;
-            let end_angle = (obstacle.end - origin).to_angle();
-            let full_angle = (obstacle.end - origin).angle_between(obstacle.start - origin);
-            let segment_angle = full_angle / (obstacle.segments as f32);
-            let segment_mid_angle = segment_angle / 2.0;
-            let segment_arc = Arc2d::from_radians(obstacle.radius, segment_angle);
-            let segment_length = segment_arc.chord_length().abs();
-
-            for i in 0..obstacle.segments {
-                let true_angle = (i as f32 * segment_angle) + segment_mid_angle + end_angle;
-                let true_midpoint = Vec2::new(
-                    obstacle.radius * f32::cos(true_angle),
-                    obstacle.radius * f32::sin(true_angle),
-                );
-                let mut transform =
-                    Transform::from_translation((true_midpoint + origin).extend(0.0));
-                transform.rotate_z(true_angle);
-
-                let capsule = Capsule2d::new(OBSTACLE_WIDTH, segment_length);
-                commands.spawn((
-                    MaterialMesh2dBundle {
-                        mesh: Mesh2dHandle(meshes.add(capsule)),
-                        material: materials.add(OBSTACLE_COLOR),
-                        transform,
-                        ..default()
-                    },
-                    PrimitiveObstacle::Capsule(capsule),
-                    Name::new("Map Obstacle"),
-                ));
-            }
+                PlayerStart {
+                    position: Vec2::splat(900.0),
+                    team: TeamType(1),
+                },
+            ],
+            resources: vec![MapResource {
+                position: Vec2::splat(500.0),
+                amount: 1000.0,
+            }],
+            obstacles: vec![
+                MapObstacle {
+                    start: Vec2::new(200.0, 500.0),
+                    end: Vec2::new(400.0, 500.0),
+                    radius: 1.0,
+                    segments: 1,
+                },
+                MapObstacle {
+                    start: Vec2::new(600.0, 500.0),
+                    end: Vec2::new(800.0, 500.0),
+                    radius: 150.0,
+                    segments: 8,
+                },
+            ],
         }
     }
 
-    //  set starting pos
-    for point in map.starting_points.iter() {
-        spawn_hero(
-            &mut commands,
-            &asset_server,
-            point.position,
-            point.team.clone(),
+    #[test]
+    fn a_valid_map_passes() {
+        assert!(valid_map().validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_maps_are_rejected() {
+        let mut map = valid_map();
+        map.size = Vec2::new(1000.0, 0.0);
+        assert!(matches!(map.validate(), Err(MapError::InvalidSize(_))));
+
+        let mut map = valid_map();
+        map.starting_points.clear();
+        assert!(matches!(map.validate(), Err(MapError::NoStartingPoints)));
+
+        let mut map = valid_map();
+        map.starting_points[1].team = TeamType(0);
+        assert!(matches!(map.validate(), Err(MapError::DuplicateStart(0))));
+
+        let mut map = valid_map();
+        map.resources[0].position = Vec2::new(1200.0, 500.0);
+        assert!(matches!(
+            map.validate(),
+            Err(MapError::OutOfBounds {
+                kind: "resource",
+                index: 0,
+                ..
+            })
+        ));
+
+        let mut map = valid_map();
+        map.resources[0].amount = 0.0;
+        assert!(matches!(
+            map.validate(),
+            Err(MapError::EmptyResource { index: 0, .. })
+        ));
+
+        let mut map = valid_map();
+        map.obstacles[0].end = map.obstacles[0].start;
+        assert!(matches!(
+            map.validate(),
+            Err(MapError::ZeroLengthObstacle { index: 0 })
+        ));
+
+        let mut map = valid_map();
+        map.obstacles[1].segments = 0;
+        assert!(matches!(
+            map.validate(),
+            Err(MapError::NoSegments { index: 1 })
+        ));
+
+        let mut map = valid_map();
+        map.obstacles[1].radius = 50.0;
+        assert!(matches!(
+            map.validate(),
+            Err(MapError::ArcTooTight { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn obstacle_points_run_from_start_to_end() {
+        let map = valid_map();
+
+        assert_eq!(
+            map.obstacles[0].points(),
+            vec![map.obstacles[0].start, map.obstacles[0].end]
         );
+
+        let arc = &map.obstacles[1];
+        let points = arc.points();
+        assert_eq!(points.len(), arc.segments + 1);
+        assert!(points[0].distance(arc.start) < 0.01);
+        assert!(points[arc.segments].distance(arc.end) < 0.01);
     }
 }
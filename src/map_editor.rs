@@ -0,0 +1,449 @@
+use std::fs;
+
+use bevy::{
+    color::palettes::tailwind::{BLUE_300, GRAY_100, ORANGE_300, RED_300, YELLOW_300},
+    prelude::*,
+};
+use ron::ser::PrettyConfig;
+
+use crate::{
+    inputs::{handle_keys, mouse_is_hovered_over, store_mouse_position, MousePosition},
+    map::{
+        spawn_map_elements, spawn_resource_nodes, Map, MapElement, MapObstacle, MapResource,
+        PlayerStart, ResourceNode, DEFAULT_MAP_PATH,
+    },
+    state::GameState,
+    teams::{TeamType, MAX_PLAYERS},
+};
+
+const HANDLE_RADIUS: f32 = 24.0;
+const MIN_OBSTACLE_LENGTH: f32 = 20.0;
+const ARC_SEGMENTS: usize = 12;
+const RADIUS_STEP: f32 = 50.0;
+const AMOUNT_STEP: f32 = 100.0;
+const NEW_RESOURCE_AMOUNT: f32 = 1200.0;
+
+const OBSTACLE_HANDLE_COLOR: Srgba = ORANGE_300;
+const RESOURCE_HANDLE_COLOR: Srgba = RED_300;
+const START_HANDLE_COLOR: Srgba = BLUE_300;
+const SELECTED_HANDLE_COLOR: Srgba = YELLOW_300;
+
+pub struct MapEditorPlugin;
+
+impl Plugin for MapEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::MapEditor), show_editor_help)
+            .add_systems(OnExit(GameState::MapEditor), hide_editor_help)
+            .add_systems(
+                Update,
+                (
+                    store_mouse_position,
+                    handle_keys,
+                    select_tool,
+                    edit_map.run_if(mouse_is_hovered_over::<true>),
+                    release_handle,
+                    adjust_selected,
+                    save_map,
+                    rerender_map,
+                    draw_editor_handles,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::MapEditor).and_then(resource_exists::<Map>)),
+            )
+            .insert_resource(MapEditor {
+                tool: EditorTool::Select,
+                selected: None,
+                dragging: false,
+                drag_start: None,
+                save_path: DEFAULT_MAP_PATH.into(),
+                edited: false,
+            });
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditorTool {
+    Select,
+    Segment,
+    Arc,
+    Resource,
+    Start,
+}
+
+/// A draggable point of the map layout
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditorHandle {
+    ObstacleStart(usize),
+    ObstacleEnd(usize),
+    Resource(usize),
+    Start(usize),
+}
+
+#[derive(Resource)]
+pub struct MapEditor {
+    pub tool: EditorTool,
+    pub selected: Option<EditorHandle>,
+    pub dragging: bool,
+    pub drag_start: Option<Vec2>,
+    /// path within the assets folder that the map is saved to
+    pub save_path: String,
+    /// whether the map has been edited since its entities were last spawned
+    pub edited: bool,
+}
+
+#[derive(Component)]
+struct EditorHelpText;
+
+fn show_editor_help(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "MAP EDITOR (F2 to exit)\
+            \n1 Select  2 Segment  3 Arc  4 Resource  5 Start\
+            \nDrag a handle to move it, Delete to remove it\
+            \nQ/E radius or amount, Z/X arc segments\
+            \nF5 to save",
+            TextStyle { ..default() },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.),
+            right: Val::Px(12.),
+            ..default()
+        }),
+        EditorHelpText,
+    ));
+}
+
+fn hide_editor_help(texts: Query<Entity, With<EditorHelpText>>, mut commands: Commands) {
+    for entity in texts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn select_tool(keys: Res<ButtonInput<KeyCode>>, mut editor: ResMut<MapEditor>) {
+    let tool = if keys.just_pressed(KeyCode::Digit1) {
+        EditorTool::Select
+    } else if keys.just_pressed(KeyCode::Digit2) {
+        EditorTool::Segment
+    } else if keys.just_pressed(KeyCode::Digit3) {
+        EditorTool::Arc
+    } else if keys.just_pressed(KeyCode::Digit4) {
+        EditorTool::Resource
+    } else if keys.just_pressed(KeyCode::Digit5) {
+        EditorTool::Start
+    } else {
+        return;
+    };
+
+    editor.tool = tool;
+    editor.drag_start = None;
+}
+
+fn edit_map(
+    mouse_position: Res<MousePosition>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mut editor: ResMut<MapEditor>,
+    mut map: ResMut<Map>,
+) {
+    let pos = mouse_position.0;
+
+    if mouse_button_input.just_pressed(MouseButton::Left) {
+        //  grabbing an existing handle always wins over placing something new
+        if let Some(handle) = find_handle(&map, pos) {
+            editor.selected = Some(handle);
+            editor.dragging = true;
+            return;
+        }
+
+        editor.selected = None;
+        match editor.tool {
+            EditorTool::Select => (),
+            EditorTool::Segment | EditorTool::Arc => {
+                editor.drag_start = Some(pos);
+            }
+            EditorTool::Resource => {
+                map.resources.push(MapResource {
+                    position: pos,
+                    amount: NEW_RESOURCE_AMOUNT,
+                });
+                editor.edited = true;
+                editor.selected = Some(EditorHandle::Resource(map.resources.len() - 1));
+            }
+            EditorTool::Start => {
+                let next_team = (0..MAX_PLAYERS)
+                    .map(TeamType)
+                    .find(|team| !map.starting_points.iter().any(|p| p.team == *team));
+
+                if let Some(team) = next_team {
+                    map.starting_points.push(PlayerStart {
+                        position: pos,
+                        team,
+                    });
+                    editor.edited = true;
+                    editor.selected = Some(EditorHandle::Start(map.starting_points.len() - 1));
+                }
+            }
+        }
+    } else if mouse_button_input.pressed(MouseButton::Left) {
+        if editor.dragging {
+            if let Some(handle) = editor.selected {
+                move_handle(&mut map, handle, pos);
+                editor.edited = true;
+            }
+        }
+    } else if mouse_button_input.just_released(MouseButton::Left) {
+        editor.dragging = false;
+
+        if let Some(start) = editor.drag_start.take() {
+            if start.distance(pos) < MIN_OBSTACLE_LENGTH {
+                return;
+            }
+
+            let (radius, segments) = match editor.tool {
+                EditorTool::Arc => (start.distance(pos), ARC_SEGMENTS),
+                _ => (1.0, 1),
+            };
+            map.obstacles.push(MapObstacle {
+                start,
+                end: pos,
+                radius,
+                segments,
+            });
+            editor.edited = true;
+            editor.selected = Some(EditorHandle::ObstacleEnd(map.obstacles.len() - 1));
+        }
+    }
+}
+
+//  a button released over the ui never reaches edit_map, so it is let go of here as well
+fn release_handle(
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mut editor: ResMut<MapEditor>,
+) {
+    if mouse_button_input.just_released(MouseButton::Left) {
+        editor.dragging = false;
+        editor.drag_start = None;
+    }
+}
+
+fn adjust_selected(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<MapEditor>,
+    mut map: ResMut<Map>,
+) {
+    let Some(handle) = editor.selected else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Delete) {
+        match handle {
+            EditorHandle::ObstacleStart(index) | EditorHandle::ObstacleEnd(index) => {
+                map.obstacles.remove(index);
+            }
+            EditorHandle::Resource(index) => {
+                map.resources.remove(index);
+            }
+            EditorHandle::Start(index) => {
+                map.starting_points.remove(index);
+            }
+        }
+        editor.selected = None;
+        editor.dragging = false;
+        editor.edited = true;
+        return;
+    }
+
+    let step = if keys.just_pressed(KeyCode::KeyE) {
+        1.0
+    } else if keys.just_pressed(KeyCode::KeyQ) {
+        -1.0
+    } else {
+        0.0
+    };
+    let segment_step: isize = if keys.just_pressed(KeyCode::KeyX) {
+        1
+    } else if keys.just_pressed(KeyCode::KeyZ) {
+        -1
+    } else {
+        0
+    };
+
+    if step == 0.0 && segment_step == 0 {
+        return;
+    }
+
+    match handle {
+        EditorHandle::ObstacleStart(index) | EditorHandle::ObstacleEnd(index) => {
+            let obstacle = &mut map.obstacles[index];
+            let half_chord = obstacle.start.distance(obstacle.end) / 2.0;
+
+            obstacle.segments = obstacle.segments.saturating_add_signed(segment_step).max(1);
+            obstacle.radius = (obstacle.radius + step * RADIUS_STEP).max(half_chord);
+        }
+        EditorHandle::Resource(index) => {
+            let resource = &mut map.resources[index];
+            resource.amount = (resource.amount + step * AMOUNT_STEP).max(AMOUNT_STEP);
+        }
+        EditorHandle::Start(_) => (),
+    }
+    editor.edited = true;
+}
+
+fn save_map(keys: Res<ButtonInput<KeyCode>>, editor: Res<MapEditor>, map: Res<Map>) {
+    if !keys.just_pressed(KeyCode::F5) {
+        return;
+    }
+
+    if let Err(error) = map.validate() {
+        error!("map not saved: {}", error);
+        return;
+    }
+
+    let path = format!("assets/{}", editor.save_path);
+    match ron::ser::to_string_pretty(&*map, PrettyConfig::default()) {
+        Ok(contents) => match fs::write(&path, contents) {
+            Ok(_) => info!("map saved to {}", path),
+            Err(error) => error!("map not saved to {}: {}", path, error),
+        },
+        Err(error) => error!("map not saved: {}", error),
+    }
+}
+
+/// Everything spawned from the map layout
+type MapEntities = Or<(With<MapElement>, With<ResourceNode>)>;
+
+/// Rebuilds the map entities after an edit, which also rebuilds the navmesh.
+/// Only edits count, since the map resource also reads as changed whenever the editor is opened.
+/// Resource nodes are respawned full, as the map lays them out.
+fn rerender_map(
+    mut editor: ResMut<MapEditor>,
+    map: Res<Map>,
+    elements: Query<Entity, MapEntities>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !editor.edited {
+        return;
+    }
+    editor.edited = false;
+
+    for entity in elements.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    spawn_map_elements(&map, &mut commands, &mut meshes, &mut materials);
+    spawn_resource_nodes(&map, &mut commands, &mut meshes, &mut materials);
+}
+
+fn draw_editor_handles(
+    editor: Res<MapEditor>,
+    map: Res<Map>,
+    mouse_position: Res<MousePosition>,
+    mut gizmos: Gizmos,
+) {
+    for (index, obstacle) in map.obstacles.iter().enumerate() {
+        gizmos.line_2d(obstacle.start, obstacle.end, OBSTACLE_HANDLE_COLOR);
+        draw_handle(
+            &mut gizmos,
+            &editor,
+            EditorHandle::ObstacleStart(index),
+            obstacle.start,
+            OBSTACLE_HANDLE_COLOR,
+        );
+        draw_handle(
+            &mut gizmos,
+            &editor,
+            EditorHandle::ObstacleEnd(index),
+            obstacle.end,
+            OBSTACLE_HANDLE_COLOR,
+        );
+    }
+
+    for (index, resource) in map.resources.iter().enumerate() {
+        draw_handle(
+            &mut gizmos,
+            &editor,
+            EditorHandle::Resource(index),
+            resource.position,
+            RESOURCE_HANDLE_COLOR,
+        );
+    }
+
+    for (index, point) in map.starting_points.iter().enumerate() {
+        draw_handle(
+            &mut gizmos,
+            &editor,
+            EditorHandle::Start(index),
+            point.position,
+            START_HANDLE_COLOR,
+        );
+    }
+
+    //  preview the obstacle being placed
+    if let Some(start) = editor.drag_start {
+        gizmos.line_2d(start, mouse_position.0, GRAY_100);
+    }
+}
+
+fn draw_handle(
+    gizmos: &mut Gizmos,
+    editor: &MapEditor,
+    handle: EditorHandle,
+    position: Vec2,
+    color: Srgba,
+) {
+    let color = if editor.selected == Some(handle) {
+        SELECTED_HANDLE_COLOR
+    } else {
+        color
+    };
+
+    gizmos.circle_2d(position, HANDLE_RADIUS, color);
+}
+
+fn find_handle(map: &Map, position: Vec2) -> Option<EditorHandle> {
+    let mut handles: Vec<(EditorHandle, Vec2)> = Vec::new();
+
+    for (index, obstacle) in map.obstacles.iter().enumerate() {
+        handles.push((EditorHandle::ObstacleStart(index), obstacle.start));
+        handles.push((EditorHandle::ObstacleEnd(index), obstacle.end));
+    }
+    for (index, resource) in map.resources.iter().enumerate() {
+        handles.push((EditorHandle::Resource(index), resource.position));
+    }
+    for (index, point) in map.starting_points.iter().enumerate() {
+        handles.push((EditorHandle::Start(index), point.position));
+    }
+
+    handles
+        .into_iter()
+        .filter(|(_, point)| point.distance(position) < HANDLE_RADIUS)
+        .min_by(|(_, a), (_, b)| a.distance(position).total_cmp(&b.distance(position)))
+        .map(|(handle, _)| handle)
+}
+
+fn move_handle(map: &mut Map, handle: EditorHandle, position: Vec2) {
+    match handle {
+        EditorHandle::ObstacleStart(index) | EditorHandle::ObstacleEnd(index) => {
+            let obstacle = &mut map.obstacles[index];
+            if let EditorHandle::ObstacleStart(_) = handle {
+                obstacle.start = position;
+            } else {
+                obstacle.end = position;
+            }
+
+            //	keep arcs valid, as a radius under the half chord has no circle to sit on
+            if obstacle.segments > 1 {
+                let half_chord = obstacle.start.distance(obstacle.end) / 2.0;
+                obstacle.radius = obstacle.radius.max(half_chord);
+            }
+        }
+        EditorHandle::Resource(index) => {
+            map.resources[index].position = position;
+        }
+        EditorHandle::Start(index) => {
+            map.starting_points[index].position = position;
+        }
+    }
+}
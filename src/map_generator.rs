@@ -0,0 +1,315 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+use polyanya::{Mesh, Triangulation};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    map::{Map, MapError, MapObstacle, MapResource, PlayerStart, OBSTACLE_WIDTH},
+    teams::{TeamType, MAX_PLAYERS},
+};
+
+const MAX_ATTEMPTS: usize = 32;
+
+//	the first player sits in the SW corner, as in the hand-made maps
+const FIRST_START_ANGLE: f32 = -3.0 * PI / 4.0;
+
+const EDGE_MARGIN: f32 = 150.0;
+const CLEARANCE: f32 = 250.0;
+const OBSTACLE_SPACING: f32 = 120.0;
+
+const HOME_RESOURCE_AMOUNT: f32 = 1800.0;
+const LANE_RESOURCE_AMOUNT: f32 = 1200.0;
+const CENTRAL_AMOUNT_PER_PLAYER: f32 = 1150.0;
+
+/// What to build a random map from, where the same settings always give the same map
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GeneratorSettings {
+    pub seed: u64,
+    pub players: usize,
+    pub size: f32,
+    pub obstacles_per_player: usize,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            players: 4,
+            size: 5000.0,
+            obstacles_per_player: 4,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GeneratorError {
+    #[error("a generated map needs between 2 and {MAX_PLAYERS} players, found {0}")]
+    InvalidPlayers(usize),
+    #[error("map size of {0} is too small for a generated map")]
+    TooSmall(f32),
+    #[error("no map with every start connected was found in {0} attempts")]
+    Unreachable(usize),
+    #[error("generated an invalid map: {0}")]
+    Invalid(#[from] MapError),
+}
+
+/// Builds a map that is rotationally symmetric around its center, one wedge per player.
+/// Layouts where a start cannot walk to every other start and the center are thrown away.
+pub fn generate_map(settings: &GeneratorSettings) -> Result<Map, GeneratorError> {
+    if settings.players < 2 || settings.players > MAX_PLAYERS {
+        return Err(GeneratorError::InvalidPlayers(settings.players));
+    }
+    if settings.size < 8.0 * CLEARANCE {
+        return Err(GeneratorError::TooSmall(settings.size));
+    }
+
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+
+    for _ in 0..MAX_ATTEMPTS {
+        let map = generate_layout(settings, &mut rng);
+        map.validate()?;
+
+        if is_connected(&map) {
+            return Ok(map);
+        }
+    }
+
+    Err(GeneratorError::Unreachable(MAX_ATTEMPTS))
+}
+
+fn generate_layout(settings: &GeneratorSettings, rng: &mut StdRng) -> Map {
+    let center = Vec2::splat(settings.size / 2.0);
+    //	staying inside the inscribed circle keeps every rotated copy on the map
+    let reach = settings.size / 2.0 - EDGE_MARGIN;
+    let wedge = TAU / settings.players as f32;
+
+    //	lay out the wedge of the first player, relative to the center
+    let start_distance = reach * rng.gen_range(0.68..0.8);
+    let start = polar(FIRST_START_ANGLE, start_distance);
+
+    let home_resource = polar(
+        FIRST_START_ANGLE + rng.gen_range(-0.15..0.15),
+        (start_distance + rng.gen_range(200.0..350.0)).min(reach),
+    );
+    //	lane resources sit on the border of two wedges, so neighbours share them
+    let lane_resource = polar(
+        FIRST_START_ANGLE + wedge / 2.0,
+        reach * rng.gen_range(0.45..0.8),
+    );
+
+    let keep_clear = [Vec2::ZERO, start, home_resource, lane_resource];
+    let mut obstacles: Vec<MapObstacle> = Vec::new();
+    for _ in 0..settings.obstacles_per_player * 8 {
+        if obstacles.len() >= settings.obstacles_per_player {
+            break;
+        }
+
+        let obstacle = random_obstacle(rng, reach, wedge);
+        if fits(&obstacle, &obstacles, &keep_clear, reach, wedge) {
+            obstacles.push(obstacle);
+        }
+    }
+
+    //	copy the wedge around the center
+    let mut map = Map {
+        size: Vec2::splat(settings.size),
+        starting_points: Vec::new(),
+        resources: vec![MapResource {
+            position: center,
+            amount: CENTRAL_AMOUNT_PER_PLAYER * settings.players as f32,
+        }],
+        obstacles: Vec::new(),
+    };
+
+    for player in 0..settings.players {
+        let rotation = Vec2::from_angle(wedge * player as f32);
+        let at = |point: Vec2| center + rotation.rotate(point);
+
+        map.starting_points.push(PlayerStart {
+            position: at(start),
+            team: TeamType(player),
+        });
+        map.resources.push(MapResource {
+            position: at(home_resource),
+            amount: HOME_RESOURCE_AMOUNT,
+        });
+        map.resources.push(MapResource {
+            position: at(lane_resource),
+            amount: LANE_RESOURCE_AMOUNT,
+        });
+        map.obstacles
+            .extend(obstacles.iter().map(|obstacle| MapObstacle {
+                start: at(obstacle.start),
+                end: at(obstacle.end),
+                radius: obstacle.radius,
+                segments: obstacle.segments,
+            }));
+    }
+
+    map
+}
+
+fn random_obstacle(rng: &mut StdRng, reach: f32, wedge: f32) -> MapObstacle {
+    let midpoint = polar(
+        FIRST_START_ANGLE + rng.gen_range(-0.5..0.5) * wedge,
+        reach * rng.gen_range(0.3..0.95),
+    );
+    let half_chord = rng.gen_range(150.0..450.0);
+    let direction = Vec2::from_angle(rng.gen_range(0.0..TAU));
+
+    //	some straight walls, mostly arcs
+    let (radius, segments) = if rng.gen_bool(0.3) {
+        (1.0, 1)
+    } else {
+        (half_chord * rng.gen_range(1.1..3.0), rng.gen_range(8..=16))
+    };
+
+    MapObstacle {
+        start: midpoint - direction * half_chord,
+        end: midpoint + direction * half_chord,
+        radius,
+        segments,
+    }
+}
+
+/// whether an obstacle stays in its own wedge, away from everything players need to reach
+fn fits(
+    obstacle: &MapObstacle,
+    placed: &[MapObstacle],
+    keep_clear: &[Vec2],
+    reach: f32,
+    wedge: f32,
+) -> bool {
+    let points = obstacle.points();
+
+    points.iter().all(|point| {
+        let offset = (point.to_angle() - FIRST_START_ANGLE + PI).rem_euclid(TAU) - PI;
+        //	the arc width keeps rotated copies from touching across the wedge border
+        let border_gap = OBSTACLE_SPACING / point.length().max(1.0);
+
+        point.length() <= reach
+            && offset.abs() <= wedge / 2.0 - border_gap
+            && keep_clear
+                .iter()
+                .all(|clear| clear.distance(*point) >= CLEARANCE)
+            && placed.iter().all(|other| {
+                other
+                    .points()
+                    .iter()
+                    .all(|other_point| other_point.distance(*point) >= OBSTACLE_SPACING)
+            })
+    })
+}
+
+fn polar(angle: f32, distance: f32) -> Vec2 {
+    Vec2::from_angle(angle) * distance
+}
+
+/// Checks on a navmesh with the map obstacles cut out that every start reaches every other
+/// start and the central resource
+fn is_connected(map: &Map) -> bool {
+    let mut triangulation = Triangulation::from_outer_edges(&[
+        Vec2::ZERO,
+        Vec2::new(map.size.x, 0.0),
+        map.size,
+        Vec2::new(0.0, map.size.y),
+    ]);
+    triangulation.add_obstacles(
+        map.obstacles
+            .iter()
+            .map(|obstacle| outline(&obstacle.points(), OBSTACLE_WIDTH)),
+    );
+    let mesh: Mesh = triangulation.as_navmesh();
+
+    let center = map.size / 2.0;
+    let starts: Vec<Vec2> = map.starting_points.iter().map(|p| p.position).collect();
+
+    starts.iter().enumerate().all(|(index, from)| {
+        mesh.path(*from, center).is_some()
+            && starts
+                .iter()
+                .skip(index + 1)
+                .all(|to| mesh.path(*from, *to).is_some())
+    })
+}
+
+/// A polygon around a line of points, thickened by the given width on each side
+fn outline(points: &[Vec2], width: f32) -> Vec<Vec2> {
+    let last = points.len() - 1;
+
+    //	round the ends out like the capsules that are spawned for them
+    let mut line = points.to_vec();
+    line[0] -= (points[1] - points[0]).normalize_or_zero() * width;
+    line[last] += (points[last] - points[last - 1]).normalize_or_zero() * width;
+
+    let normal_at = |i: usize| {
+        let before = line[i.saturating_sub(1)];
+        let after = line[(i + 1).min(last)];
+        (after - before).normalize_or_zero().perp() * width
+    };
+
+    (0..=last)
+        .map(|i| line[i] + normal_at(i))
+        .chain((0..=last).rev().map(|i| line[i] - normal_at(i)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(seed: u64) -> GeneratorSettings {
+        GeneratorSettings { seed, ..default() }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_map() {
+        let first = generate_map(&settings(7)).expect("map generates");
+        let second = generate_map(&settings(7)).expect("map generates");
+
+        assert_eq!(format!("{:?}", first), format!("{:?}", second));
+    }
+
+    #[test]
+    fn different_seeds_give_different_maps() {
+        let first = generate_map(&settings(7)).expect("map generates");
+        let second = generate_map(&settings(8)).expect("map generates");
+
+        assert_ne!(format!("{:?}", first), format!("{:?}", second));
+    }
+
+    #[test]
+    fn generated_maps_have_a_start_for_every_player() {
+        for players in 2..=MAX_PLAYERS {
+            let map = generate_map(&GeneratorSettings {
+                players,
+                ..settings(3)
+            })
+            .expect("map generates");
+
+            assert_eq!(map.starting_points.len(), players);
+            assert!(map.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        assert!(matches!(
+            generate_map(&GeneratorSettings {
+                players: 1,
+                ..default()
+            }),
+            Err(GeneratorError::InvalidPlayers(1))
+        ));
+        assert!(matches!(
+            generate_map(&GeneratorSettings {
+                size: 100.0,
+                ..default()
+            }),
+            Err(GeneratorError::TooSmall(_))
+        ));
+    }
+}
@@ -0,0 +1,269 @@
+use bevy::{
+    ecs::{schedule::ExecutorKind, system::SystemParam},
+    prelude::*,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::AIDifficulty,
+    archetypes::archetypes_ready,
+    diplomacy::Diplomacy,
+    fog::FogOfWar,
+    headless::HeadlessSettings,
+    map::{load_map, Map, MapHandle, MapLoaded, MapSource, DEFAULT_MAP_PATH},
+    map_generator::GeneratorSettings,
+    replay::ReplayRecorder,
+    selectable::{SelectedStructures, SelectedUnits, SelectionStateChanged, SelectionType},
+    state::GameState,
+    teams::{Controller, Players, TeamType},
+    victory::{MatchProgress, MatchResult, MatchRules},
+};
+
+pub struct MatchSetupPlugin;
+
+impl Plugin for MatchSetupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                teardown_match.run_if(on_event::<StartMatch>().or_else(on_event::<EndMatch>())),
+                request_match.run_if(on_event::<StartMatch>()),
+                (begin_match, load_map)
+                    .chain()
+                    .run_if(resource_equals(PendingMatch(true)).and_then(archetypes_ready)),
+                return_to_menu.run_if(on_event::<EndMatch>()),
+                focus_camera_on_start.run_if(on_event::<MapLoaded>()),
+            )
+                .chain(),
+        )
+        .add_systems(OnEnter(GameState::InGame), pick_executor)
+        .add_event::<StartMatch>()
+        .add_event::<EndMatch>()
+        .insert_resource(MatchSettings::default())
+        .insert_resource(PendingMatch(false))
+        .insert_resource(MatchRng::seeded(0));
+    }
+}
+
+/// Everything spawned for a match, which is despawned when it ends or restarts
+#[derive(Component)]
+pub struct MatchEntity;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TeamSetup {
+    FreeForAll,
+    /// even player ids against odd ones
+    TwoTeams,
+}
+
+impl TeamSetup {
+    pub fn diplomacy(&self, players: &Players) -> Diplomacy {
+        match self {
+            TeamSetup::FreeForAll => Diplomacy::free_for_all(),
+            TeamSetup::TwoTeams => {
+                let (evens, odds): (Vec<TeamType>, Vec<TeamType>) = players
+                    .list
+                    .iter()
+                    .map(|player| player.team)
+                    .partition(|team| team.0 % 2 == 0);
+
+                Diplomacy::alliances(&[evens, odds])
+            }
+        }
+    }
+}
+
+/// Who gives the orders in the next match
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum MatchMode {
+    /// the local player against the AI
+    Play,
+    /// every team is left to the AI, with the local player only watching
+    Spectate,
+    /// every team follows the commands of a recorded replay
+    Replay,
+    /// the first `humans` teams are played over the network, this machine playing `local`
+    Lockstep { local: TeamType, humans: usize },
+}
+
+/// What the next match is started with, as chosen in the main menu
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct MatchSettings {
+    pub map: MapSource,
+    pub players: usize,
+    pub teams: TeamSetup,
+    pub difficulty: AIDifficulty,
+    pub rules: MatchRules,
+    pub mode: MatchMode,
+    /// a fixed seed replays the same match, otherwise a new one is picked every start
+    pub seed: Option<u64>,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self {
+            map: MapSource::File(DEFAULT_MAP_PATH.into()),
+            players: 2,
+            teams: TeamSetup::FreeForAll,
+            difficulty: AIDifficulty::Normal,
+            rules: MatchRules::default(),
+            mode: MatchMode::Play,
+            seed: None,
+        }
+    }
+}
+
+/// The only source of randomness during a match, so the same seed plays out the same way
+#[derive(Resource)]
+pub struct MatchRng {
+    pub seed: u64,
+    pub rng: StdRng,
+}
+
+impl MatchRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+/// Tears down any running match and starts a new one from the `MatchSettings`
+#[derive(Event)]
+pub struct StartMatch;
+
+/// Tears down the running match and returns to the main menu
+#[derive(Event)]
+pub struct EndMatch;
+
+/// Set while a started match waits on the archetypes, as the map spawns its units from them
+#[derive(Resource, PartialEq)]
+struct PendingMatch(bool);
+
+fn teardown_match(
+    entities: Query<Entity, With<MatchEntity>>,
+    players: Res<Players>,
+    mut map: ResMut<Map>,
+    mut selection_state_changed: EventWriter<SelectionStateChanged>,
+    mut commands: Commands,
+) {
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    //	close whatever the last player had open
+    selection_state_changed.send(SelectionStateChanged {
+        new_type: SelectionType::None,
+        team: players.local,
+    });
+
+    *map = Map::default();
+    commands.remove_resource::<MapHandle>();
+    commands.insert_resource(SelectedUnits {
+        entities: Default::default(),
+    });
+    commands.insert_resource(SelectedStructures {
+        entities: Default::default(),
+    });
+    commands.insert_resource(MatchProgress::default());
+    commands.insert_resource(MatchResult::default());
+    commands.insert_resource(FogOfWar::default());
+}
+
+fn request_match(mut pending: ResMut<PendingMatch>) {
+    pending.0 = true;
+}
+
+/// Everything the settings decide about the match that is about to begin
+#[derive(SystemParam)]
+struct MatchConfig<'w> {
+    players: ResMut<'w, Players>,
+    diplomacy: ResMut<'w, Diplomacy>,
+    rules: ResMut<'w, MatchRules>,
+    difficulty: ResMut<'w, AIDifficulty>,
+    map_source: ResMut<'w, MapSource>,
+    rng: ResMut<'w, MatchRng>,
+}
+
+fn begin_match(
+    mut pending: ResMut<PendingMatch>,
+    settings: Res<MatchSettings>,
+    mut config: MatchConfig,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    *config.players = match settings.mode {
+        MatchMode::Play => Players::new(settings.players, TeamType(0)),
+        MatchMode::Spectate => Players::all(settings.players, TeamType(0), Controller::CPU),
+        MatchMode::Replay => Players::all(settings.players, TeamType(0), Controller::Replay),
+        MatchMode::Lockstep { local, humans } => {
+            Players::networked(settings.players, humans, local)
+        }
+    };
+    *config.diplomacy = settings.teams.diplomacy(&config.players);
+    *config.rules = settings.rules.clone();
+    *config.difficulty = settings.difficulty;
+    *config.map_source = match &settings.map {
+        MapSource::Generated(generator) => MapSource::Generated(GeneratorSettings {
+            players: settings.players,
+            ..generator.clone()
+        }),
+        source => source.clone(),
+    };
+    *config.rng = MatchRng::seeded(settings.seed.unwrap_or_else(rand::random));
+
+    pending.0 = false;
+    next_state.set(GameState::InGame);
+}
+
+//	lockstep peers, replays, the matches they are recorded from and headless runs have to
+//	play out the same way every time, which a multi-threaded executor only does for systems
+//	with an order between them, so their frames run one system at a time in schedule order
+fn pick_executor(world: &mut World) {
+    let lockstep_or_replay = matches!(
+        world.resource::<MatchSettings>().mode,
+        MatchMode::Lockstep { .. } | MatchMode::Replay
+    );
+    let recorded = world
+        .get_resource::<ReplayRecorder>()
+        .is_some_and(|recorder| recorder.path.is_some());
+    let headless = world.contains_resource::<HeadlessSettings>();
+
+    let kind = if lockstep_or_replay || recorded || headless {
+        ExecutorKind::SingleThreaded
+    } else {
+        ExecutorKind::MultiThreaded
+    };
+
+    let mut schedules = world.resource_mut::<Schedules>();
+    if let Some(update) = schedules.get_mut(Update) {
+        if update.get_executor_kind() != kind {
+            update.set_executor_kind(kind);
+        }
+    }
+}
+
+fn return_to_menu(mut pending: ResMut<PendingMatch>, mut next_state: ResMut<NextState<GameState>>) {
+    pending.0 = false;
+    next_state.set(GameState::MainMenu);
+}
+
+fn focus_camera_on_start(
+    map: Res<Map>,
+    players: Res<Players>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+) {
+    let Some(start) = map
+        .starting_points
+        .iter()
+        .find(|point| players.is_local(&point.team))
+    else {
+        return;
+    };
+
+    for mut transform in camera.iter_mut() {
+        transform.translation.x = start.position.x;
+        transform.translation.y = start.position.y;
+    }
+}
@@ -0,0 +1,372 @@
+use std::fs;
+
+use bevy::{
+    app::AppExit,
+    color::palettes::tailwind::{GRAY_200, GRAY_900},
+    prelude::*,
+    utils::HashMap,
+};
+
+use crate::{
+    ai::AIDifficulty,
+    map::{Map, MapSource, DEFAULT_MAP_PATH},
+    map_generator::GeneratorSettings,
+    match_setup::{EndMatch, MatchSettings, StartMatch, TeamSetup},
+    state::GameState,
+    teams::MAX_PLAYERS,
+};
+
+const MAPS_DIRECTORY: &str = "maps";
+const MAP_EXTENSION: &str = ".map.ron";
+
+const MENU_BACKGROUND: Color = Color::Srgba(GRAY_900);
+const PAUSE_BACKGROUND: Color = Color::linear_rgba(0.0, 0.0, 0.0, 0.6);
+const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.35, 0.35);
+const HOVERED_BUTTON: Color = Color::srgb(0.2, 0.45, 0.45);
+const BUTTON_BORDER: Color = Color::Srgba(GRAY_200);
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
+            .add_systems(OnExit(GameState::MainMenu), despawn_menu::<MainMenu>)
+            .add_systems(OnEnter(GameState::Paused), spawn_pause_menu)
+            .add_systems(OnExit(GameState::Paused), despawn_menu::<PauseMenu>)
+            .add_systems(
+                Update,
+                (
+                    menu_button_interactions,
+                    update_menu_labels,
+                    game_over_input.run_if(in_state(GameState::GameOver)),
+                )
+                    .chain(),
+            )
+            .insert_resource(MapChoices::new());
+    }
+}
+
+#[derive(Component)]
+struct MainMenu;
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+enum MenuButton {
+    NewGame,
+    CycleMap,
+    CyclePlayers,
+    CycleTeams,
+    CycleDifficulty,
+    Quit,
+    Resume,
+    Restart,
+    QuitToMenu,
+}
+
+#[derive(Component)]
+struct MenuLabel;
+
+/// The maps shipped in the assets folder, with a generated map offered after them
+#[derive(Resource)]
+struct MapChoices {
+    files: Vec<String>,
+    /// how many players each map has a start for
+    starts: HashMap<String, usize>,
+    current: usize,
+}
+
+impl MapChoices {
+    fn new() -> Self {
+        let mut files: Vec<String> = fs::read_dir(format!("assets/{}", MAPS_DIRECTORY))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .filter(|name| name.ends_with(MAP_EXTENSION))
+                    .map(|name| format!("{}/{}", MAPS_DIRECTORY, name))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+
+        if files.is_empty() {
+            files.push(DEFAULT_MAP_PATH.into());
+        }
+
+        let current = files
+            .iter()
+            .position(|path| path == DEFAULT_MAP_PATH)
+            .unwrap_or_default();
+
+        //	a map that does not read is left out, and fails when the match loads it instead
+        let starts = files
+            .iter()
+            .filter_map(|path| {
+                let bytes = fs::read(format!("assets/{}", path)).ok()?;
+                let map: Map = ron::de::from_bytes(&bytes).ok()?;
+                Some((path.clone(), map.starting_points.len()))
+            })
+            .collect();
+
+        Self {
+            files,
+            starts,
+            current,
+        }
+    }
+
+    /// the most players a match on the map can be set up for
+    fn max_players(&self, map: &MapSource) -> usize {
+        let starts = match map {
+            MapSource::File(path) => self.starts.get(path).copied().unwrap_or(MAX_PLAYERS),
+            MapSource::Generated(_) => MAX_PLAYERS,
+            MapSource::Saved(map) => map.starting_points.len(),
+        };
+
+        starts.clamp(2, MAX_PLAYERS)
+    }
+
+    /// moves on to the next map, rolling a new seed whenever the generated map comes up
+    fn next(&mut self) -> MapSource {
+        self.current = (self.current + 1) % (self.files.len() + 1);
+
+        match self.files.get(self.current) {
+            Some(path) => MapSource::File(path.clone()),
+            None => MapSource::Generated(GeneratorSettings {
+                seed: rand::random(),
+                ..default()
+            }),
+        }
+    }
+}
+
+impl MenuButton {
+    fn label(&self, settings: &MatchSettings) -> String {
+        match self {
+            MenuButton::NewGame => "New Game".into(),
+            MenuButton::CycleMap => match &settings.map {
+                MapSource::File(path) => {
+                    let name = path
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or(path)
+                        .trim_end_matches(MAP_EXTENSION);
+                    format!("Map: {}", name)
+                }
+                MapSource::Generated(generator) => format!("Map: Generated #{}", generator.seed),
+                MapSource::Saved(_) => "Map: Saved".into(),
+            },
+            MenuButton::CyclePlayers => format!("Players: {}", settings.players),
+            MenuButton::CycleTeams => match settings.teams {
+                TeamSetup::FreeForAll => "Teams: Free for All".into(),
+                TeamSetup::TwoTeams => "Teams: Two Teams".into(),
+            },
+            MenuButton::CycleDifficulty => format!("AI: {:?}", settings.difficulty),
+            MenuButton::Quit => "Quit".into(),
+            MenuButton::Resume => "Resume".into(),
+            MenuButton::Restart => "Restart".into(),
+            MenuButton::QuitToMenu => "Quit to Menu".into(),
+        }
+    }
+}
+
+fn menu_root(background: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(12.),
+            ..default()
+        },
+        background_color: background.into(),
+        z_index: ZIndex::Global(20),
+        ..default()
+    }
+}
+
+fn spawn_menu_button(builder: &mut ChildBuilder, button: MenuButton, settings: &MatchSettings) {
+    builder
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(280.),
+                    height: Val::Px(48.),
+                    border: UiRect::all(Val::Px(2.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: BUTTON_BORDER.into(),
+                border_radius: BorderRadius::all(Val::Px(4.)),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                TextBundle::from_section(
+                    button.label(settings),
+                    TextStyle {
+                        font_size: 24.0,
+                        ..default()
+                    },
+                ),
+                MenuLabel,
+            ));
+        });
+}
+
+fn spawn_main_menu(settings: Res<MatchSettings>, mut commands: Commands) {
+    commands
+        .spawn((menu_root(MENU_BACKGROUND), MainMenu, Name::new("Main Menu")))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "Animancer",
+                TextStyle {
+                    font_size: 72.0,
+                    ..default()
+                },
+            ));
+
+            for button in [
+                MenuButton::NewGame,
+                MenuButton::CycleMap,
+                MenuButton::CyclePlayers,
+                MenuButton::CycleTeams,
+                MenuButton::CycleDifficulty,
+                MenuButton::Quit,
+            ] {
+                spawn_menu_button(builder, button, &settings);
+            }
+        });
+}
+
+fn spawn_pause_menu(settings: Res<MatchSettings>, mut commands: Commands) {
+    commands
+        .spawn((
+            menu_root(PAUSE_BACKGROUND),
+            PauseMenu,
+            Name::new("Pause Menu"),
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font_size: 64.0,
+                    ..default()
+                },
+            ));
+
+            for button in [
+                MenuButton::Resume,
+                MenuButton::Restart,
+                MenuButton::QuitToMenu,
+            ] {
+                spawn_menu_button(builder, button, &settings);
+            }
+        });
+}
+
+fn despawn_menu<T: Component>(menus: Query<Entity, With<T>>, mut commands: Commands) {
+    for entity in menus.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn menu_button_interactions(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &MenuButton),
+        Changed<Interaction>,
+    >,
+    mut settings: ResMut<MatchSettings>,
+    mut map_choices: ResMut<MapChoices>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut start_match: EventWriter<StartMatch>,
+    mut end_match: EventWriter<EndMatch>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => match button {
+                MenuButton::NewGame | MenuButton::Restart => {
+                    start_match.send(StartMatch);
+                }
+                MenuButton::CycleMap => {
+                    settings.map = map_choices.next();
+
+                    //	a smaller map leaves out the players it has no start for
+                    let max_players = map_choices.max_players(&settings.map);
+                    if settings.players > max_players {
+                        settings.players = max_players;
+                    }
+                }
+                MenuButton::CyclePlayers => {
+                    let max_players = map_choices.max_players(&settings.map);
+                    settings.players = if settings.players >= max_players {
+                        2
+                    } else {
+                        settings.players + 1
+                    };
+                }
+                MenuButton::CycleTeams => {
+                    settings.teams = match settings.teams {
+                        TeamSetup::FreeForAll => TeamSetup::TwoTeams,
+                        TeamSetup::TwoTeams => TeamSetup::FreeForAll,
+                    }
+                }
+                MenuButton::CycleDifficulty => {
+                    settings.difficulty = match settings.difficulty {
+                        AIDifficulty::Easy => AIDifficulty::Normal,
+                        AIDifficulty::Normal => AIDifficulty::Hard,
+                        AIDifficulty::Hard => AIDifficulty::Easy,
+                    }
+                }
+                MenuButton::Quit => {
+                    app_exit.send(AppExit::Success);
+                }
+                MenuButton::Resume => next_state.set(GameState::InGame),
+                MenuButton::QuitToMenu => {
+                    end_match.send(EndMatch);
+                }
+            },
+            Interaction::Hovered => *color = HOVERED_BUTTON.into(),
+            Interaction::None => *color = NORMAL_BUTTON.into(),
+        }
+    }
+}
+
+fn update_menu_labels(
+    settings: Res<MatchSettings>,
+    buttons: Query<(&MenuButton, &Children)>,
+    mut labels: Query<&mut Text, With<MenuLabel>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (button, children) in buttons.iter() {
+        for child in children.iter() {
+            let Ok(mut text) = labels.get_mut(*child) else {
+                continue;
+            };
+
+            text.sections[0].value = button.label(&settings);
+        }
+    }
+}
+
+//	the result screen stays up until the player is done looking at it
+fn game_over_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut end_match: EventWriter<EndMatch>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        end_match.send(EndMatch);
+    }
+}
@@ -0,0 +1,387 @@
+use bevy::{
+    color::palettes::{
+        css::WHITE,
+        tailwind::{GRAY_900, RED_300, YELLOW_300},
+    },
+    ecs::system::SystemParam,
+    prelude::*,
+    ui::RelativeCursorPosition,
+    utils::HashSet,
+};
+
+use crate::{
+    combat::AttackPursuit,
+    inputs::BoxSelector,
+    map::{Map, ResourceNode, OBSTACLE_WIDTH},
+    movement::SetUnitPosition,
+    schedule::InGameSet,
+    selectable::SelectionOrder,
+    structure::Structure,
+    teams::{Players, Team},
+    unit::Unit,
+};
+
+pub const MINIMAP_SIZE: f32 = 200.;
+
+const UNIT_DOT_SIZE: f32 = 3.;
+const STRUCTURE_DOT_SIZE: f32 = 6.;
+const TERRAIN_DOT_SIZE: f32 = 2.;
+const TERRAIN_DOT_SPACING: f32 = 40.;
+const FLASH_RATE: f32 = 8.0;
+
+const MINIMAP_BACKGROUND: Color = Color::Srgba(GRAY_900);
+const OBSTACLE_COLOR: Color = Color::Srgba(YELLOW_300);
+const RESOURCE_COLOR: Color = Color::Srgba(RED_300);
+const ATTACKED_COLOR: Color = Color::Srgba(WHITE);
+const VIEW_COLOR: Color = Color::Srgba(WHITE);
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_minimap_terrain.run_if(resource_changed::<Map>))
+            .add_systems(Update, minimap_clicks.in_set(InGameSet::UIInput))
+            .add_systems(
+                Update,
+                (
+                    add_minimap_markers,
+                    update_minimap_markers,
+                    update_minimap_view,
+                )
+                    .chain()
+                    .in_set(InGameSet::EntityUpdates),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct MinimapPanel;
+
+#[derive(Component)]
+struct MinimapTerrain;
+
+#[derive(Component)]
+struct MinimapMarker {
+    target: Entity,
+}
+
+#[derive(Component)]
+struct MinimapView;
+
+pub fn spawn_minimap_panel(builder: &mut ChildBuilder) {
+    builder
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(12.),
+                    bottom: Val::Px(12.),
+                    width: Val::Px(MINIMAP_SIZE),
+                    height: Val::Px(MINIMAP_SIZE),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                background_color: MINIMAP_BACKGROUND.into(),
+                ..default()
+            },
+            Interaction::default(),
+            RelativeCursorPosition::default(),
+            MinimapPanel,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        border: UiRect::all(Val::Px(1.)),
+                        ..default()
+                    },
+                    border_color: VIEW_COLOR.into(),
+                    z_index: ZIndex::Local(1),
+                    ..default()
+                },
+                MinimapView,
+            ));
+        });
+}
+
+/// whether the cursor is over the minimap rather than the world behind it
+pub fn cursor_over_minimap(minimap: &Query<&RelativeCursorPosition, With<MinimapPanel>>) -> bool {
+    minimap.iter().any(|cursor| cursor.mouse_over())
+}
+
+fn to_minimap(position: Vec2, map_size: Vec2) -> Vec2 {
+    position / map_size * MINIMAP_SIZE
+}
+
+fn dot(position: Vec2, size: f32, color: Color) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(position.x - size / 2.),
+            bottom: Val::Px(position.y - size / 2.),
+            width: Val::Px(size),
+            height: Val::Px(size),
+            ..default()
+        },
+        background_color: color.into(),
+        ..default()
+    }
+}
+
+fn draw_minimap_terrain(
+    map: Res<Map>,
+    panel: Query<Entity, With<MinimapPanel>>,
+    terrain: Query<Entity, With<MinimapTerrain>>,
+    mut commands: Commands,
+) {
+    for entity in terrain.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let Ok(panel) = panel.get_single() else {
+        return;
+    };
+    if map.size.x <= 0.0 || map.size.y <= 0.0 {
+        return;
+    }
+
+    //	obstacles are drawn as a trail of dots along each of their segments
+    let width = (OBSTACLE_WIDTH * 2.0 / map.size.x * MINIMAP_SIZE).max(TERRAIN_DOT_SIZE);
+    commands.entity(panel).with_children(|builder| {
+        for obstacle in map.obstacles.iter() {
+            for pair in obstacle.points().windows(2) {
+                let steps = (pair[0].distance(pair[1]) / TERRAIN_DOT_SPACING)
+                    .ceil()
+                    .max(1.0);
+
+                for step in 0..=(steps as usize) {
+                    let point = pair[0].lerp(pair[1], step as f32 / steps);
+                    builder.spawn((
+                        dot(to_minimap(point, map.size), width, OBSTACLE_COLOR),
+                        MinimapTerrain,
+                    ));
+                }
+            }
+        }
+    });
+}
+
+/// Anything that shows up on the minimap
+type Mapped = Or<(With<Unit>, With<Structure>, With<ResourceNode>)>;
+
+/// Anything that just appeared and shows up on the minimap
+type NewlyMapped = Or<(Added<Unit>, Added<Structure>, Added<ResourceNode>)>;
+
+//	a panel spawned after the match began, or into a loaded one, starts out with everything already there
+fn add_minimap_markers(
+    mapped: Query<Entity, Mapped>,
+    added: Query<Entity, NewlyMapped>,
+    panel: Query<(Entity, Ref<MinimapPanel>)>,
+    mut commands: Commands,
+) {
+    let Ok((panel, created)) = panel.get_single() else {
+        return;
+    };
+    let targets: Vec<Entity> = if created.is_added() {
+        mapped.iter().collect()
+    } else {
+        added.iter().collect()
+    };
+
+    commands.entity(panel).with_children(|builder| {
+        for target in targets {
+            builder.spawn((
+                dot(Vec2::ZERO, UNIT_DOT_SIZE, Color::NONE),
+                MinimapMarker { target },
+            ));
+        }
+    });
+}
+
+type MarkerTargetData = (
+    &'static Transform,
+    &'static Visibility,
+    Option<&'static Team>,
+    Option<&'static ResourceNode>,
+    Has<Structure>,
+);
+
+fn update_minimap_markers(
+    mut markers: Query<(Entity, &MinimapMarker, &mut Style, &mut BackgroundColor)>,
+    targets: Query<MarkerTargetData>,
+    attackers: Query<&AttackPursuit>,
+    map: Res<Map>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    if map.size.x <= 0.0 || map.size.y <= 0.0 {
+        return;
+    }
+
+    //	flash the same structures that are highlighted in the world
+    let attacked: HashSet<Entity> = attackers.iter().map(|attacker| attacker.prey).collect();
+    let flash_on = (time.elapsed_seconds() * FLASH_RATE).sin() > 0.0;
+
+    for (entity, marker, mut style, mut background) in markers.iter_mut() {
+        let Ok((transform, visibility, team, node, is_structure)) = targets.get(marker.target)
+        else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        //	anything hidden by the fog of war stays off the minimap too
+        let (size, color) = match (node, team) {
+            _ if *visibility == Visibility::Hidden => (UNIT_DOT_SIZE, Color::NONE),
+            (Some(node), _) => (
+                to_minimap(Vec2::splat(node.radius() * 2.0), map.size).x,
+                RESOURCE_COLOR,
+            ),
+            (None, Some(team)) if is_structure => {
+                if flash_on && attacked.contains(&marker.target) {
+                    (STRUCTURE_DOT_SIZE, ATTACKED_COLOR)
+                } else {
+                    (STRUCTURE_DOT_SIZE, team.0.color())
+                }
+            }
+            (None, Some(team)) => (UNIT_DOT_SIZE, team.0.color()),
+            (None, None) => (UNIT_DOT_SIZE, Color::NONE),
+        };
+
+        let position = to_minimap(transform.translation.xy(), map.size);
+        style.left = Val::Px(position.x - size / 2.);
+        style.bottom = Val::Px(position.y - size / 2.);
+        style.width = Val::Px(size);
+        style.height = Val::Px(size);
+        background.0 = color;
+    }
+}
+
+fn update_minimap_view(
+    camera: Query<(&Transform, &OrthographicProjection), With<Camera2d>>,
+    mut view: Query<&mut Style, With<MinimapView>>,
+    map: Res<Map>,
+) {
+    let Ok((transform, projection)) = camera.get_single() else {
+        return;
+    };
+    if map.size.x <= 0.0 || map.size.y <= 0.0 {
+        return;
+    }
+
+    let min = to_minimap(transform.translation.xy() + projection.area.min, map.size);
+    let size = to_minimap(projection.area.size(), map.size);
+
+    for mut style in view.iter_mut() {
+        style.left = Val::Px(min.x);
+        style.bottom = Val::Px(min.y);
+        style.width = Val::Px(size.x);
+        style.height = Val::Px(size.y);
+    }
+}
+
+/// What a right click on the minimap tells the local selection to do
+#[derive(SystemParam)]
+struct MinimapOrders<'w> {
+    movement_writer: EventWriter<'w, SetUnitPosition>,
+    selection_order: EventWriter<'w, SelectionOrder>,
+    box_selector: Res<'w, BoxSelector>,
+    players: Res<'w, Players>,
+}
+
+fn minimap_clicks(
+    minimap: Query<&RelativeCursorPosition, With<MinimapPanel>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut camera: Query<&mut Transform, With<Camera2d>>,
+    mut orders: MinimapOrders,
+    map: Res<Map>,
+) {
+    let Some(normalized) = minimap
+        .iter()
+        .filter(|cursor| cursor.mouse_over())
+        .find_map(|cursor| cursor.normalized)
+    else {
+        return;
+    };
+
+    //	the ui measures down from the top, the world measures up from the bottom
+    let position = Vec2::new(normalized.x, 1.0 - normalized.y) * map.size;
+
+    if mouse_button_input.pressed(MouseButton::Left) {
+        for mut transform in camera.iter_mut() {
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+        }
+    }
+
+    if mouse_button_input.just_pressed(MouseButton::Right) {
+        let queued = keys.pressed(KeyCode::ShiftLeft);
+
+        //	nothing on the minimap is picked out, so an outright move only drops what the selection was doing
+        orders.selection_order.send(SelectionOrder {
+            team: orders.players.local,
+            queued,
+            site: None,
+            generator: None,
+            prey: None,
+            convertee: None,
+        });
+
+        orders.movement_writer.send(SetUnitPosition {
+            position,
+            direction: Vec2::ZERO,
+            formation: orders.box_selector.formation.clone(),
+            team: orders.players.local,
+            queued,
+            attack_move: keys.pressed(KeyCode::ControlLeft),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        movement::Formation,
+        producer::ProductionType,
+        teams::TeamType,
+        testing::{Scenario, TestApp},
+    };
+
+    #[test]
+    fn a_late_panel_marks_what_is_already_there() {
+        let mut test = TestApp::new(MinimapPlugin);
+        test.app
+            .add_event::<SelectionOrder>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .insert_resource(BoxSelector {
+                selecting: false,
+                start: Vec2::ZERO,
+                current: Vec2::ZERO,
+                formation: Formation::Line,
+            })
+            .insert_resource(Map::default());
+        test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Worker, TeamType(0), Vec2::ZERO),
+                (ProductionType::Warrior, TeamType(1), Vec2::new(100.0, 0.0)),
+            ],
+            ..default()
+        });
+        test.step(2);
+
+        //	the units were spawned, and their addition seen, before there was a panel to mark them on
+        let world = test.app.world_mut();
+        world
+            .commands()
+            .spawn(NodeBundle::default())
+            .with_children(spawn_minimap_panel);
+        world.flush();
+        test.step(2);
+
+        let world = test.app.world_mut();
+        assert_eq!(world.query::<&MinimapMarker>().iter(world).count(), 2);
+    }
+}
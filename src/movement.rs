@@ -4,24 +4,35 @@ use bevy::{
     math::{vec2, vec3},
     prelude::*,
 };
+use serde::{Deserialize, Serialize};
 
-use crate::{nav_agent::AssignNavigatorPath, selectable::SelectedUnits, teams::TeamType};
+use crate::{
+    nav_agent::{AssignNavigatorPath, PathFailed},
+    orders::{Order, Orders},
+    schedule::InGameSet,
+    selectable::SelectedUnits,
+    teams::TeamType,
+};
 
 const UNIT_BUFFER: f32 = 40.0;
 const LINE_STRENGTH_SCALE: f32 = 2.4;
 const RINGED_STRENGTH_SCALE: f32 = 0.9;
 const BOX_STRENGTH_SCALE: f32 = 0.9;
+//	pursuers are sent to a ring around their prey, which may have moved on a little when the path fails
+const PURSUIT_REACH: f32 = RINGED_STRENGTH_SCALE * (UNIT_BUFFER + 1.0) + UNIT_BUFFER;
 
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
+        //	move orders are read in the frame they are given, which lockstep relies on to hold them back
         app.add_systems(
             Update,
             (
                 set_moveable_location,
                 // move_unit
-            ),
+            )
+                .in_set(InGameSet::EntityUpdates),
         )
         .add_event::<SetUnitPosition>();
     }
@@ -33,6 +44,8 @@ pub struct Moving(pub bool);
 #[derive(Component, Default)]
 pub struct Moveable {
     pub location: Vec3,
+    /// whether the unit stops to fight anything it detects on the way to `location`
+    pub attack_move: bool,
 }
 
 #[derive(Event, Default)]
@@ -41,8 +54,13 @@ pub struct SetUnitPosition {
     pub direction: Vec2,
     pub formation: Formation,
     pub team: TeamType,
+    /// whether each unit goes once it has finished its other orders, rather than straight away
+    pub queued: bool,
+    /// whether the units stop to fight anything they detect on the way, rather than walking past it
+    pub attack_move: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
 pub enum Formation {
     Ringed,
     Line,
@@ -55,6 +73,33 @@ impl Default for Formation {
     }
 }
 
+/// A unit going after another, given up once it cannot get anywhere near it
+pub trait Pursuit: Component {
+    fn prey(&self) -> Entity;
+}
+
+//	prey nobody can get anywhere near is left alone rather than chased in place forever,
+//	though only when it was the chase that failed and not some other move the pursuer was given
+pub fn abandon_unreachable_prey<P: Pursuit>(
+    mut failed: EventReader<PathFailed>,
+    pursuers: Query<&P>,
+    prey: Query<&Transform>,
+    mut commands: Commands,
+) {
+    for failure in failed.read() {
+        let Ok(pursuit) = pursuers.get(failure.entity) else {
+            continue;
+        };
+        let chased = prey.get(pursuit.prey()).is_ok_and(|transform| {
+            transform.translation.xy().distance(failure.location) <= PURSUIT_REACH
+        });
+
+        if chased {
+            commands.entity(failure.entity).remove::<P>();
+        }
+    }
+}
+
 impl Clone for Formation {
     fn clone(&self) -> Self {
         match self {
@@ -65,9 +110,9 @@ impl Clone for Formation {
     }
 }
 
-fn set_moveable_location(
+pub fn set_moveable_location(
     mut reader: EventReader<SetUnitPosition>,
-    mut query: Query<&mut Moveable>,
+    mut query: Query<(&mut Moveable, &mut Orders)>,
     selected: Res<SelectedUnits>,
     mut nav_path_assigner: EventWriter<AssignNavigatorPath>,
 ) {
@@ -86,17 +131,21 @@ fn set_moveable_location(
                 let theta = (circumference / unit_count) / radius;
 
                 for &entity in selected.entities.iter(&unit_movement.team) {
-                    if let Ok(mut moveable) = query.get_mut(entity) {
-                        moveable.location = vec3(
+                    if let Ok((mut moveable, mut orders)) = query.get_mut(entity) {
+                        let location = vec3(
                             unit_movement.position.x + radius * f32::cos(order * theta + aim_angle),
                             unit_movement.position.y + radius * f32::sin(order * theta + aim_angle),
                             0.0,
                         );
 
-                        nav_path_assigner.send(AssignNavigatorPath {
+                        move_or_queue(
                             entity,
-                            location: moveable.location,
-                        });
+                            location,
+                            unit_movement,
+                            &mut moveable,
+                            &mut orders,
+                            &mut nav_path_assigner,
+                        );
                     }
                     order += 1.0;
                 }
@@ -108,7 +157,7 @@ fn set_moveable_location(
                 let units_per_line = f32::ceil(unit_count / line_count);
 
                 for &entity in selected.entities.iter(&unit_movement.team) {
-                    if let Ok(mut moveable) = query.get_mut(entity) {
+                    if let Ok((mut moveable, mut orders)) = query.get_mut(entity) {
                         let current_line_index = f32::floor(order / units_per_line);
                         let stagger =
                             if line_count == 1.0 || (current_line_index + 1.0) != line_count {
@@ -122,7 +171,7 @@ fn set_moveable_location(
                             -f32::floor(order / units_per_line),
                         ) * UNIT_BUFFER;
 
-                        moveable.location = vec3(
+                        let location = vec3(
                             unit_movement.position.x
                                 + position.length()
                                     * f32::cos(aim_angle - PI / 2. + position.to_angle()),
@@ -132,10 +181,14 @@ fn set_moveable_location(
                             0.0,
                         );
 
-                        nav_path_assigner.send(AssignNavigatorPath {
+                        move_or_queue(
                             entity,
-                            location: moveable.location,
-                        });
+                            location,
+                            unit_movement,
+                            &mut moveable,
+                            &mut orders,
+                            &mut nav_path_assigner,
+                        );
                     }
                     order += 1.0;
                 }
@@ -150,7 +203,7 @@ fn set_moveable_location(
 
                 //  move along the square, placing units evenly apart
                 for &entity in selected.entities.iter(&unit_movement.team) {
-                    if let Ok(mut moveable) = query.get_mut(entity) {
+                    if let Ok((mut moveable, mut orders)) = query.get_mut(entity) {
                         distance_traveled += unit_spacing;
                         if distance_traveled / (line_count * side_length) > 1.0 {
                             line_count += 1.0;
@@ -166,7 +219,7 @@ fn set_moveable_location(
                             _ => vec2(0., 0.),
                         };
 
-                        moveable.location = vec3(
+                        let location = vec3(
                             unit_movement.position.x
                                 + position.length()
                                     * f32::cos(aim_angle - PI / 2. + position.to_angle()),
@@ -176,13 +229,46 @@ fn set_moveable_location(
                             0.0,
                         );
 
-                        nav_path_assigner.send(AssignNavigatorPath {
+                        move_or_queue(
                             entity,
-                            location: moveable.location,
-                        });
+                            location,
+                            unit_movement,
+                            &mut moveable,
+                            &mut orders,
+                            &mut nav_path_assigner,
+                        );
                     }
                 }
             }
         }
     }
 }
+
+//	a queued move waits its turn in the unit's orders, anything else sets off straight away
+fn move_or_queue(
+    entity: Entity,
+    location: Vec3,
+    unit_movement: &SetUnitPosition,
+    moveable: &mut Moveable,
+    orders: &mut Orders,
+    nav_path_assigner: &mut EventWriter<AssignNavigatorPath>,
+) {
+    let corridor = Some(unit_movement.position);
+    let attack_move = unit_movement.attack_move;
+
+    if unit_movement.queued {
+        orders.queued.push_back(Order::Move {
+            location,
+            corridor,
+            attack_move,
+        });
+    } else {
+        moveable.location = location;
+        moveable.attack_move = attack_move;
+        nav_path_assigner.send(AssignNavigatorPath {
+            entity,
+            location,
+            corridor,
+        });
+    }
+}
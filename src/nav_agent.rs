@@ -1,26 +1,44 @@
-use bevy::{math::vec2, prelude::*};
+use std::f32::consts::TAU;
+
+use bevy::{ecs::entity::EntityHashMap, math::vec2, prelude::*, utils::HashMap};
 use vleue_navigator::prelude::*;
 
-use crate::{movement::Moving, schedule::InGameSet};
+use crate::{
+    map::Map, match_setup::MatchEntity, movement::Moving, schedule::InGameSet,
+    spatial::SpatialIndex,
+};
 
-const MESH_WIDTH: u32 = 5000;
-const MESH_HEIGHT: u32 = 5000;
+//	how far around itself a navigator looks for others to steer clear of
+const NEIGHBOUR_RANGE: f32 = 120.0;
+const MAX_NEIGHBOURS: usize = 10;
+//	seconds ahead a navigator makes sure it will not run into anyone
+const TIME_HORIZON: f32 = 1.0;
+//	how far from whoever searched for a group's path the rest can be and still follow it
+const CORRIDOR_REACH: f32 = 200.0;
+//	how far apart the points checked along a follower's way to the shared path are,
+//	which is narrower than any obstacle
+const SIGHT_STEP: f32 = 8.0;
+//	rings around a destination that cannot be reached, tried from the inside out for somewhere close
+const FALLBACK_STEP: f32 = 16.0;
+const FALLBACK_RINGS: usize = 40;
+const FALLBACK_DIRECTIONS: usize = 16;
+const FALLBACK_SEARCHES: usize = 8;
 
 pub struct NavAgentPlugin;
 
 impl Plugin for NavAgentPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_navmesh)
-            .add_systems(
-                Update,
-                (
-                    give_target_to_navigator::<MESH_WIDTH, MESH_HEIGHT>,
-                    move_navigator,
-                    refresh_path::<MESH_WIDTH, MESH_HEIGHT>,
-                )
-                    .in_set(InGameSet::EntityUpdates),
+        app.add_systems(
+            Update,
+            (
+                give_target_to_navigator,
+                (avoid_navigators, move_navigator).chain(),
+                refresh_path,
             )
-            .add_event::<AssignNavigatorPath>();
+                .in_set(InGameSet::EntityUpdates),
+        )
+        .add_event::<AssignNavigatorPath>()
+        .add_event::<PathFailed>();
     }
 }
 
@@ -30,6 +48,20 @@ pub struct Obstacle;
 #[derive(Component)]
 pub struct Navigator {
     pub speed: f32,
+    /// how much room it takes up, which others keep out of
+    pub radius: f32,
+    /// where it is heading this frame, once others have been steered around
+    pub velocity: Vec2,
+}
+
+impl Navigator {
+    pub fn new(speed: f32, size: Vec2) -> Self {
+        Self {
+            speed,
+            radius: size.max_element() / 2.0,
+            velocity: Vec2::ZERO,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -37,107 +69,259 @@ pub struct Path {
     pub current: Vec2,
     pub next: Vec<Vec2>,
     pub target: Entity,
+    /// where the group this navigator was ordered with is headed, which its searches are shared for
+    pub corridor: Option<Vec2>,
 }
 
 #[derive(Event)]
 pub struct AssignNavigatorPath {
     pub entity: Entity,
     pub location: Vec3,
+    /// the destination of the whole group, when the navigator is one of many given the same order
+    pub corridor: Option<Vec2>,
+}
+
+/// Sent when a navigator cannot get anywhere near where it was sent, leaving it to whatever reacts
+#[derive(Event)]
+pub struct PathFailed {
+    pub entity: Entity,
+    pub location: Vec2,
+}
+
+/// The paths found for each group destination during one frame, so a group costs one search
+#[derive(Default)]
+struct Corridors {
+    found: HashMap<(u32, u32), Corridor>,
+    /// how many path searches the group paths have taken
+    searches: usize,
+}
+
+struct Corridor {
+    /// where the search was made from
+    start: Vec2,
+    waypoints: Option<Vec<Vec2>>,
+}
+
+impl Corridors {
+    /// the path from `position` to its `slot` in a group headed for `destination`, following the
+    /// path of whoever in the group searched first, or `None` if the navigator has to search alone
+    fn share(
+        &mut self,
+        navmesh: &NavMesh,
+        position: Vec2,
+        destination: Vec2,
+        slot: Vec2,
+    ) -> Option<Vec<Vec2>> {
+        let searches = &mut self.searches;
+        let corridor = self
+            .found
+            .entry((destination.x.to_bits(), destination.y.to_bits()))
+            .or_insert_with(|| {
+                *searches += 1;
+                Corridor {
+                    start: position,
+                    waypoints: search(navmesh, position.extend(0.0), destination.extend(0.0)),
+                }
+            });
+
+        if corridor.start.distance(position) > CORRIDOR_REACH {
+            return None;
+        }
+
+        //	the group spreads out to its own slots once it gets there
+        let (_, waypoints) = corridor.waypoints.as_ref()?.split_last()?;
+        let path: Vec<Vec2> = waypoints.iter().copied().chain([slot]).collect();
+
+        //	a wall may stand between a follower and the leader, in which case it searches alone
+        if corridor.start != position && !in_sight(navmesh, position, path[0]) {
+            return None;
+        }
+        Some(path)
+    }
+}
+
+//	whether a navigator can walk straight from one point to the other, checked by looking up
+//	points along the way in the mesh rather than searching
+fn in_sight(navmesh: &NavMesh, from: Vec2, to: Vec2) -> bool {
+    let steps = (from.distance(to) / SIGHT_STEP).ceil() as usize;
+
+    (0..=steps).all(|step| {
+        let point = from.lerp(to, step as f32 / steps.max(1) as f32);
+        navmesh.transformed_is_in_mesh(point.extend(0.0))
+    })
+}
+
+//	a path the mesh understands, in the order it is walked
+fn search(navmesh: &NavMesh, from: Vec3, to: Vec3) -> Option<Vec<Vec2>> {
+    navmesh
+        .transformed_path(from, to)
+        .map(|path| path.path.iter().map(|point| point.xy()).collect())
 }
 
-pub fn spawn_navmesh(mut commands: Commands) {
-    commands.spawn(NavMeshBundle {
-        settings: NavMeshSettings {
-            // Define the outer borders of the navmesh.
-            fixed: Triangulation::from_outer_edges(&[
-                vec2(0.0, 0.0),
-                vec2(MESH_WIDTH as f32, 0.0),
-                vec2(MESH_WIDTH as f32, MESH_HEIGHT as f32),
-                vec2(0.0, MESH_HEIGHT as f32),
-            ]),
-            ..default()
+/// a path to `to`, or else to the closest point around it that can be reached from `from`
+fn search_nearest(navmesh: &NavMesh, from: Vec3, to: Vec2) -> Option<Vec<Vec2>> {
+    if navmesh.transformed_is_in_mesh(to.extend(0.0)) {
+        if let Some(path) = search(navmesh, from, to.extend(0.0)) {
+            return Some(path);
+        }
+    }
+
+    let mut searches = 0;
+    for ring in 1..=FALLBACK_RINGS {
+        let radius = ring as f32 * FALLBACK_STEP;
+        let mut candidates: Vec<Vec2> = (0..FALLBACK_DIRECTIONS)
+            .map(|index| {
+                to + Vec2::from_angle(index as f32 * TAU / FALLBACK_DIRECTIONS as f32) * radius
+            })
+            .filter(|candidate| navmesh.transformed_is_in_mesh(candidate.extend(0.0)))
+            .collect();
+
+        //	of the points as close as each other, the ones on the way there are tried first
+        candidates.sort_by(|a, b| {
+            a.distance_squared(from.xy())
+                .total_cmp(&b.distance_squared(from.xy()))
+        });
+
+        for candidate in candidates {
+            //	somewhere walled off takes a search per candidate to find out, so give up early
+            if searches == FALLBACK_SEARCHES {
+                return None;
+            }
+            searches += 1;
+
+            if let Some(path) = search(navmesh, from, candidate.extend(0.0)) {
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
+
+pub fn spawn_navmesh(mut commands: Commands, map: Res<Map>) {
+    commands.spawn((
+        NavMeshBundle {
+            settings: NavMeshSettings {
+                // Define the outer borders of the navmesh.
+                fixed: Triangulation::from_outer_edges(&[
+                    vec2(0.0, 0.0),
+                    vec2(map.size.x, 0.0),
+                    vec2(map.size.x, map.size.y),
+                    vec2(0.0, map.size.y),
+                ]),
+                ..default()
+            },
+            // Mark it for update as soon as obstacles are changed.
+            // Other modes can be debounced or manually triggered.
+            update_mode: NavMeshUpdateMode::Direct,
+            transform: Transform::from_translation(Vec3::ZERO),
+            ..NavMeshBundle::with_default_id()
         },
-        // Mark it for update as soon as obstacles are changed.
-        // Other modes can be debounced or manually triggered.
-        update_mode: NavMeshUpdateMode::Direct,
-        transform: Transform::from_translation(Vec3::ZERO),
-        //     Vec3::new(
-        //     -(MESH_WIDTH as f32) / 2.0,
-        //     -(MESH_HEIGHT as f32) / 2.0,
-        //     0.0,
-        // )),
-        ..NavMeshBundle::with_default_id()
-    });
-}
-
-fn give_target_to_navigator<const X: u32, const Y: u32>(
+        MatchEntity,
+    ));
+}
+
+pub fn give_target_to_navigator(
     mut nav_path_assignment: EventReader<AssignNavigatorPath>,
     navigator: Query<&Transform, With<Navigator>>,
     navmeshes: Res<Assets<NavMesh>>,
     navmesh: Query<&Handle<NavMesh>>,
+    mut path_failed: EventWriter<PathFailed>,
     mut commands: Commands,
 ) {
+    let Ok(navmesh_handle) = navmesh.get_single() else {
+        return;
+    };
+    let mut corridors = Corridors::default();
+
     for assignment in nav_path_assignment.read() {
         for transform in &navigator.get(assignment.entity) {
-            let Some(navmesh) = navmeshes.get(navmesh.single()) else {
+            let Some(navmesh) = navmeshes.get(navmesh_handle) else {
                 continue;
             };
 
-            //	Check if movement position is in mesh
+            //	Check if movement position is in mesh, since only then can it follow the group there
             let position = assignment.location;
-            if !navmesh.is_in_mesh(position.xy()) {
-                continue;
-            }
+            let in_mesh = navmesh.is_in_mesh(position.xy());
 
-            //	Create a path that the mesh understands
-            let Some(path) = navmesh.transformed_path(
-                transform.translation.xyz(),
-                navmesh.transform().transform_point(position),
-            ) else {
-                //	we need some way of handling the no-path scenario
+            //	Create a path that the mesh understands, once for the whole group where possible
+            let shared = assignment
+                .corridor
+                .filter(|_| in_mesh)
+                .and_then(|destination| {
+                    corridors.share(
+                        navmesh,
+                        transform.translation.xy(),
+                        destination,
+                        position.xy(),
+                    )
+                });
+            let Some(path) = shared.or_else(|| {
+                search_nearest(
+                    navmesh,
+                    transform.translation.xyz(),
+                    navmesh.transform().transform_point(position).xy(),
+                )
+            }) else {
+                path_failed.send(PathFailed {
+                    entity: assignment.entity,
+                    location: position.xy(),
+                });
                 break;
             };
 
             //	Setting the path
-            if let Some((first, remaining)) = path.path.split_first() {
-                let mut remaining = remaining.iter().map(|p| p.xy()).collect::<Vec<_>>();
+            if let Some((first, remaining)) = path.split_first() {
+                let mut remaining = remaining.to_vec();
 
                 remaining.reverse();
                 let id = commands
-                    .spawn(TransformBundle {
-                        local: Transform::from_translation(
-                            remaining.first().unwrap_or(&first.xy()).extend(1.5),
-                        ),
-                        ..default()
-                    })
+                    .spawn((
+                        TransformBundle {
+                            local: Transform::from_translation(
+                                remaining.first().unwrap_or(first).extend(1.5),
+                            ),
+                            ..default()
+                        },
+                        MatchEntity,
+                    ))
                     .id();
                 commands.entity(assignment.entity).insert(Path {
-                    current: first.xy(),
+                    current: *first,
                     next: remaining,
                     target: id,
+                    corridor: assignment.corridor,
                 });
             }
 
             // info!("set path to: {:?}", position);
         }
     }
+
+    if corridors.searches > 0 {
+        debug!("searched {} group paths", corridors.searches);
+    }
 }
 
-fn refresh_path<const X: u32, const Y: u32>(
+fn refresh_path(
     mut commands: Commands,
     mut navigator: Query<(Entity, &Transform, &mut Path), With<Navigator>>,
     mut navmeshes: ResMut<Assets<NavMesh>>,
     navmesh: Query<(&Handle<NavMesh>, Ref<NavMeshStatus>)>,
     transforms: Query<&Transform>,
+    mut path_failed: EventWriter<PathFailed>,
     mut delta: Local<f32>,
 ) {
-    let (navmesh_handle, status) = navmesh.single();
+    let Ok((navmesh_handle, status)) = navmesh.get_single() else {
+        return;
+    };
     if (!status.is_changed() || *status != NavMeshStatus::Built) && *delta == 0.0 {
         return;
     }
     let Some(navmesh) = navmeshes.get_mut(navmesh_handle) else {
         return;
     };
+    let mut corridors = Corridors::default();
 
     for (entity, transform, mut path) in &mut navigator {
         let target = transforms.get(path.target).unwrap().translation.xy();
@@ -148,41 +332,67 @@ fn refresh_path<const X: u32, const Y: u32>(
             navmesh.set_search_delta(*delta);
             continue;
         }
-        if !navmesh.transformed_is_in_mesh(target.extend(0.0)) {
-            // info!("new target not in mesh");
-            commands.entity(path.target).despawn_recursive();
-            commands.entity(entity).remove::<Path>();
-            continue;
-        }
+        //	something may have been built on the target, leaving the group to go around it alone
+        let in_mesh = navmesh.transformed_is_in_mesh(target.extend(0.0));
 
-        let Some(new_path) = navmesh.transformed_path(transform.translation, target.extend(0.0))
+        let shared = path.corridor.filter(|_| in_mesh).and_then(|destination| {
+            corridors.share(navmesh, transform.translation.xy(), destination, target)
+        });
+        let Some(new_path) =
+            shared.or_else(|| search_nearest(navmesh, transform.translation, target))
         else {
             // info!("failed to get new path");
+            path_failed.send(PathFailed {
+                entity,
+                location: target,
+            });
             commands.entity(path.target).despawn_recursive();
             commands.entity(entity).remove::<Path>();
             continue;
         };
-        if let Some((first, remaining)) = new_path.path.split_first() {
+        if let Some(&end) = new_path.last().filter(|end| **end != target) {
+            commands
+                .entity(path.target)
+                .insert(Transform::from_translation(end.extend(1.5)));
+        }
+        if let Some((first, remaining)) = new_path.split_first() {
             // info!("setting remaining path");
-            let mut remaining = remaining.iter().map(|p| p.xy()).collect::<Vec<_>>();
+            let mut remaining = remaining.to_vec();
             remaining.reverse();
-            path.current = first.xy();
+            path.current = *first;
             path.next = remaining;
             *delta = 0.0;
         }
     }
 }
 
-fn move_navigator(
+pub fn move_navigator(
     mut commands: Commands,
     mut navigator: Query<(&mut Transform, &mut Path, Entity, &Navigator, &mut Moving)>,
+    navmeshes: Res<Assets<NavMesh>>,
+    navmesh: Query<&Handle<NavMesh>>,
     time: Res<Time>,
 ) {
+    let navmesh = navmesh
+        .get_single()
+        .ok()
+        .and_then(|handle| navmeshes.get(handle));
+
     for (mut transform, mut path, entity, navigator, mut moving) in navigator.iter_mut() {
-        let move_direction = path.current - transform.translation.xy();
-        transform.translation +=
-            (move_direction.normalize() * time.delta_seconds() * navigator.speed).extend(0.0);
-        while transform.translation.xy().distance(path.current) < navigator.speed / 50.0 {
+        let avoiding =
+            transform.translation + (navigator.velocity * time.delta_seconds()).extend(0.0);
+
+        //	stepping around a neighbour is never worth leaving the mesh for
+        if navmesh.map_or(true, |navmesh| navmesh.transformed_is_in_mesh(avoiding)) {
+            transform.translation = avoiding;
+        } else {
+            let move_direction = path.current - transform.translation.xy();
+            transform.translation +=
+                (move_direction.normalize_or_zero() * time.delta_seconds() * navigator.speed)
+                    .extend(0.0);
+        }
+
+        while has_reached(transform.translation.xy(), &path, navigator) {
             if let Some(next) = path.next.pop() {
                 path.current = next;
             } else {
@@ -196,3 +406,319 @@ fn move_navigator(
         }
     }
 }
+
+//	a crowd around the end of a path can keep a navigator from ever getting there,
+//	so being held up close enough to it counts as arriving
+fn has_reached(position: Vec2, path: &Path, navigator: &Navigator) -> bool {
+    let distance = position.distance(path.current);
+
+    distance < navigator.speed / 50.0
+        || (path.next.is_empty()
+            && distance < navigator.radius * 3.0
+            && navigator.velocity.length() < navigator.speed * 0.1)
+}
+
+/// Where a navigator is and where it is heading, as the others see it this frame
+#[derive(Clone, Copy)]
+struct Agent {
+    position: Vec2,
+    velocity: Vec2,
+    radius: f32,
+    /// parked navigators stay put, so whoever is moving steers fully around them
+    parked: bool,
+}
+
+/// The velocities on the far side of `direction` from `point` would run into a neighbour
+struct Line {
+    point: Vec2,
+    direction: Vec2,
+}
+
+//	every moving navigator picks the velocity closest to the one it wants that does not run into
+//	anyone within the time horizon, trusting moving neighbours to take half the avoiding on themselves
+fn avoid_navigators(
+    mut navigators: Query<(Entity, &Transform, &mut Navigator, Option<&Path>)>,
+    spatial: Res<SpatialIndex>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+
+    //	everyone decides from where the others were before anyone moved
+    let agents: EntityHashMap<Agent> = navigators
+        .iter()
+        .map(|(entity, transform, navigator, path)| {
+            let agent = Agent {
+                position: transform.translation.xy(),
+                velocity: navigator.velocity,
+                radius: navigator.radius,
+                parked: path.is_none(),
+            };
+            (entity, agent)
+        })
+        .collect();
+
+    for (entity, transform, mut navigator, path) in navigators.iter_mut() {
+        let Some(path) = path else {
+            navigator.velocity = Vec2::ZERO;
+            continue;
+        };
+
+        let agent = agents[&entity];
+        let position = transform.translation.xy();
+        let to_waypoint = path.current - position;
+        let mut speed = navigator.speed;
+        if path.next.is_empty() {
+            speed = speed.min(to_waypoint.length() / delta);
+        }
+        let preferred = to_waypoint.normalize_or_zero() * speed;
+
+        let mut neighbours: Vec<Agent> = spatial
+            .within(position, NEIGHBOUR_RANGE)
+            .filter(|other| *other != entity)
+            .filter_map(|other| agents.get(&other).copied())
+            .collect();
+        neighbours.sort_by(|a, b| {
+            a.position
+                .distance_squared(position)
+                .total_cmp(&b.position.distance_squared(position))
+        });
+        neighbours.truncate(MAX_NEIGHBOURS);
+
+        let lines: Vec<Line> = neighbours
+            .iter()
+            .map(|neighbour| avoidance_line(&agent, neighbour, delta))
+            .collect();
+
+        navigator.velocity = solve_velocity(&lines, navigator.speed, preferred);
+    }
+}
+
+fn det(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+//	the half of velocity space that keeps `agent` clear of `other` for the time horizon,
+//	bounded as close to its current velocity as possible
+fn avoidance_line(agent: &Agent, other: &Agent, delta: f32) -> Line {
+    let relative_position = other.position - agent.position;
+    let relative_velocity = agent.velocity - other.velocity;
+    let distance_squared = relative_position.length_squared();
+    let radius = agent.radius + other.radius;
+    let radius_squared = radius * radius;
+
+    let (direction, u) = if distance_squared > radius_squared {
+        //	not touching yet, so only what would touch within the time horizon is ruled out
+        let w = relative_velocity - relative_position / TIME_HORIZON;
+        let w_length_squared = w.length_squared();
+        let dot = w.dot(relative_position);
+
+        if dot < 0.0 && dot * dot > radius_squared * w_length_squared {
+            let w_length = w_length_squared.sqrt();
+            let unit_w = w / w_length;
+            (
+                vec2(unit_w.y, -unit_w.x),
+                unit_w * (radius / TIME_HORIZON - w_length),
+            )
+        } else {
+            let leg = (distance_squared - radius_squared).sqrt();
+            let direction = if det(relative_position, w) > 0.0 {
+                vec2(
+                    relative_position.x * leg - relative_position.y * radius,
+                    relative_position.x * radius + relative_position.y * leg,
+                ) / distance_squared
+            } else {
+                -vec2(
+                    relative_position.x * leg + relative_position.y * radius,
+                    -relative_position.x * radius + relative_position.y * leg,
+                ) / distance_squared
+            };
+            (
+                direction,
+                direction * relative_velocity.dot(direction) - relative_velocity,
+            )
+        }
+    } else {
+        //	already overlapping, so get apart within the frame
+        let w = relative_velocity - relative_position / delta;
+        let w_length = w.length();
+        let unit_w = if w_length > 0.0 {
+            w / w_length
+        } else {
+            Vec2::X
+        };
+        (
+            vec2(unit_w.y, -unit_w.x),
+            unit_w * (radius / delta - w_length),
+        )
+    };
+
+    let responsibility = if other.parked { 1.0 } else { 0.5 };
+    Line {
+        point: agent.velocity + u * responsibility,
+        direction,
+    }
+}
+
+//	the velocity no faster than `speed` that is closest to `preferred` while on the allowed side
+//	of every line, or the best found before a line could not be kept to
+fn solve_velocity(lines: &[Line], speed: f32, preferred: Vec2) -> Vec2 {
+    let mut result = preferred.clamp_length_max(speed);
+
+    for (index, line) in lines.iter().enumerate() {
+        if det(line.direction, line.point - result) > 0.0 {
+            match closest_on_line(lines, index, speed, preferred) {
+                Some(velocity) => result = velocity,
+                None => break,
+            }
+        }
+    }
+
+    result
+}
+
+//	the point on `lines[index]` closest to `preferred` that the earlier lines and the speed allow
+fn closest_on_line(lines: &[Line], index: usize, speed: f32, preferred: Vec2) -> Option<Vec2> {
+    let line = &lines[index];
+    let dot = line.point.dot(line.direction);
+    let discriminant = dot * dot + speed * speed - line.point.length_squared();
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    let (mut left, mut right) = (-dot - root, -dot + root);
+
+    for earlier in lines[..index].iter() {
+        let denominator = det(line.direction, earlier.direction);
+        let numerator = det(earlier.direction, line.point - earlier.point);
+
+        if denominator.abs() <= f32::EPSILON {
+            //	parallel lines either leave this one alone or rule all of it out
+            if numerator < 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let t = numerator / denominator;
+        if denominator >= 0.0 {
+            right = right.min(t);
+        } else {
+            left = left.max(t);
+        }
+        if left > right {
+            return None;
+        }
+    }
+
+    let t = line
+        .direction
+        .dot(preferred - line.point)
+        .clamp(left, right);
+    Some(line.point + line.direction * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(position: Vec2, velocity: Vec2, parked: bool) -> Agent {
+        Agent {
+            position,
+            velocity,
+            radius: 16.0,
+            parked,
+        }
+    }
+
+    #[test]
+    fn navigators_heading_at_each_other_step_aside() {
+        let left = agent(Vec2::ZERO, vec2(100.0, 0.0), false);
+        let right = agent(vec2(50.0, 0.0), vec2(-100.0, 0.0), false);
+
+        let line = avoidance_line(&left, &right, 1.0 / 60.0);
+        let velocity = solve_velocity(&[line], 100.0, vec2(100.0, 0.0));
+
+        assert!(velocity.x < 100.0);
+        assert!(velocity.y.abs() > 1.0);
+    }
+
+    #[test]
+    fn navigators_go_around_parked_ones() {
+        let moving = agent(Vec2::ZERO, vec2(100.0, 0.0), false);
+        let parked = agent(vec2(40.0, 0.0), Vec2::ZERO, true);
+
+        let line = avoidance_line(&moving, &parked, 1.0 / 60.0);
+        let velocity = solve_velocity(&[line], 100.0, vec2(100.0, 0.0));
+
+        //	the parked navigator does nothing, so the whole way around is the moving one's
+        for step in 0..=10 {
+            let position = velocity * TIME_HORIZON * step as f32 / 10.0;
+            assert!(position.distance(parked.position) >= 31.9);
+        }
+    }
+
+    #[test]
+    fn followers_behind_a_wall_search_alone() {
+        let square = vec![
+            Vec2::ZERO,
+            vec2(400.0, 0.0),
+            vec2(400.0, 400.0),
+            vec2(0.0, 400.0),
+        ];
+        let wall = vec![
+            vec2(190.0, 10.0),
+            vec2(210.0, 10.0),
+            vec2(210.0, 300.0),
+            vec2(190.0, 300.0),
+        ];
+        let navmesh = NavMesh::from_edge_and_obstacles(square, vec![wall]);
+        let destination = vec2(300.0, 50.0);
+
+        let mut corridors = Corridors::default();
+        let leader = corridors.share(&navmesh, vec2(150.0, 100.0), destination, destination);
+        let beside = corridors.share(&navmesh, vec2(100.0, 100.0), destination, destination);
+        let behind = corridors.share(&navmesh, vec2(250.0, 100.0), destination, destination);
+
+        //	the leader goes around the wall and the follower beside it can see the way
+        assert!(leader.is_some_and(|path| path.len() > 1));
+        assert!(beside.is_some());
+        assert!(behind.is_none());
+    }
+
+    #[test]
+    fn a_group_shares_one_search() {
+        let square = vec![
+            Vec2::ZERO,
+            vec2(1000.0, 0.0),
+            vec2(1000.0, 1000.0),
+            vec2(0.0, 1000.0),
+        ];
+        let wall = vec![
+            vec2(490.0, 100.0),
+            vec2(510.0, 100.0),
+            vec2(510.0, 900.0),
+            vec2(490.0, 900.0),
+        ];
+        let navmesh = NavMesh::from_edge_and_obstacles(square, vec![wall]);
+        let destination = vec2(800.0, 500.0);
+
+        let mut corridors = Corridors::default();
+        for index in 0..100 {
+            let position = vec2(
+                200.0 + (index % 10) as f32 * 10.0,
+                450.0 + (index / 10) as f32 * 10.0,
+            );
+            let slot = destination + vec2((index % 10) as f32, (index / 10) as f32) * 10.0;
+
+            assert!(corridors
+                .share(&navmesh, position, destination, slot)
+                .is_some_and(|path| path.len() > 1 && path.last() == Some(&slot)));
+        }
+
+        assert_eq!(corridors.searches, 1);
+    }
+}
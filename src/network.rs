@@ -0,0 +1,797 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{ErrorKind, Read, Write},
+    mem,
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use bevy::{ecs::event::ManualEventReader, prelude::*, time::TimeUpdateStrategy, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    combat::{AssignAttackPursuit, BreakAttackPursuit, Health},
+    construction::{AssignConstructionWorkers, PlaceConstructionSite},
+    conversion::{AssignConvertPursuit, BreakConvertPursuit},
+    currency::Energy,
+    generator::AssignGeneratorWorkers,
+    map::MapLoaded,
+    match_setup::{EndMatch, MatchMode, MatchSettings, StartMatch},
+    movement::SetUnitPosition,
+    orders::{ClearOrders, HoldPosition, PatrolUnits, QueueOrder, StopUnits},
+    producer::AttemptProductionIncrease,
+    replay::{Recorded, ReplayCommand, ReplayId, ReplaySet},
+    schedule::InGameSet,
+    selectable::{BoxSelection, SelectionOrder},
+    state::GameState,
+    teams::{Players, Team, TeamType},
+};
+
+//	every peer simulates at the same fixed rate, however quickly its frames actually come
+const LOCKSTEP_TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+/// frames simulated between two exchanges of commands
+const TURN_FRAMES: u64 = 6;
+/// turns between a command being given and being carried out, to hide the time it takes to arrive
+const INPUT_DELAY: u64 = 2;
+/// turns between two comparisons of the match state
+const HASH_TURNS: u64 = 10;
+/// turns a state hash is kept around for the slowest peer to send its own
+const HASH_HISTORY: u64 = 100;
+
+pub struct NetworkPlugin {
+    pub settings: NetworkSettings,
+}
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, open_connections)
+            .add_systems(
+                Update,
+                (
+                    accept_players.run_if(in_state(GameState::MainMenu)),
+                    receive_messages,
+                )
+                    .chain()
+                    .before(LockstepSet::Capture),
+            )
+            .add_systems(
+                Update,
+                (
+                    start_lockstep
+                        .run_if(on_event::<MapLoaded>())
+                        .before(LockstepSet::Capture),
+                    (advance_turn, send_messages)
+                        .chain()
+                        .in_set(LockstepSet::Advance),
+                    play_turn.run_if(lockstep_active).in_set(ReplaySet::Play),
+                    count_frame
+                        .run_if(lockstep_active.and_then(lockstep_ready))
+                        .after(InGameSet::DespawnEntities)
+                        .run_if(in_state(GameState::InGame)),
+                    leave_lockstep.run_if(on_event::<EndMatch>()),
+                ),
+            )
+            .configure_sets(
+                Update,
+                (LockstepSet::Capture, LockstepSet::Advance)
+                    .chain()
+                    .after(InGameSet::UserInput)
+                    .before(InGameSet::AIInput)
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(lockstep_active),
+            )
+            //	the simulation waits on the slowest peer, while the input sets keep taking commands
+            .configure_sets(
+                Update,
+                (
+                    InGameSet::AIInput,
+                    InGameSet::Replay,
+                    InGameSet::SelectionState,
+                    InGameSet::SpawnEntities,
+                    InGameSet::EntityUpdates,
+                    InGameSet::ConvertEntities,
+                    InGameSet::DespawnEntities,
+                )
+                    .run_if(lockstep_ready),
+            )
+            .insert_resource(Network::new(self.settings.clone()))
+            .insert_resource(Lockstep::default());
+
+        capture::<BoxSelection>(app);
+        capture::<SetUnitPosition>(app);
+        capture::<PlaceConstructionSite>(app);
+        capture::<AttemptProductionIncrease>(app);
+        capture::<AssignConstructionWorkers>(app);
+        capture::<AssignGeneratorWorkers>(app);
+        capture::<AssignAttackPursuit>(app);
+        capture::<BreakAttackPursuit>(app);
+        capture::<AssignConvertPursuit>(app);
+        capture::<BreakConvertPursuit>(app);
+        capture::<QueueOrder>(app);
+        capture::<ClearOrders>(app);
+        capture::<StopUnits>(app);
+        capture::<HoldPosition>(app);
+        capture::<PatrolUnits>(app);
+        capture::<SelectionOrder>(app);
+    }
+}
+
+/// The steps a lockstep frame goes through before the simulation is allowed to run
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum LockstepSet {
+    Capture,
+    Advance,
+}
+
+/// Whether this game hosts a networked match or joins one, as read from the command line
+#[derive(Clone, Debug)]
+pub enum NetworkSettings {
+    /// waits on a port for the other humans, then starts the match set up in its own menu
+    Host { port: u16, humans: usize },
+    /// plays the match set up by the host at this address
+    Join { address: String },
+}
+
+#[derive(Error, Debug)]
+pub enum NetworkArgsError {
+    #[error("unknown argument {0}")]
+    Unknown(String),
+    #[error("{0} needs a value")]
+    Missing(String),
+    #[error("could not read {flag} from {value}")]
+    Invalid { flag: String, value: String },
+    #[error("--humans only applies to --host")]
+    NotHosting,
+}
+
+impl NetworkSettings {
+    /// reads `--host <port>` with an optional `--humans <count>`, or `--join <address>`,
+    /// and gives `None` when the game is played alone
+    pub fn from_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<Option<Self>, NetworkArgsError> {
+        let mut settings = None;
+        let mut humans = None;
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| NetworkArgsError::Missing(flag.clone()))?;
+            let invalid = || NetworkArgsError::Invalid {
+                flag: flag.clone(),
+                value: value.clone(),
+            };
+
+            match flag.as_str() {
+                "--host" => {
+                    settings = Some(NetworkSettings::Host {
+                        port: value.parse().map_err(|_| invalid())?,
+                        humans: 2,
+                    })
+                }
+                "--humans" => {
+                    //	the host is one of them, and a match alone needs no network
+                    humans = Some(
+                        value
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|count| *count >= 2)
+                            .ok_or_else(invalid)?,
+                    )
+                }
+                "--join" => settings = Some(NetworkSettings::Join { address: value }),
+                _ => return Err(NetworkArgsError::Unknown(flag)),
+            }
+        }
+
+        match (&mut settings, humans) {
+            (Some(NetworkSettings::Host { humans, .. }), Some(count)) => *humans = count,
+            (_, Some(_)) => return Err(NetworkArgsError::NotHosting),
+            _ => (),
+        }
+
+        Ok(settings)
+    }
+}
+
+/// What peers tell each other, one per line
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum Message {
+    /// sent by the host once everyone has joined, with the receiver's own team in the mode
+    Welcome { settings: MatchSettings },
+    /// every command a team gave for a turn, even if there were none
+    Turn {
+        turn: u64,
+        team: TeamType,
+        commands: Vec<ReplayCommand>,
+    },
+    /// the state of the match at the start of a turn, which every peer should agree on
+    Hash {
+        turn: u64,
+        team: TeamType,
+        hash: u64,
+    },
+}
+
+/// A connection to another game, read and written without ever waiting on it
+struct Peer {
+    stream: TcpStream,
+    team: TeamType,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    connected: bool,
+}
+
+impl Peer {
+    fn new(stream: TcpStream, team: TeamType) -> std::io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            team,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            connected: true,
+        })
+    }
+
+    fn send(&mut self, message: &Message) {
+        match ron::to_string(message) {
+            Ok(line) => {
+                self.outgoing.extend(line.as_bytes());
+                self.outgoing.push(b'\n');
+            }
+            Err(error) => error!("could not write message: {}", error),
+        }
+        self.flush();
+    }
+
+    //	whatever the socket does not take now is tried again on the next send
+    fn flush(&mut self) {
+        while !self.outgoing.is_empty() && self.connected {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.connected = false,
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => (),
+                Err(_) => self.connected = false,
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Vec<Message> {
+        let mut buffer = [0; 4096];
+        while self.connected {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.connected = false,
+                Ok(read) => self.incoming.extend(&buffer[..read]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => (),
+                Err(_) => self.connected = false,
+            }
+        }
+        self.flush();
+
+        let mut messages = Vec::new();
+        while let Some(end) = self.incoming.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            match ron::de::from_bytes(&line[..end]) {
+                Ok(message) => messages.push(message),
+                Err(error) => warn!(
+                    "could not read message from team {}: {}",
+                    self.team.0, error
+                ),
+            }
+        }
+        messages
+    }
+}
+
+/// The connections of a networked match, where the host passes on whatever a peer sends to the others
+#[derive(Resource)]
+pub struct Network {
+    settings: NetworkSettings,
+    listener: Option<TcpListener>,
+    peers: Vec<Peer>,
+}
+
+impl Network {
+    fn new(settings: NetworkSettings) -> Self {
+        Self {
+            settings,
+            listener: None,
+            peers: Vec::new(),
+        }
+    }
+
+    fn broadcast(&mut self, message: &Message, except: Option<TeamType>) {
+        for peer in self.peers.iter_mut() {
+            if Some(peer.team) != except {
+                peer.send(message);
+            }
+        }
+    }
+}
+
+/// The turn a networked match is on, and the commands every human gave for the turns to come
+#[derive(Resource, Default)]
+pub struct Lockstep {
+    active: bool,
+    started: bool,
+    local: TeamType,
+    humans: usize,
+    /// frames simulated since the map loaded
+    frame: u64,
+    ready: bool,
+    flushed: Option<u64>,
+    captured: Vec<ReplayCommand>,
+    turns: HashMap<u64, Vec<(TeamType, Vec<ReplayCommand>)>>,
+    due: Vec<ReplayCommand>,
+    hashes: HashMap<u64, u64>,
+    remote_hashes: HashMap<u64, Vec<(TeamType, u64)>>,
+    outgoing: Vec<Message>,
+}
+
+impl Lockstep {
+    fn begin(&mut self, local: TeamType, humans: usize) {
+        *self = Lockstep {
+            active: true,
+            local,
+            humans,
+            ready: true,
+            ..default()
+        };
+
+        //	nobody could have given commands for the turns before the first delay has passed
+        for turn in 0..INPUT_DELAY {
+            self.turns.insert(
+                turn,
+                (0..humans)
+                    .map(|team| (TeamType(team), Vec::new()))
+                    .collect(),
+            );
+        }
+    }
+
+    fn receive_turn(&mut self, turn: u64, team: TeamType, commands: Vec<ReplayCommand>) {
+        self.turns.entry(turn).or_default().push((team, commands));
+    }
+
+    fn receive_hash(&mut self, turn: u64, team: TeamType, hash: u64) {
+        match self.hashes.get(&turn) {
+            Some(local) => check_hash(turn, team, *local, hash),
+            None => self
+                .remote_hashes
+                .entry(turn)
+                .or_default()
+                .push((team, hash)),
+        }
+    }
+
+    /// sends off the commands given during the last turn and, if everyone's have arrived,
+    /// lines up the ones for this turn
+    fn advance(&mut self, hash: impl FnOnce() -> u64) {
+        if self.frame % TURN_FRAMES != 0 {
+            self.ready = true;
+            return;
+        }
+
+        let turn = self.frame / TURN_FRAMES;
+        if self.flushed != Some(turn) {
+            self.flushed = Some(turn);
+
+            let commands = mem::take(&mut self.captured);
+            self.outgoing.push(Message::Turn {
+                turn: turn + INPUT_DELAY,
+                team: self.local,
+                commands: commands.clone(),
+            });
+            self.receive_turn(turn + INPUT_DELAY, self.local, commands);
+
+            //	the simulation has not run since the turn began, so a stall does not change the hash
+            if turn % HASH_TURNS == 0 {
+                let hash = hash();
+                for (team, remote) in self.remote_hashes.remove(&turn).unwrap_or_default() {
+                    check_hash(turn, team, hash, remote);
+                }
+                self.hashes.insert(turn, hash);
+                self.hashes
+                    .retain(|hashed, _| *hashed + HASH_HISTORY * HASH_TURNS > turn);
+                self.outgoing.push(Message::Hash {
+                    turn,
+                    team: self.local,
+                    hash,
+                });
+            }
+        }
+
+        self.ready = self
+            .turns
+            .get(&turn)
+            .is_some_and(|arrived| arrived.len() >= self.humans);
+
+        if self.ready {
+            //	every peer carries out the turn in the same order, whichever commands arrived first
+            let mut arrived = self.turns.remove(&turn).unwrap_or_default();
+            arrived.sort_by_key(|(team, _)| team.0);
+            self.due = arrived
+                .into_iter()
+                .flat_map(|(_, commands)| commands)
+                .collect();
+        }
+    }
+}
+
+fn check_hash(turn: u64, team: TeamType, local: u64, remote: u64) {
+    if local != remote {
+        error!(
+            "desync at turn {}: team {} disagrees on the state of the match",
+            turn, team.0
+        );
+    }
+}
+
+fn lockstep_active(lockstep: Res<Lockstep>) -> bool {
+    lockstep.active
+}
+
+/// whether the simulation may run this frame, which is always the case outside of networked matches
+fn lockstep_ready(lockstep: Option<Res<Lockstep>>) -> bool {
+    lockstep.map_or(true, |lockstep| {
+        !lockstep.active || !lockstep.started || lockstep.ready
+    })
+}
+
+#[derive(Resource)]
+struct CaptureReader<E: Event>(ManualEventReader<E>);
+
+//	commands given locally are taken out before anything reads them, and come back once their turn
+//	is due on every peer, while whatever the simulation sends later in the frame is left alone
+fn capture<E: Recorded>(app: &mut App) {
+    app.insert_resource(CaptureReader::<E>(default()))
+        .add_systems(Update, capture_commands::<E>.in_set(LockstepSet::Capture))
+        .add_systems(
+            Update,
+            skip_simulation_commands::<E>
+                .after(InGameSet::DespawnEntities)
+                .run_if(in_state(GameState::InGame)),
+        );
+}
+
+fn capture_commands<E: Recorded>(
+    mut events: ResMut<Events<E>>,
+    mut reader: ResMut<CaptureReader<E>>,
+    ids: Query<&ReplayId>,
+    mut lockstep: ResMut<Lockstep>,
+) {
+    let captured: Vec<_> = reader
+        .0
+        .read(&events)
+        .filter_map(|event| event.to_command(&ids))
+        .collect();
+    lockstep.captured.extend(captured);
+
+    //	the simulation sends its own commands before reading them, so only the local ones are lost
+    events.clear();
+}
+
+fn skip_simulation_commands<E: Recorded>(
+    events: Res<Events<E>>,
+    mut reader: ResMut<CaptureReader<E>>,
+) {
+    reader.0.clear(&events);
+}
+
+fn open_connections(mut network: ResMut<Network>) {
+    match network.settings.clone() {
+        NetworkSettings::Host { port, humans } => {
+            match TcpListener::bind(("0.0.0.0", port))
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            {
+                Ok(listener) => {
+                    info!("waiting on port {} for {} more players", port, humans - 1);
+                    network.listener = Some(listener);
+                }
+                Err(error) => error!("could not host on port {}: {}", port, error),
+            }
+        }
+        NetworkSettings::Join { address } => {
+            //	the host only knows which team we are once everyone has joined
+            match TcpStream::connect(&address).and_then(|stream| Peer::new(stream, TeamType(0))) {
+                Ok(peer) => {
+                    info!("joined {}, waiting for the match to start", address);
+                    network.peers.push(peer);
+                }
+                Err(error) => error!("could not join {}: {}", address, error),
+            }
+        }
+    }
+}
+
+fn accept_players(
+    mut network: ResMut<Network>,
+    mut settings: ResMut<MatchSettings>,
+    mut lockstep: ResMut<Lockstep>,
+    mut start_match: EventWriter<StartMatch>,
+) {
+    let NetworkSettings::Host { humans, .. } = network.settings else {
+        return;
+    };
+    let Some(listener) = network.listener.as_ref() else {
+        return;
+    };
+
+    let mut joined = Vec::new();
+    while let Ok((stream, address)) = listener.accept() {
+        let team = TeamType(network.peers.len() + joined.len() + 1);
+        match Peer::new(stream, team) {
+            Ok(peer) => {
+                info!("{} joined as team {}", address, team.0);
+                joined.push(peer);
+            }
+            Err(error) => warn!("{} could not join: {}", address, error),
+        }
+    }
+    network.peers.extend(joined);
+
+    if network.peers.len() + 1 < humans {
+        return;
+    }
+
+    //	everyone plays the match set up in the host's menu, with the seed decided here
+    settings.players = settings.players.max(humans);
+    settings.seed = Some(settings.seed.unwrap_or_else(rand::random));
+    for peer in network.peers.iter_mut() {
+        let mut welcome = settings.clone();
+        welcome.mode = MatchMode::Lockstep {
+            local: peer.team,
+            humans,
+        };
+        peer.send(&Message::Welcome { settings: welcome });
+    }
+
+    network.listener = None;
+    settings.mode = MatchMode::Lockstep {
+        local: TeamType(0),
+        humans,
+    };
+    lockstep.begin(TeamType(0), humans);
+    start_match.send(StartMatch);
+}
+
+fn receive_messages(
+    mut network: ResMut<Network>,
+    mut settings: ResMut<MatchSettings>,
+    mut lockstep: ResMut<Lockstep>,
+    mut start_match: EventWriter<StartMatch>,
+    mut end_match: EventWriter<EndMatch>,
+) {
+    let hosting = matches!(network.settings, NetworkSettings::Host { .. });
+
+    let mut received = Vec::new();
+    for peer in network.peers.iter_mut() {
+        received.extend(
+            peer.receive()
+                .into_iter()
+                .map(|message| (peer.team, message)),
+        );
+    }
+
+    for (from, message) in received {
+        if hosting {
+            network.broadcast(&message, Some(from));
+        }
+
+        match message {
+            Message::Welcome { settings: welcome } => {
+                let MatchMode::Lockstep { local, humans } = welcome.mode else {
+                    warn!("the host sent a match that is not networked");
+                    continue;
+                };
+                info!("playing as team {}", local.0);
+                *settings = welcome;
+                lockstep.begin(local, humans);
+                start_match.send(StartMatch);
+            }
+            Message::Turn {
+                turn,
+                team,
+                commands,
+            } => lockstep.receive_turn(turn, team, commands),
+            Message::Hash { turn, team, hash } => lockstep.receive_hash(turn, team, hash),
+        }
+    }
+
+    let lost = network.peers.iter().filter(|peer| !peer.connected).count();
+    if lost > 0 {
+        network.peers.retain(|peer| peer.connected);
+        if lockstep.active {
+            //	the match cannot go on without every human's commands
+            error!("lost connection to {} players, ending the match", lost);
+            end_match.send(EndMatch);
+        }
+    }
+}
+
+fn start_lockstep(mut lockstep: ResMut<Lockstep>, mut commands: Commands) {
+    if !lockstep.active {
+        return;
+    }
+
+    lockstep.started = true;
+    lockstep.frame = 0;
+    commands.insert_resource(TimeUpdateStrategy::ManualDuration(LOCKSTEP_TIMESTEP));
+}
+
+fn advance_turn(
+    mut lockstep: ResMut<Lockstep>,
+    entities: Query<(&ReplayId, &Team, &Transform, Option<&Health>)>,
+    energy: Res<Energy>,
+    players: Res<Players>,
+) {
+    if !lockstep.started {
+        return;
+    }
+
+    lockstep.advance(|| state_hash(&entities, &energy, &players));
+}
+
+//	everything the simulation decides that a desync would eventually show up in
+fn state_hash(
+    entities: &Query<(&ReplayId, &Team, &Transform, Option<&Health>)>,
+    energy: &Energy,
+    players: &Players,
+) -> u64 {
+    let mut entities: Vec<_> = entities.iter().collect();
+    entities.sort_by_key(|(id, ..)| id.0);
+
+    let mut hasher = DefaultHasher::new();
+    for (id, team, transform, health) in entities {
+        id.hash(&mut hasher);
+        team.0.hash(&mut hasher);
+        transform.translation.x.to_bits().hash(&mut hasher);
+        transform.translation.y.to_bits().hash(&mut hasher);
+        health.map(|health| health.0.to_bits()).hash(&mut hasher);
+    }
+    for player in players.list.iter() {
+        energy.get(&player.team).to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn send_messages(mut lockstep: ResMut<Lockstep>, mut network: ResMut<Network>) {
+    for message in mem::take(&mut lockstep.outgoing) {
+        network.broadcast(&message, None);
+    }
+}
+
+fn play_turn(
+    mut lockstep: ResMut<Lockstep>,
+    named: Query<(Entity, &ReplayId)>,
+    mut commands: Commands,
+) {
+    if lockstep.due.is_empty() {
+        return;
+    }
+
+    let entities: HashMap<ReplayId, Entity> =
+        named.iter().map(|(entity, id)| (*id, entity)).collect();
+    for command in mem::take(&mut lockstep.due) {
+        command.send(&entities, &mut commands);
+    }
+}
+
+fn count_frame(mut lockstep: ResMut<Lockstep>) {
+    if lockstep.started {
+        lockstep.frame += 1;
+    }
+}
+
+//	a networked match is played once, after which the game is back to playing alone
+fn leave_lockstep(
+    mut lockstep: ResMut<Lockstep>,
+    mut network: ResMut<Network>,
+    mut settings: ResMut<MatchSettings>,
+    mut commands: Commands,
+) {
+    if !lockstep.active {
+        return;
+    }
+
+    *lockstep = Lockstep::default();
+    network.peers.clear();
+    settings.mode = MatchMode::Play;
+    commands.insert_resource(TimeUpdateStrategy::Automatic);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(lockstep: &mut Lockstep) {
+        lockstep.advance(|| 0);
+        if lockstep.ready {
+            lockstep.frame += 1;
+        }
+    }
+
+    #[test]
+    fn stalls_until_every_human_has_sent_the_turn() {
+        let mut lockstep = Lockstep::default();
+        lockstep.begin(TeamType(0), 2);
+        lockstep.started = true;
+
+        for _ in 0..INPUT_DELAY * TURN_FRAMES {
+            turn(&mut lockstep);
+            assert!(lockstep.ready);
+        }
+
+        turn(&mut lockstep);
+        assert!(!lockstep.ready);
+
+        lockstep.receive_turn(INPUT_DELAY, TeamType(1), Vec::new());
+        turn(&mut lockstep);
+        assert!(lockstep.ready);
+    }
+
+    #[test]
+    fn carries_out_commands_in_team_order() {
+        let mut lockstep = Lockstep::default();
+        lockstep.begin(TeamType(1), 2);
+        lockstep.started = true;
+        lockstep.frame = INPUT_DELAY * TURN_FRAMES;
+
+        let command = |team| ReplayCommand::BoxSelection {
+            rect: Rect::default(),
+            team,
+        };
+        lockstep.receive_turn(INPUT_DELAY, TeamType(1), vec![command(TeamType(1))]);
+        lockstep.receive_turn(INPUT_DELAY, TeamType(0), vec![command(TeamType(0))]);
+        lockstep.advance(|| 0);
+
+        let teams: Vec<_> = lockstep
+            .due
+            .iter()
+            .map(|command| match command {
+                ReplayCommand::BoxSelection { team, .. } => team.0,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(teams, vec![0, 1]);
+    }
+
+    #[test]
+    fn messages_arrive_whole_over_a_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut sender = Peer::new(TcpStream::connect(address).unwrap(), TeamType(1)).unwrap();
+        let mut receiver = Peer::new(listener.accept().unwrap().0, TeamType(0)).unwrap();
+
+        sender.send(&Message::Hash {
+            turn: 3,
+            team: TeamType(1),
+            hash: 42,
+        });
+
+        let mut received = Vec::new();
+        for _ in 0..100 {
+            received.extend(receiver.receive());
+            if !received.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(matches!(
+            received.as_slice(),
+            [Message::Hash {
+                turn: 3,
+                team: TeamType(1),
+                hash: 42
+            }]
+        ));
+    }
+}
@@ -0,0 +1,652 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    ecs::{entity::EntityHashSet, event::ManualEventReader, system::SystemParam},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::AttackPursuit,
+    construction::ConstructionSite,
+    conversion::ConvertPursuit,
+    generator::Generator,
+    movement::{set_moveable_location, Moveable, Moving},
+    nav_agent::{give_target_to_navigator, move_navigator, AssignNavigatorPath, Path},
+    priest::Priest,
+    schedule::InGameSet,
+    selectable::SelectedUnits,
+    teams::{Team, TeamType},
+    warrior::Warrior,
+    worker::Worker,
+};
+
+pub struct OrdersPlugin;
+
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut App) {
+        //	queued moves are added by `set_moveable_location`, so orders are read before it and
+        //	carried out after it, as soon as whoever is moving has arrived
+        app.add_systems(
+            Update,
+            (
+                (
+                    forget_converted_orders,
+                    clear_orders,
+                    queue_orders,
+                    stop_units,
+                    start_patrols,
+                )
+                    .chain()
+                    .before(set_moveable_location),
+                advance_orders
+                    .after(set_moveable_location)
+                    .after(move_navigator)
+                    .before(give_target_to_navigator),
+            )
+                .in_set(InGameSet::EntityUpdates),
+        )
+        .add_event::<QueueOrder>()
+        .add_event::<ClearOrders>()
+        .add_event::<StopUnits>()
+        .add_event::<HoldPosition>()
+        .add_event::<PatrolUnits>();
+    }
+}
+
+/// What a unit can be told to do to something else
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OrderKind {
+    Attack,
+    Convert,
+    Build,
+    Work,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Order {
+    Move {
+        location: Vec3,
+        corridor: Option<Vec2>,
+        attack_move: bool,
+    },
+    Target {
+        kind: OrderKind,
+        target: Entity,
+    },
+}
+
+impl Order {
+    /// where the order takes the unit, while there is still somewhere to go
+    pub fn position(&self, transforms: &Query<&GlobalTransform>) -> Option<Vec2> {
+        match self {
+            Order::Move { location, .. } => Some(location.truncate()),
+            Order::Target { target, .. } => transforms
+                .get(*target)
+                .ok()
+                .map(|transform| transform.translation().truncate()),
+        }
+    }
+}
+
+/// The orders a unit carries out one after another, once it has finished with whatever it is doing
+#[derive(Component, Default)]
+pub struct Orders {
+    /// the queued order being carried out, which is finished once the unit is idle again
+    pub current: Option<Order>,
+    pub queued: VecDeque<Order>,
+}
+
+/// Adds an order on `target` to the end of every unit's queue
+#[derive(Event)]
+pub struct QueueOrder {
+    pub units: Vec<Entity>,
+    pub kind: OrderKind,
+    pub target: Entity,
+}
+
+/// Drops everything the units had queued, for when they are given a new order outright
+#[derive(Event)]
+pub struct ClearOrders {
+    pub units: Vec<Entity>,
+}
+
+/// Drops everything the team's selected units are doing or were going to do,
+/// leaving them where they stand
+#[derive(Event)]
+pub struct StopUnits {
+    pub team: TeamType,
+}
+
+/// Stops the team's selected units and keeps them there, only fighting what comes within reach
+#[derive(Event)]
+pub struct HoldPosition {
+    pub team: TeamType,
+}
+
+/// Walks the team's selected units round from where they are through every point and back,
+/// fighting on the way
+#[derive(Event)]
+pub struct PatrolUnits {
+    pub team: TeamType,
+    pub points: Vec<Vec2>,
+}
+
+/// Attacks or converts only within range, never chasing anything out of it
+#[derive(Component)]
+pub struct Holding;
+
+/// The route a unit keeps walking until it is given another order
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Patrol {
+    pub points: Vec<Vec2>,
+    /// the point being walked to
+    pub next: usize,
+    /// set while engaging, so the unit heads back to the same point afterwards
+    pub resume: bool,
+}
+
+//	a converted unit has no business finishing what its old team queued up
+fn forget_converted_orders(mut units: Query<(Ref<Team>, &mut Orders), Changed<Team>>) {
+    for (team, mut orders) in units.iter_mut() {
+        if !team.is_added() {
+            orders.current = None;
+            orders.queued.clear();
+        }
+    }
+}
+
+fn clear_orders(
+    mut clears: EventReader<ClearOrders>,
+    mut units: Query<&mut Orders>,
+    mut commands: Commands,
+) {
+    for clear in clears.read() {
+        for &entity in clear.units.iter() {
+            if let Ok(mut orders) = units.get_mut(entity) {
+                orders.current = None;
+                orders.queued.clear();
+                //	holding and patrolling last only until the next order
+                commands.entity(entity).remove::<(Holding, Patrol)>();
+            }
+        }
+    }
+}
+
+fn queue_orders(mut queues: EventReader<QueueOrder>, mut units: Query<&mut Orders>) {
+    for queue in queues.read() {
+        for &entity in queue.units.iter() {
+            if let Ok(mut orders) = units.get_mut(entity) {
+                orders.queued.push_back(Order::Target {
+                    kind: queue.kind,
+                    target: queue.target,
+                });
+            }
+        }
+    }
+}
+
+fn stop_units(
+    mut stops: EventReader<StopUnits>,
+    mut holds: EventReader<HoldPosition>,
+    selected: Res<SelectedUnits>,
+    mut units: Query<(
+        &mut Orders,
+        &mut Moveable,
+        &mut Moving,
+        &Transform,
+        Option<&Path>,
+    )>,
+    mut commands: Commands,
+) {
+    let stopped = stops.read().map(|stop| (stop.team, false));
+    let held = holds.read().map(|hold| (hold.team, true));
+
+    //	the selection is read as the command is carried out, after any selection given before it
+    for (team, hold) in stopped.chain(held) {
+        for &entity in selected.entities.iter(&team) {
+            let Ok((mut orders, mut moveable, mut moving, transform, path)) = units.get_mut(entity)
+            else {
+                continue;
+            };
+
+            halt(
+                entity,
+                &mut orders,
+                &mut moveable,
+                &mut moving,
+                transform,
+                path,
+                &mut commands,
+            );
+            if hold {
+                commands.entity(entity).insert(Holding);
+            }
+        }
+    }
+}
+
+fn start_patrols(
+    mut patrols: EventReader<PatrolUnits>,
+    selected: Res<SelectedUnits>,
+    mut units: Query<(
+        &mut Orders,
+        &mut Moveable,
+        &mut Moving,
+        &Transform,
+        Option<&Path>,
+    )>,
+    mut commands: Commands,
+) {
+    for patrol in patrols.read() {
+        let patrolling = selected.entities.to_vec(&patrol.team);
+        let positions: Vec<Vec2> = patrolling
+            .iter()
+            .filter_map(|&entity| units.get(entity).ok())
+            .map(|(_, _, _, transform, _)| transform.translation.xy())
+            .collect();
+        if positions.is_empty() || patrol.points.is_empty() {
+            continue;
+        }
+        let centre = positions.iter().sum::<Vec2>() / positions.len() as f32;
+
+        for &entity in patrolling.iter() {
+            let Ok((mut orders, mut moveable, mut moving, transform, path)) = units.get_mut(entity)
+            else {
+                continue;
+            };
+
+            //	the group keeps its shape, each unit walking its own copy of the route
+            let position = transform.translation.xy();
+            let offset = position - centre;
+            let points = std::iter::once(position)
+                .chain(patrol.points.iter().map(|&point| point + offset))
+                .collect();
+
+            halt(
+                entity,
+                &mut orders,
+                &mut moveable,
+                &mut moving,
+                transform,
+                path,
+                &mut commands,
+            );
+            commands.entity(entity).insert(Patrol {
+                points,
+                next: 0,
+                resume: false,
+            });
+        }
+    }
+}
+
+//	whatever the unit was doing is dropped, along with anything it was going to do
+fn halt(
+    entity: Entity,
+    orders: &mut Orders,
+    moveable: &mut Moveable,
+    moving: &mut Moving,
+    transform: &Transform,
+    path: Option<&Path>,
+    commands: &mut Commands,
+) {
+    orders.current = None;
+    orders.queued.clear();
+    moveable.location = transform.translation;
+    moving.0 = false;
+
+    if let Some(path) = path {
+        commands.entity(path.target).despawn_recursive();
+    }
+    commands
+        .entity(entity)
+        .remove::<(Path, AttackPursuit, ConvertPursuit, Holding, Patrol)>();
+}
+
+type OrderedUnitData = (
+    Entity,
+    &'static mut Orders,
+    &'static mut Moveable,
+    &'static mut Moving,
+    Has<Path>,
+    Has<AttackPursuit>,
+    Has<ConvertPursuit>,
+    Option<&'static mut Patrol>,
+);
+
+/// Where workers can be put to building or working
+#[derive(SystemParam)]
+struct Workplaces<'w, 's> {
+    sites: Query<'w, 's, &'static mut ConstructionSite>,
+    generators: Query<'w, 's, &'static mut Generator>,
+}
+
+fn advance_orders(
+    mut units: Query<OrderedUnitData>,
+    abilities: Query<(Has<Warrior>, Has<Priest>, Has<Worker>)>,
+    mut workplaces: Workplaces,
+    transforms: Query<&Transform>,
+    mut nav_path_assigner: ResMut<Events<AssignNavigatorPath>>,
+    mut assigned: Local<ManualEventReader<AssignNavigatorPath>>,
+    mut commands: Commands,
+) {
+    //	a path sent this frame has not been found yet, but the unit is already on its way
+    let heading: EntityHashSet = assigned
+        .read(&nav_path_assigner)
+        .map(|assignment| assignment.entity)
+        .collect();
+
+    for (
+        entity,
+        mut orders,
+        mut moveable,
+        mut moving,
+        pathing,
+        attacking,
+        converting,
+        mut patrol,
+    ) in units.iter_mut()
+    {
+        if orders.current.is_none() && orders.queued.is_empty() && patrol.is_none() {
+            continue;
+        }
+
+        //	building and working keep a unit busy for as long as there is anything left to do
+        let working = match orders.current {
+            Some(Order::Target {
+                kind: OrderKind::Build,
+                target,
+            }) => workplaces.sites.contains(target),
+            Some(Order::Target {
+                kind: OrderKind::Work,
+                target,
+            }) => workplaces
+                .generators
+                .get(target)
+                .is_ok_and(|generator| generator.is_running),
+            _ => false,
+        };
+        if attacking || converting {
+            if let Some(patrol) = patrol.as_mut() {
+                patrol.resume = true;
+            }
+        }
+        if pathing || heading.contains(&entity) || attacking || converting || working {
+            continue;
+        }
+
+        orders.current = None;
+        let (warrior, priest, worker) = abilities.get(entity).unwrap_or_default();
+
+        //	orders the unit cannot carry out, or whose target is gone, are passed over
+        while let Some(order) = orders.queued.pop_front() {
+            let started = match order {
+                Order::Move {
+                    location,
+                    corridor,
+                    attack_move,
+                } => {
+                    moveable.location = location;
+                    moveable.attack_move = attack_move;
+                    moving.0 = true;
+                    nav_path_assigner.send(AssignNavigatorPath {
+                        entity,
+                        location,
+                        corridor,
+                    });
+                    true
+                }
+                Order::Target { kind, target } => {
+                    let Ok(target_transform) = transforms.get(target) else {
+                        continue;
+                    };
+
+                    match kind {
+                        OrderKind::Attack if warrior => {
+                            commands.entity(entity).insert(AttackPursuit {
+                                cooldown: 0.0,
+                                prey: target,
+                            });
+                            true
+                        }
+                        OrderKind::Convert if priest => {
+                            commands.entity(entity).insert(ConvertPursuit {
+                                cooldown: 0.0,
+                                prey: target,
+                            });
+                            true
+                        }
+                        OrderKind::Build => {
+                            let Ok(mut site) = workplaces.sites.get_mut(target) else {
+                                continue;
+                            };
+                            if !site.assigned_units.contains(&entity)
+                                && !site.working_units.contains(&entity)
+                            {
+                                site.assigned_units.push(entity);
+                            }
+                            walk_to(
+                                entity,
+                                target_transform,
+                                &mut moveable,
+                                &mut moving,
+                                &mut nav_path_assigner,
+                            );
+                            true
+                        }
+                        OrderKind::Work if worker => {
+                            let Ok(mut generator) = workplaces.generators.get_mut(target) else {
+                                continue;
+                            };
+                            if !generator.assigned_workers.contains(&entity)
+                                && !generator.working_workers.contains(&entity)
+                            {
+                                generator.assigned_workers.push(entity);
+                            }
+                            walk_to(
+                                entity,
+                                target_transform,
+                                &mut moveable,
+                                &mut moving,
+                                &mut nav_path_assigner,
+                            );
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+            };
+
+            if started {
+                orders.current = Some(order);
+                break;
+            }
+        }
+
+        //	a patrol is walked once nothing queued is left, starting over from the first point after the last
+        if let Some(patrol) = patrol.as_mut().filter(|_| orders.current.is_none()) {
+            if patrol.resume {
+                patrol.resume = false;
+            } else {
+                patrol.next = (patrol.next + 1) % patrol.points.len();
+            }
+
+            let location = patrol.points[patrol.next].extend(0.0);
+            moveable.location = location;
+            moveable.attack_move = true;
+            moving.0 = true;
+            nav_path_assigner.send(AssignNavigatorPath {
+                entity,
+                location,
+                corridor: None,
+            });
+        }
+    }
+}
+
+//	structures block the navmesh, so the path ends at the closest point around it
+fn walk_to(
+    entity: Entity,
+    target: &Transform,
+    moveable: &mut Moveable,
+    moving: &mut Moving,
+    nav_path_assigner: &mut Events<AssignNavigatorPath>,
+) {
+    moveable.location = target.translation;
+    moveable.attack_move = false;
+    moving.0 = true;
+    nav_path_assigner.send(AssignNavigatorPath {
+        entity,
+        location: target.translation,
+        corridor: None,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        combat::CombatPlugin,
+        conversion::ConversionPlugin,
+        detection::DetectionPlugin,
+        producer::ProductionType,
+        teams::TeamType,
+        testing::{Scenario, TestApp},
+    };
+
+    /// a warrior with attacks queued on two workers next to it, returned in that order
+    fn queued_attacks() -> (TestApp, Entity, Entity, Entity) {
+        let mut test = TestApp::new((OrdersPlugin, CombatPlugin));
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Warrior, TeamType(0), Vec2::new(0.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(20.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(0.0, 20.0)),
+            ],
+            ..default()
+        });
+        let (warrior, first, second) = (spawned.units[0], spawned.units[1], spawned.units[2]);
+
+        for target in [first, second] {
+            test.send(QueueOrder {
+                units: vec![warrior],
+                kind: OrderKind::Attack,
+                target,
+            });
+        }
+        (test, warrior, first, second)
+    }
+
+    #[test]
+    fn queued_attacks_are_carried_out_in_turn() {
+        let (mut test, _, first, second) = queued_attacks();
+        test.step_seconds(1.0);
+        test.assert_health_below(first, 24.0);
+        assert_eq!(test.health(second), 24.0);
+
+        test.step_seconds(4.0);
+        test.assert_despawned(first);
+        test.step_seconds(1.0);
+        test.assert_health_below(second, 24.0);
+    }
+
+    #[test]
+    fn cleared_orders_are_not_carried_out() {
+        let (mut test, warrior, first, second) = queued_attacks();
+        test.step_seconds(1.0);
+        test.send(ClearOrders {
+            units: vec![warrior],
+        });
+
+        //	the attack already under way is left to finish
+        test.step_seconds(5.0);
+        test.assert_despawned(first);
+        assert_eq!(test.health(second), 24.0);
+    }
+
+    #[test]
+    fn stopped_units_drop_what_they_were_doing() {
+        let (mut test, warrior, first, second) = queued_attacks();
+        test.step_seconds(1.0);
+        test.select_units(TeamType(0), &[warrior]);
+        test.send(StopUnits { team: TeamType(0) });
+        //	a blow already struck can still land while the stop is taking hold
+        test.step(2);
+        let wounded = test.health(first);
+
+        test.step_seconds(5.0);
+        assert!(test.app.world().get::<AttackPursuit>(warrior).is_none());
+        assert_eq!(test.health(first), wounded);
+        assert_eq!(test.health(second), 24.0);
+    }
+
+    #[test]
+    fn holding_units_only_fight_what_they_can_reach() {
+        let mut test = TestApp::new((
+            OrdersPlugin,
+            CombatPlugin,
+            ConversionPlugin,
+            DetectionPlugin,
+        ));
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Warrior, TeamType(0), Vec2::new(0.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(200.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(0.0, 20.0)),
+            ],
+            ..default()
+        });
+        let (warrior, far, near) = (spawned.units[0], spawned.units[1], spawned.units[2]);
+
+        test.select_units(TeamType(0), &[warrior]);
+        test.send(HoldPosition { team: TeamType(0) });
+        test.step_seconds(2.0);
+
+        //	the worker further off is seen, but left alone rather than chased
+        let prey = test
+            .app
+            .world()
+            .get::<AttackPursuit>(warrior)
+            .map(|pursuit| pursuit.prey);
+        assert_eq!(prey, Some(near));
+        assert_eq!(test.health(far), 24.0);
+        test.assert_health_below(near, 24.0);
+    }
+
+    #[test]
+    fn only_attack_moves_stop_for_enemies_on_the_way() {
+        let mut test = TestApp::new((
+            OrdersPlugin,
+            CombatPlugin,
+            ConversionPlugin,
+            DetectionPlugin,
+        ));
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Warrior, TeamType(0), Vec2::new(0.0, 0.0)),
+                (ProductionType::Warrior, TeamType(0), Vec2::new(0.0, 40.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(100.0, 20.0)),
+            ],
+            ..default()
+        });
+        let (walking, attacking) = (spawned.units[0], spawned.units[1]);
+
+        //	both are partway along a path, which nothing here moves them down
+        for (entity, attack_move) in [(walking, false), (attacking, true)] {
+            let world = test.app.world_mut();
+            let target = world.spawn(TransformBundle::default()).id();
+            world.entity_mut(entity).insert(Path {
+                current: Vec2::new(400.0, 0.0),
+                next: Vec::new(),
+                target,
+                corridor: None,
+            });
+            world.get_mut::<Moveable>(entity).unwrap().attack_move = attack_move;
+        }
+        test.step_seconds(1.0);
+
+        assert!(test.app.world().get::<AttackPursuit>(walking).is_none());
+        assert!(test.app.world().get::<AttackPursuit>(attacking).is_some());
+    }
+}
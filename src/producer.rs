@@ -1,20 +1,19 @@
-use bevy::{math::vec3, prelude::*};
+use bevy::{ecs::system::SystemParam, math::vec3, prelude::*};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     ai::Idle,
+    archetypes::{rescale, Balance, UnitArchetypesChanged, UnitRegistry},
     currency::Energy,
     schedule::InGameSet,
     selectable::SelectedStructures,
     structure::Structure,
-    teams::{Team, TeamType},
+    teams::{Players, Team, TeamType},
+    unit::Unit,
 };
 
 pub const SPAWN_OFFSET: Vec3 = vec3(0.0, -50.0, 0.1);
 
-pub const WORKER_COST: f32 = 10.0;
-pub const PRIEST_COST: f32 = 18.0;
-pub const WARRIOR_COST: f32 = 14.0;
-
 pub struct ProducerPlugin;
 
 impl Plugin for ProducerPlugin {
@@ -27,6 +26,10 @@ impl Plugin for ProducerPlugin {
             )
                 .in_set(InGameSet::EntityUpdates),
         )
+        .add_systems(
+            Update,
+            rebalance_productions.run_if(on_event::<UnitArchetypesChanged>()),
+        )
         .add_event::<AttemptProductionIncrease>()
         .add_event::<Produce>()
         .add_event::<DisplayProducerUI>()
@@ -39,6 +42,7 @@ pub struct Producer {
     pub current_production: ProductionType,
     pub queue: Vec<ProductionType>,
     pub value: f32,
+    //	seconds of build time worked through every second
     pub rate: f32,
     pub post_spawn_location: Vec3,
 }
@@ -49,7 +53,7 @@ impl Default for Producer {
             current_production: ProductionType::None,
             queue: Vec::new(),
             value: 0.0,
-            rate: 2.0,
+            rate: 1.0,
             post_spawn_location: Vec3::ZERO,
         }
     }
@@ -71,6 +75,7 @@ impl Clone for Producer {
 pub struct Production {
     pub production_type: ProductionType,
     pub cost: f32,
+    pub build_time: f32,
     pub queue: i32,
 }
 
@@ -79,14 +84,16 @@ impl Clone for Production {
         Self {
             production_type: self.production_type.clone(),
             cost: self.cost.clone(),
+            build_time: self.build_time.clone(),
             queue: self.queue.clone(),
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub enum ProductionType {
     None,
+    Hero,
     Worker,
     Priest,
     Warrior,
@@ -96,6 +103,7 @@ impl Clone for ProductionType {
     fn clone(&self) -> Self {
         match self {
             Self::None => Self::None,
+            Self::Hero => Self::Hero,
             Self::Worker => Self::Worker,
             Self::Priest => Self::Priest,
             Self::Warrior => Self::Warrior,
@@ -103,6 +111,12 @@ impl Clone for ProductionType {
     }
 }
 
+/// Raises the number of units its team can field
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+pub struct Supply {
+    pub amount: usize,
+}
+
 #[derive(Component)]
 pub struct PostSpawnMarker {
     pub not_set: bool,
@@ -128,21 +142,55 @@ pub struct Produce {
     pub team: TeamType,
 }
 
+/// Units on the field and the supply that allows for more
+#[derive(SystemParam)]
+struct SupplyCount<'w, 's> {
+    units: Query<'w, 's, &'static Team, With<Unit>>,
+    supplies: Query<'w, 's, (&'static Supply, &'static Team)>,
+    balance: Res<'w, Balance>,
+}
+
+impl SupplyCount<'_, '_> {
+    /// units fielded and queued against the supply available, for one team
+    fn usage(
+        &self,
+        team: &TeamType,
+        producers: &Query<(&mut Producer, &Children, &Team)>,
+    ) -> (usize, usize) {
+        let fielded = self.units.iter().filter(|t| t.0 == *team).count();
+        let queued: usize = producers
+            .iter()
+            .filter(|(_, _, t)| t.0 == *team)
+            .map(|(producer, _, _)| producer.queue.len())
+            .sum();
+        let available: usize = self
+            .supplies
+            .iter()
+            .filter(|(_, t)| t.0 == *team)
+            .map(|(supply, _)| supply.amount)
+            .sum();
+
+        (fielded + queued, self.balance.base_supply + available)
+    }
+}
+
 fn attempt_production_increase(
     mut attempt_event: EventReader<AttemptProductionIncrease>,
     selected_structures: Res<SelectedStructures>,
-    mut producer_query: Query<(&mut Producer, &Children)>,
+    mut producer_query: Query<(&mut Producer, &Children, &Team)>,
     mut production_query: Query<&mut Production>,
+    supply: SupplyCount,
     mut energy: ResMut<Energy>,
 ) {
     for attempt in attempt_event.read() {
-        let selected_entities = match attempt.team {
-            TeamType::Human => selected_structures.entities.human.clone(),
-            TeamType::CPU => selected_structures.entities.cpu.clone(),
-        };
+        let (mut used, available) = supply.usage(&attempt.team, &producer_query);
 
-        for entity in selected_entities {
-            if let Ok((mut producer, children)) = producer_query.get_mut(entity) {
+        //	every selected producer queues one, for as long as the supply lasts
+        for &entity in selected_structures.entities.iter(&attempt.team) {
+            if used >= available {
+                break;
+            }
+            if let Ok((mut producer, children, _)) = producer_query.get_mut(entity) {
                 for &child in children.iter() {
                     if let Ok(mut production) = production_query.get_mut(child) {
                         // info!(
@@ -154,6 +202,7 @@ fn attempt_production_increase(
                         {
                             energy.add(&attempt.team, -production.cost);
                             production.queue += 1;
+                            used += 1;
 
                             producer.queue.push(production.production_type.clone());
 
@@ -192,9 +241,9 @@ fn produce(
                         //     "producer [ value: {:?}\t rate: {:?} ]",
                         //     producer.value, producer.rate
                         // );
-                        if producer.value >= production.cost {
+                        if producer.value >= production.build_time {
                             //	leave the remainder, so as to avoid value loss over time
-                            producer.value = producer.value % production.cost;
+                            producer.value = producer.value % production.build_time;
                             production.queue -= 1;
 
                             //  create unit
@@ -202,7 +251,7 @@ fn produce(
                                 production_type: producer.current_production.clone(),
                                 position: transform.translation() + SPAWN_OFFSET,
                                 location: producer.post_spawn_location,
-                                team: team.0.clone(),
+                                team: team.0,
                             });
 
                             //  shift production
@@ -223,6 +272,7 @@ fn produce(
 
 fn display_post_spawn_marker(
     selected_structures: Res<SelectedStructures>,
+    players: Res<Players>,
     producer_query: Query<(&GlobalTransform, &Producer, &Children)>,
     mut marker_query: Query<
         (&mut Visibility, &mut Transform, &PostSpawnMarker),
@@ -233,7 +283,7 @@ fn display_post_spawn_marker(
         *visibility = Visibility::Hidden;
     }
 
-    for &selected_entity in selected_structures.entities.human.iter() {
+    for &selected_entity in selected_structures.entities.iter(&players.local) {
         if let Ok((global_transform, producer, children)) = producer_query.get(selected_entity) {
             for &child in children.iter() {
                 if let Ok((mut visibility, mut transform, marker)) = marker_query.get_mut(child) {
@@ -248,3 +298,129 @@ fn display_post_spawn_marker(
         }
     }
 }
+
+//	queued units were already paid for, so only the price of new ones changes
+fn rebalance_productions(
+    registry: Res<UnitRegistry>,
+    mut producers: Query<(&mut Producer, &Children)>,
+    mut productions: Query<&mut Production>,
+) {
+    for (mut producer, children) in producers.iter_mut() {
+        for &child in children.iter() {
+            let Ok(mut production) = productions.get_mut(child) else {
+                continue;
+            };
+            let Some(archetype) = registry.get(&production.production_type) else {
+                continue;
+            };
+
+            //	keep the progress on the current unit proportional to its new build time
+            if production.production_type == producer.current_production {
+                producer.value =
+                    rescale(producer.value, production.build_time, archetype.build_time);
+            }
+
+            production.cost = archetype.cost;
+            production.build_time = archetype.build_time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{Scenario, TestApp},
+        unit::UnitPlugin,
+    };
+
+    #[test]
+    fn producer_queue_spawns_units() {
+        let mut test = TestApp::new((ProducerPlugin, UnitPlugin));
+        let spawned = test.spawn(&Scenario {
+            structures: vec![("Producer".into(), TeamType(0), Vec2::ZERO)],
+            energy: vec![(TeamType(0), 100.0)],
+            ..default()
+        });
+
+        test.select_structures(TeamType(0), &spawned.structures);
+        for _ in 0..2 {
+            test.send(AttemptProductionIncrease {
+                production_type: ProductionType::Worker,
+                team: TeamType(0),
+            });
+        }
+
+        //	workers cost 10 and take 5 seconds each
+        test.step(1);
+        test.assert_energy(TeamType(0), 80.0);
+        assert!(test.find::<Unit>(TeamType(0)).is_empty());
+
+        test.step_seconds(10.5);
+        assert_eq!(test.find::<Unit>(TeamType(0)).len(), 2);
+    }
+
+    #[test]
+    fn supply_depots_raise_the_unit_cap() {
+        let mut test = TestApp::new((ProducerPlugin, UnitPlugin));
+        test.app.world_mut().resource_mut::<Balance>().base_supply = 1;
+        let spawned = test.spawn(&Scenario {
+            structures: vec![("Producer".into(), TeamType(0), Vec2::ZERO)],
+            energy: vec![(TeamType(0), 100.0)],
+            ..default()
+        });
+
+        //	the second worker would be one more than the team is supplied for
+        test.select_structures(TeamType(0), &spawned.structures);
+        for _ in 0..2 {
+            test.send(AttemptProductionIncrease {
+                production_type: ProductionType::Worker,
+                team: TeamType(0),
+            });
+        }
+        test.step(1);
+        test.assert_energy(TeamType(0), 90.0);
+
+        test.spawn(&Scenario {
+            structures: vec![("SupplyDepot".into(), TeamType(0), Vec2::new(200.0, 0.0))],
+            ..default()
+        });
+        test.send(AttemptProductionIncrease {
+            production_type: ProductionType::Worker,
+            team: TeamType(0),
+        });
+        test.step(1);
+        test.assert_energy(TeamType(0), 80.0);
+    }
+
+    #[test]
+    fn producers_selected_together_share_the_unit_cap() {
+        let mut test = TestApp::new((ProducerPlugin, UnitPlugin));
+        test.app.world_mut().resource_mut::<Balance>().base_supply = 1;
+        let spawned = test.spawn(&Scenario {
+            structures: vec![
+                ("Producer".into(), TeamType(0), Vec2::ZERO),
+                ("Producer".into(), TeamType(0), Vec2::new(300.0, 0.0)),
+            ],
+            energy: vec![(TeamType(0), 100.0)],
+            ..default()
+        });
+
+        //	there is room for one more worker, however many producers are asked
+        test.select_structures(TeamType(0), &spawned.structures);
+        test.send(AttemptProductionIncrease {
+            production_type: ProductionType::Worker,
+            team: TeamType(0),
+        });
+        test.step(1);
+
+        test.assert_energy(TeamType(0), 90.0);
+        let world = test.app.world_mut();
+        let queued: usize = world
+            .query::<&Producer>()
+            .iter(world)
+            .map(|producer| producer.queue.len())
+            .sum();
+        assert_eq!(queued, 1);
+    }
+}
@@ -0,0 +1,880 @@
+use std::{fs, path::Path, time::Duration};
+
+use bevy::{ecs::event::ManualEventReader, prelude::*, time::TimeUpdateStrategy, utils::HashMap};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    ai::AIDifficulty,
+    combat::{AssignAttackPursuit, BreakAttackPursuit},
+    construction::{AssignConstructionWorkers, PlaceConstructionSite},
+    conversion::{AssignConvertPursuit, BreakConvertPursuit},
+    generator::AssignGeneratorWorkers,
+    map::{MapLoaded, MapSource},
+    match_setup::{EndMatch, MatchMode, MatchRng, MatchSettings, StartMatch, TeamSetup},
+    movement::{Formation, SetUnitPosition},
+    orders::{ClearOrders, HoldPosition, OrderKind, PatrolUnits, QueueOrder, StopUnits},
+    producer::{AttemptProductionIncrease, ProductionType},
+    schedule::InGameSet,
+    selectable::{BoxSelection, SelectionOrder},
+    state::GameState,
+    structure::StructureType,
+    teams::{Players, Team, TeamType},
+    victory::MatchRules,
+};
+
+pub const DEFAULT_REPLAY_PATH: &str = "replays/last.replay.ron";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                finish_recording.run_if(on_event::<StartMatch>().or_else(on_event::<EndMatch>())),
+                (start_recording, start_playback)
+                    .run_if(on_event::<MapLoaded>())
+                    .before(InGameSet::Replay),
+                assign_replay_ids.in_set(ReplaySet::Name),
+                play_commands
+                    .run_if(resource_exists::<ReplayPlayer>)
+                    .in_set(ReplaySet::Play),
+                advance_frame
+                    .after(ReplaySet::Record)
+                    .in_set(InGameSet::Replay),
+            ),
+        )
+        .add_systems(OnEnter(GameState::GameOver), finish_recording)
+        .configure_sets(
+            Update,
+            (ReplaySet::Name, ReplaySet::Play, ReplaySet::Record)
+                .chain()
+                .in_set(InGameSet::Replay),
+        )
+        .insert_resource(ReplayRecorder::new(Some(DEFAULT_REPLAY_PATH.into())))
+        .insert_resource(ReplayIds::default());
+
+        record::<BoxSelection>(app);
+        record::<SetUnitPosition>(app);
+        record::<PlaceConstructionSite>(app);
+        record::<AttemptProductionIncrease>(app);
+        record::<AssignConstructionWorkers>(app);
+        record::<AssignGeneratorWorkers>(app);
+        record::<AssignAttackPursuit>(app);
+        record::<BreakAttackPursuit>(app);
+        record::<AssignConvertPursuit>(app);
+        record::<BreakConvertPursuit>(app);
+        record::<QueueOrder>(app);
+        record::<ClearOrders>(app);
+        record::<StopUnits>(app);
+        record::<HoldPosition>(app);
+        record::<PatrolUnits>(app);
+        record::<SelectionOrder>(app);
+    }
+}
+
+/// The steps commands go through each frame, where anything else feeding commands in plays them
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum ReplaySet {
+    Name,
+    Play,
+    Record,
+}
+
+/// Names an entity the same way in every run of a match, unlike its `Entity`
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(transparent)]
+pub struct ReplayId(pub u64);
+
+#[derive(Resource, Default)]
+struct ReplayIds {
+    next: u64,
+}
+
+/// A command as it is stored in a replay, with entities named by their `ReplayId`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ReplayCommand {
+    BoxSelection {
+        rect: Rect,
+        team: TeamType,
+    },
+    SetUnitPosition {
+        position: Vec2,
+        direction: Vec2,
+        formation: Formation,
+        team: TeamType,
+        #[serde(default)]
+        queued: bool,
+        #[serde(default)]
+        attack_move: bool,
+    },
+    PlaceConstructionSite {
+        structure_type: StructureType,
+        team: TeamType,
+        position: Vec2,
+        effort: f32,
+        cost: f32,
+    },
+    AttemptProductionIncrease {
+        production_type: ProductionType,
+        team: TeamType,
+    },
+    AssignConstructionWorkers {
+        site: ReplayId,
+        units: Vec<ReplayId>,
+    },
+    AssignGeneratorWorkers {
+        generator: ReplayId,
+        workers: Vec<ReplayId>,
+    },
+    AssignAttackPursuit {
+        predators: Vec<ReplayId>,
+        prey: ReplayId,
+    },
+    BreakAttackPursuit {
+        entities: Vec<ReplayId>,
+    },
+    AssignConvertPursuit {
+        predators: Vec<ReplayId>,
+        prey: ReplayId,
+    },
+    BreakConvertPursuit {
+        entities: Vec<ReplayId>,
+    },
+    QueueOrder {
+        units: Vec<ReplayId>,
+        kind: OrderKind,
+        target: ReplayId,
+    },
+    ClearOrders {
+        units: Vec<ReplayId>,
+    },
+    StopUnits {
+        team: TeamType,
+    },
+    HoldPosition {
+        team: TeamType,
+    },
+    PatrolUnits {
+        team: TeamType,
+        points: Vec<Vec2>,
+    },
+    SelectionOrder {
+        team: TeamType,
+        queued: bool,
+        site: Option<ReplayId>,
+        generator: Option<ReplayId>,
+        prey: Option<ReplayId>,
+        convertee: Option<ReplayId>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedCommand {
+    /// frames since the map loaded
+    pub frame: usize,
+    pub command: ReplayCommand,
+}
+
+/// Everything needed to play a match out again: how it was set up and every command given in it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub map: MapSource,
+    pub players: usize,
+    pub teams: TeamSetup,
+    pub difficulty: AIDifficulty,
+    pub rules: MatchRules,
+    /// how long each frame took, so a match recorded with a window runs the same without one
+    pub frame_nanos: Vec<u64>,
+    pub commands: Vec<RecordedCommand>,
+}
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("could not access replay file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse replay file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write replay: {0}")]
+    Write(#[from] ron::Error),
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, ReplayError> {
+        Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), ReplayError> {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    /// the settings that start the recorded match over, with every team following the replay
+    pub fn settings(&self) -> MatchSettings {
+        MatchSettings {
+            map: self.map.clone(),
+            players: self.players,
+            teams: self.teams,
+            difficulty: self.difficulty,
+            rules: self.rules.clone(),
+            mode: MatchMode::Replay,
+            seed: Some(self.seed),
+        }
+    }
+}
+
+/// Writes the commands of the running match to a replay, saved once the match ends
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    /// where the replay is written, or nowhere if `None`
+    pub path: Option<String>,
+    replay: Option<Replay>,
+    frame: usize,
+}
+
+impl ReplayRecorder {
+    pub fn new(path: Option<String>) -> Self {
+        Self {
+            path,
+            replay: None,
+            frame: 0,
+        }
+    }
+
+    /// saves what has been recorded so far and stops recording
+    pub fn finish(&mut self) {
+        let (Some(path), Some(replay)) = (&self.path, self.replay.take()) else {
+            return;
+        };
+
+        match replay.save(path) {
+            Ok(_) => info!("replay saved to {}", path),
+            Err(error) => error!("replay not saved to {}: {}", path, error),
+        }
+    }
+}
+
+/// Feeds the commands of a replay back into the match as it plays
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    frame: usize,
+    next: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            frame: 0,
+            next: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.replay.frame_nanos.len()
+    }
+}
+
+/// An event that is written to replays as a command
+pub trait Recorded: Event + Sized {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand>;
+}
+
+#[derive(Resource)]
+struct CommandReader<E: Event>(ManualEventReader<E>);
+
+//	commands are read between the input sets and the simulation, and anything the simulation
+//	sends later in the frame is skipped, since it sends the same again when the replay plays
+fn record<E: Recorded>(app: &mut App) {
+    app.insert_resource(CommandReader::<E>(default()))
+        .add_systems(Update, record_commands::<E>.in_set(ReplaySet::Record))
+        .add_systems(
+            Update,
+            skip_simulation_commands::<E>
+                .after(InGameSet::DespawnEntities)
+                .run_if(in_state(GameState::InGame)),
+        );
+}
+
+fn start_recording(
+    settings: Res<MatchSettings>,
+    map: Res<MapSource>,
+    players: Res<Players>,
+    rng: Res<MatchRng>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    recorder.frame = 0;
+    recorder.replay = Some(Replay {
+        seed: rng.seed,
+        map: map.clone(),
+        players: players.list.len(),
+        teams: settings.teams,
+        difficulty: settings.difficulty,
+        rules: settings.rules.clone(),
+        frame_nanos: Vec::new(),
+        commands: Vec::new(),
+    });
+}
+
+fn finish_recording(mut recorder: ResMut<ReplayRecorder>, mut ids: ResMut<ReplayIds>) {
+    recorder.finish();
+    ids.next = 0;
+}
+
+fn start_playback(player: Option<ResMut<ReplayPlayer>>, mut commands: Commands) {
+    let Some(mut player) = player else {
+        return;
+    };
+
+    player.frame = 0;
+    player.next = 0;
+    if let Some(&nanos) = player.replay.frame_nanos.first() {
+        commands.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_nanos(
+            nanos,
+        )));
+    }
+}
+
+//	new entities are numbered in an order that does not depend on how they were spawned
+fn assign_replay_ids(
+    unnamed: Query<(Entity, &Team, &Transform), Without<ReplayId>>,
+    mut ids: ResMut<ReplayIds>,
+    mut commands: Commands,
+) {
+    let mut unnamed: Vec<_> = unnamed.iter().collect();
+    unnamed.sort_by(|(_, a_team, a), (_, b_team, b)| {
+        a_team
+            .0
+             .0
+            .cmp(&b_team.0 .0)
+            .then(a.translation.x.total_cmp(&b.translation.x))
+            .then(a.translation.y.total_cmp(&b.translation.y))
+    });
+
+    for (entity, _, _) in unnamed {
+        commands.entity(entity).insert(ReplayId(ids.next));
+        ids.next += 1;
+    }
+}
+
+fn play_commands(
+    mut player: ResMut<ReplayPlayer>,
+    named: Query<(Entity, &ReplayId)>,
+    mut commands: Commands,
+) {
+    let frame = player.frame;
+    let due = player.replay.commands[player.next..]
+        .iter()
+        .take_while(|recorded| recorded.frame <= frame)
+        .count();
+
+    if due > 0 {
+        let entities: HashMap<ReplayId, Entity> =
+            named.iter().map(|(entity, id)| (*id, entity)).collect();
+
+        for recorded in player.replay.commands[player.next..player.next + due].iter() {
+            recorded.command.send(&entities, &mut commands);
+        }
+        player.next += due;
+    }
+
+    //	the frame time only takes effect on the next frame
+    player.frame += 1;
+    if let Some(&nanos) = player.replay.frame_nanos.get(player.frame) {
+        commands.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_nanos(
+            nanos,
+        )));
+    }
+}
+
+fn record_commands<E: Recorded>(
+    events: Res<Events<E>>,
+    mut reader: ResMut<CommandReader<E>>,
+    ids: Query<&ReplayId>,
+    mut recorder: ResMut<ReplayRecorder>,
+) {
+    let frame = recorder.frame;
+    let recorded: Vec<_> = reader
+        .0
+        .read(&events)
+        .filter_map(|event| event.to_command(&ids))
+        .map(|command| RecordedCommand { frame, command })
+        .collect();
+
+    if let Some(replay) = recorder.replay.as_mut() {
+        replay.commands.extend(recorded);
+    }
+}
+
+fn advance_frame(time: Res<Time>, mut recorder: ResMut<ReplayRecorder>) {
+    if let Some(replay) = recorder.replay.as_mut() {
+        replay.frame_nanos.push(time.delta().as_nanos() as u64);
+        recorder.frame += 1;
+    }
+}
+
+fn skip_simulation_commands<E: Recorded>(
+    events: Res<Events<E>>,
+    mut reader: ResMut<CommandReader<E>>,
+) {
+    reader.0.clear(&events);
+}
+
+fn replay_id(ids: &Query<&ReplayId>, entity: Entity) -> Option<ReplayId> {
+    ids.get(entity).ok().copied()
+}
+
+fn replay_ids(ids: &Query<&ReplayId>, entities: &[Entity]) -> Vec<ReplayId> {
+    entities
+        .iter()
+        .filter_map(|entity| replay_id(ids, *entity))
+        .collect()
+}
+
+impl Recorded for BoxSelection {
+    fn to_command(&self, _ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::BoxSelection {
+            rect: self.rect,
+            team: self.team,
+        })
+    }
+}
+
+impl Recorded for SetUnitPosition {
+    fn to_command(&self, _ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::SetUnitPosition {
+            position: self.position,
+            direction: self.direction,
+            formation: self.formation.clone(),
+            team: self.team,
+            queued: self.queued,
+            attack_move: self.attack_move,
+        })
+    }
+}
+
+impl Recorded for PlaceConstructionSite {
+    fn to_command(&self, _ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::PlaceConstructionSite {
+            structure_type: self.structure_type.clone(),
+            team: self.team,
+            position: self.position,
+            effort: self.effort,
+            cost: self.cost,
+        })
+    }
+}
+
+impl Recorded for AttemptProductionIncrease {
+    fn to_command(&self, _ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::AttemptProductionIncrease {
+            production_type: self.production_type.clone(),
+            team: self.team,
+        })
+    }
+}
+
+impl Recorded for AssignConstructionWorkers {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::AssignConstructionWorkers {
+            site: replay_id(ids, self.site)?,
+            units: replay_ids(ids, &self.units),
+        })
+    }
+}
+
+impl Recorded for AssignGeneratorWorkers {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::AssignGeneratorWorkers {
+            generator: replay_id(ids, self.generator)?,
+            workers: replay_ids(ids, &self.workers),
+        })
+    }
+}
+
+impl Recorded for AssignAttackPursuit {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::AssignAttackPursuit {
+            predators: replay_ids(ids, &self.predators),
+            prey: replay_id(ids, self.prey)?,
+        })
+    }
+}
+
+impl Recorded for BreakAttackPursuit {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::BreakAttackPursuit {
+            entities: replay_ids(ids, &self.entities),
+        })
+    }
+}
+
+impl Recorded for AssignConvertPursuit {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::AssignConvertPursuit {
+            predators: replay_ids(ids, &self.predators),
+            prey: replay_id(ids, self.prey)?,
+        })
+    }
+}
+
+impl Recorded for BreakConvertPursuit {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::BreakConvertPursuit {
+            entities: replay_ids(ids, &self.entities),
+        })
+    }
+}
+
+impl Recorded for QueueOrder {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::QueueOrder {
+            units: replay_ids(ids, &self.units),
+            kind: self.kind,
+            target: replay_id(ids, self.target)?,
+        })
+    }
+}
+
+impl Recorded for ClearOrders {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::ClearOrders {
+            units: replay_ids(ids, &self.units),
+        })
+    }
+}
+
+impl Recorded for StopUnits {
+    fn to_command(&self, _ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::StopUnits { team: self.team })
+    }
+}
+
+impl Recorded for HoldPosition {
+    fn to_command(&self, _ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::HoldPosition { team: self.team })
+    }
+}
+
+impl Recorded for PatrolUnits {
+    fn to_command(&self, _ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        Some(ReplayCommand::PatrolUnits {
+            team: self.team,
+            points: self.points.clone(),
+        })
+    }
+}
+
+impl Recorded for SelectionOrder {
+    fn to_command(&self, ids: &Query<&ReplayId>) -> Option<ReplayCommand> {
+        let id = |entity: Option<Entity>| entity.and_then(|entity| replay_id(ids, entity));
+
+        Some(ReplayCommand::SelectionOrder {
+            team: self.team,
+            queued: self.queued,
+            site: id(self.site),
+            generator: id(self.generator),
+            prey: id(self.prey),
+            convertee: id(self.convertee),
+        })
+    }
+}
+
+impl ReplayCommand {
+    /// sends the command as the event it was recorded from
+    pub fn send(&self, entities: &HashMap<ReplayId, Entity>, commands: &mut Commands) {
+        let entity = |id: &ReplayId| entities.get(id).copied();
+        let all = |ids: &[ReplayId]| ids.iter().filter_map(entity).collect::<Vec<_>>();
+
+        match self.clone() {
+            ReplayCommand::BoxSelection { rect, team } => {
+                send(commands, BoxSelection { rect, team });
+            }
+            ReplayCommand::SetUnitPosition {
+                position,
+                direction,
+                formation,
+                team,
+                queued,
+                attack_move,
+            } => {
+                send(
+                    commands,
+                    SetUnitPosition {
+                        position,
+                        direction,
+                        formation,
+                        team,
+                        queued,
+                        attack_move,
+                    },
+                );
+            }
+            ReplayCommand::PlaceConstructionSite {
+                structure_type,
+                team,
+                position,
+                effort,
+                cost,
+            } => {
+                send(
+                    commands,
+                    PlaceConstructionSite {
+                        structure_type,
+                        team,
+                        position,
+                        effort,
+                        cost,
+                    },
+                );
+            }
+            ReplayCommand::AttemptProductionIncrease {
+                production_type,
+                team,
+            } => {
+                send(
+                    commands,
+                    AttemptProductionIncrease {
+                        production_type,
+                        team,
+                    },
+                );
+            }
+            ReplayCommand::AssignConstructionWorkers { site, units } => {
+                if let Some(site) = entity(&site) {
+                    send(
+                        commands,
+                        AssignConstructionWorkers {
+                            site,
+                            units: all(&units),
+                        },
+                    );
+                }
+            }
+            ReplayCommand::AssignGeneratorWorkers { generator, workers } => {
+                if let Some(generator) = entity(&generator) {
+                    send(
+                        commands,
+                        AssignGeneratorWorkers {
+                            generator,
+                            workers: all(&workers),
+                        },
+                    );
+                }
+            }
+            ReplayCommand::AssignAttackPursuit { predators, prey } => {
+                if let Some(prey) = entity(&prey) {
+                    send(
+                        commands,
+                        AssignAttackPursuit {
+                            predators: all(&predators),
+                            prey,
+                        },
+                    );
+                }
+            }
+            ReplayCommand::BreakAttackPursuit { entities } => {
+                send(
+                    commands,
+                    BreakAttackPursuit {
+                        entities: all(&entities),
+                    },
+                );
+            }
+            ReplayCommand::AssignConvertPursuit { predators, prey } => {
+                if let Some(prey) = entity(&prey) {
+                    send(
+                        commands,
+                        AssignConvertPursuit {
+                            predators: all(&predators),
+                            prey,
+                        },
+                    );
+                }
+            }
+            ReplayCommand::BreakConvertPursuit { entities } => {
+                send(
+                    commands,
+                    BreakConvertPursuit {
+                        entities: all(&entities),
+                    },
+                );
+            }
+            ReplayCommand::QueueOrder {
+                units,
+                kind,
+                target,
+            } => {
+                if let Some(target) = entity(&target) {
+                    send(
+                        commands,
+                        QueueOrder {
+                            units: all(&units),
+                            kind,
+                            target,
+                        },
+                    );
+                }
+            }
+            ReplayCommand::ClearOrders { units } => {
+                send(commands, ClearOrders { units: all(&units) });
+            }
+            ReplayCommand::StopUnits { team } => {
+                send(commands, StopUnits { team });
+            }
+            ReplayCommand::HoldPosition { team } => {
+                send(commands, HoldPosition { team });
+            }
+            ReplayCommand::PatrolUnits { team, points } => {
+                send(commands, PatrolUnits { team, points });
+            }
+            ReplayCommand::SelectionOrder {
+                team,
+                queued,
+                site,
+                generator,
+                prey,
+                convertee,
+            } => {
+                let target = |id: Option<ReplayId>| id.and_then(|id| entity(&id));
+
+                send(
+                    commands,
+                    SelectionOrder {
+                        team,
+                        queued,
+                        site: target(site),
+                        generator: target(generator),
+                        prey: target(prey),
+                        convertee: target(convertee),
+                    },
+                );
+            }
+        }
+    }
+}
+
+//	sent through commands so the replayed events land before the recorder reads them
+fn send<E: Event>(commands: &mut Commands, event: E) {
+    commands.add(move |world: &mut World| {
+        world.send_event(event);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{hierarchy::HierarchyPlugin, state::app::StatesPlugin, transform::TransformPlugin};
+
+    use super::*;
+    use crate::{
+        combat::Health,
+        currency::Energy,
+        headless::{HeadlessClock, HeadlessPlugin, HeadlessSettings},
+        GameplayPlugins,
+    };
+
+    const RECORDED_FRAMES: u64 = 600;
+    //	loading the archetypes and map takes a few frames before the match begins
+    const MAX_UPDATES: usize = 5000;
+
+    /// every named entity with where it stands and its health, then the energy of every team
+    type Snapshot = (Vec<(ReplayId, usize, [u32; 3])>, Vec<u32>);
+
+    fn headless_app(settings: HeadlessSettings) -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            StatesPlugin,
+            bevy::input::InputPlugin,
+            AssetPlugin {
+                watch_for_changes_override: Some(false),
+                ..default()
+            },
+        ))
+        .add_plugins(GameplayPlugins)
+        .add_plugins(HeadlessPlugin { settings });
+        app
+    }
+
+    fn play(app: &mut App) -> Snapshot {
+        for _ in 0..MAX_UPDATES {
+            app.update();
+            if app.world().resource::<HeadlessClock>().frames >= RECORDED_FRAMES {
+                break;
+            }
+        }
+        assert_eq!(
+            app.world().resource::<HeadlessClock>().frames,
+            RECORDED_FRAMES,
+            "the match never got going"
+        );
+
+        let world = app.world_mut();
+        let mut named: Vec<_> = world
+            .query::<(&ReplayId, &Team, &Transform, Option<&Health>)>()
+            .iter(world)
+            .map(|(id, team, transform, health)| {
+                (
+                    *id,
+                    team.0 .0,
+                    [
+                        transform.translation.x.to_bits(),
+                        transform.translation.y.to_bits(),
+                        health.map_or(0, |health| health.0.to_bits()),
+                    ],
+                )
+            })
+            .collect();
+        named.sort_by_key(|(id, _, _)| id.0);
+
+        let energy = world.resource::<Energy>();
+        let players = world.resource::<Players>();
+        let energy = players
+            .list
+            .iter()
+            .map(|player| energy.get(&player.team).to_bits())
+            .collect();
+
+        (named, energy)
+    }
+
+    #[test]
+    fn a_recorded_match_replays_the_same() {
+        let path = std::env::temp_dir()
+            .join("animancer_replay_test.replay.ron")
+            .to_string_lossy()
+            .into_owned();
+
+        let mut recording = headless_app(HeadlessSettings {
+            seed: 11,
+            record: Some(path.clone()),
+            ..default()
+        });
+        let recorded = play(&mut recording);
+        recording
+            .world_mut()
+            .resource_mut::<ReplayRecorder>()
+            .finish();
+
+        let replay = Replay::load(&path).expect("replay was saved");
+        assert!(!replay.commands.is_empty(), "the ai gave no commands");
+
+        let mut replaying = headless_app(HeadlessSettings {
+            replay: Some(replay),
+            ..default()
+        });
+        assert_eq!(play(&mut replaying), recorded);
+    }
+}
@@ -0,0 +1,1023 @@
+use std::{fs, path::Path as FilePath};
+
+use bevy::{core::FrameCount, ecs::system::SystemParam, prelude::*, utils::HashMap};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    ai::{AIInstructionSets, Dependancy, DependancyExpectation, Idle},
+    archetypes::{StructureRegistry, UnitRegistry},
+    combat::{AttackPursuit, Health},
+    construction::{spawn_construction_site, ConstructionSite},
+    conversion::{ConvertPursuit, Faith},
+    currency::Energy,
+    diplomacy::Diplomacy,
+    generator::Generator,
+    map::{Map, MapLoaded, MapSource, ResourceNode},
+    match_setup::{MatchEntity, MatchRng, MatchSettings, StartMatch},
+    movement::{Moveable, Moving},
+    nav_agent::Path,
+    orders::{Holding, Order, OrderKind, Orders, Patrol},
+    producer::{Producer, Production, ProductionType},
+    schedule::InGameSet,
+    selectable::{SelectedStructures, SelectedUnits},
+    state::GameState,
+    structure::{spawn_structure_archetype, StructureKind, StructureType},
+    teams::{Players, Team, TeamType},
+    unit::{spawn_archetype, Hero, Unit, UnitKind},
+    victory::MatchProgress,
+};
+
+pub const QUICK_SAVE_PATH: &str = "saves/quick.save.ron";
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                quick_save_keys.run_if(resource_exists::<ButtonInput<KeyCode>>),
+                load_match.run_if(on_event::<LoadMatch>()),
+                note_map_loaded.run_if(on_event::<MapLoaded>()),
+            ),
+        )
+        .add_systems(
+            Update,
+            restore_match
+                .run_if(resource_exists::<PendingLoad>)
+                .in_set(InGameSet::SpawnEntities),
+        )
+        //	saved at the end of the frame, once everything has settled
+        .add_systems(
+            Update,
+            save_match
+                .run_if(on_event::<SaveMatch>())
+                .after(InGameSet::DespawnEntities)
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_event::<SaveMatch>()
+        .add_event::<LoadMatch>();
+    }
+}
+
+/// Writes the running match to a save file
+#[derive(Event)]
+pub struct SaveMatch {
+    pub path: String,
+}
+
+/// Replaces whatever is running with the match in a save file
+#[derive(Event)]
+pub struct LoadMatch {
+    pub path: String,
+}
+
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("could not access save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse save file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write save: {0}")]
+    Write(#[from] ron::Error),
+}
+
+/// An entity reference inside a save file, the position of the entity in the save
+type SaveId = usize;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedPath {
+    pub current: Vec2,
+    pub next: Vec<Vec2>,
+    pub target: Vec2,
+    #[serde(default)]
+    pub corridor: Option<Vec2>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedPursuit {
+    pub cooldown: f32,
+    pub prey: SaveId,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum SavedOrder {
+    Move {
+        location: Vec3,
+        corridor: Option<Vec2>,
+        #[serde(default)]
+        attack_move: bool,
+    },
+    Target {
+        kind: OrderKind,
+        target: SaveId,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedUnit {
+    pub id: SaveId,
+    pub kind: ProductionType,
+    pub team: TeamType,
+    pub position: Vec2,
+    pub hero: bool,
+    pub health: f32,
+    pub faith: f32,
+    pub persuader: Option<TeamType>,
+    pub location: Vec3,
+    #[serde(default)]
+    pub attack_move: bool,
+    pub moving: bool,
+    pub idle: bool,
+    pub path: Option<SavedPath>,
+    pub attacking: Option<SavedPursuit>,
+    pub converting: Option<SavedPursuit>,
+    #[serde(default)]
+    pub current_order: Option<SavedOrder>,
+    #[serde(default)]
+    pub queued_orders: Vec<SavedOrder>,
+    #[serde(default)]
+    pub holding: bool,
+    #[serde(default)]
+    pub patrol: Option<Patrol>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedProducer {
+    pub queue: Vec<ProductionType>,
+    pub value: f32,
+    pub rate: f32,
+    pub post_spawn_location: Vec3,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedGenerator {
+    pub is_running: bool,
+    pub added_rate: f32,
+    pub assigned_workers: Vec<SaveId>,
+    pub working_workers: Vec<SaveId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedStructure {
+    pub id: SaveId,
+    pub kind: StructureType,
+    pub team: TeamType,
+    pub position: Vec2,
+    pub health: f32,
+    pub idle: bool,
+    pub producer: Option<SavedProducer>,
+    pub generator: Option<SavedGenerator>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedSite {
+    pub id: SaveId,
+    pub kind: StructureType,
+    pub team: TeamType,
+    pub position: Vec2,
+    pub effort: f32,
+    pub assigned_units: Vec<SaveId>,
+    pub working_units: Vec<SaveId>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedInstructionSet {
+    pub current_step: usize,
+    pub complete: bool,
+    pub dependants: Vec<(SaveId, DependancyExpectation)>,
+}
+
+/// How far an AI has worked through its instructions, which are rebuilt from the map on load
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedAI {
+    pub team: TeamType,
+    pub current_phase: usize,
+    pub cooldown: f32,
+    pub force_forward: f32,
+    pub sets: Vec<SavedInstructionSet>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedCamera {
+    pub position: Vec2,
+    pub scale: f32,
+}
+
+/// A match frozen part way through, with entities referring to each other by `SaveId`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SaveFile {
+    /// the settings the match started with, carrying its whole map
+    pub settings: MatchSettings,
+    pub energy: Vec<(TeamType, f32)>,
+    /// what is left in each resource node, by position
+    pub resources: Vec<(Vec2, f32)>,
+    pub units: Vec<SavedUnit>,
+    pub structures: Vec<SavedStructure>,
+    pub sites: Vec<SavedSite>,
+    pub ais: Vec<SavedAI>,
+    pub selected_units: Vec<(TeamType, Vec<SaveId>)>,
+    pub selected_structures: Vec<(TeamType, Vec<SaveId>)>,
+    pub camera: Option<SavedCamera>,
+    /// relations as they stood when saved, which may have changed since the match began
+    #[serde(default)]
+    pub diplomacy: Option<Diplomacy>,
+    #[serde(default)]
+    pub progress: MatchProgress,
+}
+
+impl SaveFile {
+    pub fn load(path: &str) -> Result<Self, SaveError> {
+        Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), SaveError> {
+        if let Some(parent) = FilePath::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+}
+
+/// A save waiting for its match to be set up, so it can be restored over the top
+#[derive(Resource)]
+struct PendingLoad {
+    save: SaveFile,
+    map_loaded_at: Option<u32>,
+}
+
+fn quick_save_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut save: EventWriter<SaveMatch>,
+    mut load: EventWriter<LoadMatch>,
+) {
+    if keys.just_pressed(KeyCode::F5) && *state.get() == GameState::InGame {
+        save.send(SaveMatch {
+            path: QUICK_SAVE_PATH.into(),
+        });
+    }
+    if keys.just_pressed(KeyCode::F9) && *state.get() == GameState::InGame {
+        load.send(LoadMatch {
+            path: QUICK_SAVE_PATH.into(),
+        });
+    }
+}
+
+type SavedUnitData = (
+    Entity,
+    &'static UnitKind,
+    &'static Team,
+    &'static Transform,
+    &'static Health,
+    &'static Faith,
+    &'static Moveable,
+    &'static Moving,
+    &'static Idle,
+    Has<Hero>,
+    Option<&'static Path>,
+    Option<&'static AttackPursuit>,
+    Option<&'static ConvertPursuit>,
+    (
+        Option<&'static Orders>,
+        Has<Holding>,
+        Option<&'static Patrol>,
+    ),
+);
+
+type SavedStructureData = (
+    Entity,
+    &'static StructureKind,
+    &'static Team,
+    &'static Transform,
+    &'static Health,
+    &'static Idle,
+    Option<&'static Producer>,
+    Option<&'static Generator>,
+);
+
+/// The match-wide state that goes into a save
+#[derive(SystemParam)]
+struct MatchStanding<'w> {
+    settings: Res<'w, MatchSettings>,
+    rng: Res<'w, MatchRng>,
+    map: Res<'w, Map>,
+    players: Res<'w, Players>,
+    energy: Res<'w, Energy>,
+    selected_units: Res<'w, SelectedUnits>,
+    selected_structures: Res<'w, SelectedStructures>,
+    diplomacy: Res<'w, Diplomacy>,
+    progress: Res<'w, MatchProgress>,
+}
+
+/// Every entity that goes into a save
+#[derive(SystemParam)]
+struct MatchEntities<'w, 's> {
+    units: Query<'w, 's, SavedUnitData>,
+    structures: Query<'w, 's, SavedStructureData>,
+    sites: Query<'w, 's, (Entity, &'static ConstructionSite, &'static Transform)>,
+    nodes: Query<'w, 's, (&'static Transform, &'static ResourceNode)>,
+    transforms: Query<'w, 's, &'static Transform>,
+    ais: Query<'w, 's, &'static AIInstructionSets>,
+    camera: Query<'w, 's, (&'static Transform, &'static OrthographicProjection), With<Camera2d>>,
+}
+
+fn save_match(
+    mut requests: EventReader<SaveMatch>,
+    standing: MatchStanding,
+    entities: MatchEntities,
+) {
+    let MatchStanding {
+        settings,
+        rng,
+        map,
+        players,
+        energy,
+        selected_units,
+        selected_structures,
+        diplomacy,
+        progress,
+    } = standing;
+    let MatchEntities {
+        units,
+        structures,
+        sites,
+        nodes,
+        transforms,
+        ais,
+        camera,
+    } = entities;
+
+    //	every entity that can be referred to is numbered first, so references can be written in any order
+    let ids: HashMap<Entity, SaveId> = units
+        .iter()
+        .map(|unit| unit.0)
+        .chain(structures.iter().map(|structure| structure.0))
+        .chain(sites.iter().map(|site| site.0))
+        .enumerate()
+        .map(|(id, entity)| (entity, id))
+        .collect();
+    let id = |entity: &Entity| ids.get(entity).copied();
+    let all = |entities: &[Entity]| entities.iter().filter_map(id).collect::<Vec<_>>();
+    let pursuit =
+        |cooldown: f32, prey: &Entity| id(prey).map(|prey| SavedPursuit { cooldown, prey });
+    let order = |order: &Order| match *order {
+        Order::Move {
+            location,
+            corridor,
+            attack_move,
+        } => Some(SavedOrder::Move {
+            location,
+            corridor,
+            attack_move,
+        }),
+        Order::Target { kind, target } => {
+            id(&target).map(|target| SavedOrder::Target { kind, target })
+        }
+    };
+
+    let save = SaveFile {
+        settings: MatchSettings {
+            map: MapSource::Saved(map.clone()),
+            players: players.list.len(),
+            seed: Some(rng.seed),
+            ..settings.clone()
+        },
+        energy: players
+            .list
+            .iter()
+            .map(|player| (player.team, energy.get(&player.team)))
+            .collect(),
+        resources: nodes
+            .iter()
+            .map(|(transform, node)| (transform.translation.xy(), node.remaining))
+            .collect(),
+        units: units
+            .iter()
+            .map(
+                |(
+                    entity,
+                    kind,
+                    team,
+                    transform,
+                    health,
+                    faith,
+                    moveable,
+                    moving,
+                    idle,
+                    hero,
+                    path,
+                    attacking,
+                    converting,
+                    (orders, holding, patrol),
+                )| SavedUnit {
+                    id: ids[&entity],
+                    kind: kind.0.clone(),
+                    team: team.0,
+                    position: transform.translation.xy(),
+                    hero,
+                    health: health.0,
+                    faith: faith.current,
+                    persuader: faith.persuader,
+                    location: moveable.location,
+                    attack_move: moveable.attack_move,
+                    moving: moving.0,
+                    idle: idle.0,
+                    path: path.and_then(|path| {
+                        Some(SavedPath {
+                            current: path.current,
+                            next: path.next.clone(),
+                            target: transforms.get(path.target).ok()?.translation.xy(),
+                            corridor: path.corridor,
+                        })
+                    }),
+                    attacking: attacking.and_then(|a| pursuit(a.cooldown, &a.prey)),
+                    converting: converting.and_then(|c| pursuit(c.cooldown, &c.prey)),
+                    current_order: orders
+                        .and_then(|orders| orders.current.as_ref())
+                        .and_then(order),
+                    queued_orders: orders
+                        .map(|orders| orders.queued.iter().filter_map(order).collect())
+                        .unwrap_or_default(),
+                    holding,
+                    patrol: patrol.cloned(),
+                },
+            )
+            .collect(),
+        structures: structures
+            .iter()
+            .map(
+                |(entity, kind, team, transform, health, idle, producer, generator)| {
+                    SavedStructure {
+                        id: ids[&entity],
+                        kind: kind.0.clone(),
+                        team: team.0,
+                        position: transform.translation.xy(),
+                        health: health.0,
+                        idle: idle.0,
+                        producer: producer.map(|producer| SavedProducer {
+                            queue: producer.queue.clone(),
+                            value: producer.value,
+                            rate: producer.rate,
+                            post_spawn_location: producer.post_spawn_location,
+                        }),
+                        generator: generator.map(|generator| SavedGenerator {
+                            is_running: generator.is_running,
+                            added_rate: generator.added_rate,
+                            assigned_workers: all(&generator.assigned_workers),
+                            working_workers: all(&generator.working_workers),
+                        }),
+                    }
+                },
+            )
+            .collect(),
+        sites: sites
+            .iter()
+            .map(|(entity, site, transform)| SavedSite {
+                id: ids[&entity],
+                kind: site.structure_type.clone(),
+                team: site.team,
+                position: transform.translation.xy(),
+                effort: site.effort,
+                assigned_units: all(&site.assigned_units),
+                working_units: all(&site.working_units),
+            })
+            .collect(),
+        ais: ais
+            .iter()
+            .map(|ai| SavedAI {
+                team: ai.team,
+                current_phase: ai.current_phase,
+                cooldown: ai.cooldown,
+                force_forward: ai.force_forward,
+                sets: ai
+                    .sets
+                    .iter()
+                    .map(|set| SavedInstructionSet {
+                        current_step: set.current_step,
+                        complete: set.complete,
+                        dependants: set
+                            .dependants
+                            .iter()
+                            .filter_map(|dependant| {
+                                Some((id(&dependant.entity)?, dependant.expectation))
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        selected_units: players
+            .list
+            .iter()
+            .map(|player| {
+                (
+                    player.team,
+                    all(&selected_units.entities.to_vec(&player.team)),
+                )
+            })
+            .collect(),
+        selected_structures: players
+            .list
+            .iter()
+            .map(|player| {
+                (
+                    player.team,
+                    all(&selected_structures.entities.to_vec(&player.team)),
+                )
+            })
+            .collect(),
+        camera: camera
+            .get_single()
+            .ok()
+            .map(|(transform, projection)| SavedCamera {
+                position: transform.translation.xy(),
+                scale: projection.scale,
+            }),
+        diplomacy: Some(diplomacy.clone()),
+        progress: progress.clone(),
+    };
+
+    for request in requests.read() {
+        match save.save(&request.path) {
+            Ok(_) => info!("match saved to {}", request.path),
+            Err(error) => error!("match not saved to {}: {}", request.path, error),
+        }
+    }
+}
+
+//	the save is restored over a freshly started match, so the map, navmesh and ai come from the usual setup
+fn load_match(
+    mut requests: EventReader<LoadMatch>,
+    mut settings: ResMut<MatchSettings>,
+    mut start_match: EventWriter<StartMatch>,
+    mut commands: Commands,
+) {
+    let Some(request) = requests.read().last() else {
+        return;
+    };
+
+    match SaveFile::load(&request.path) {
+        Ok(save) => {
+            *settings = save.settings.clone();
+            commands.insert_resource(PendingLoad {
+                save,
+                map_loaded_at: None,
+            });
+            start_match.send(StartMatch);
+        }
+        Err(error) => error!("could not load {}: {}", request.path, error),
+    }
+}
+
+fn note_map_loaded(pending: Option<ResMut<PendingLoad>>, frame: Res<FrameCount>) {
+    if let Some(mut pending) = pending {
+        pending.map_loaded_at = Some(frame.0);
+    }
+}
+
+/// What saved entities are spawned from
+#[derive(SystemParam)]
+struct SpawnSources<'w> {
+    asset_server: Res<'w, AssetServer>,
+    unit_registry: Res<'w, UnitRegistry>,
+    structure_registry: Res<'w, StructureRegistry>,
+    players: Res<'w, Players>,
+}
+
+/// The state of a freshly started match that a save is written over
+#[derive(SystemParam)]
+struct RestoredStanding<'w, 's> {
+    energy: ResMut<'w, Energy>,
+    selected_units: ResMut<'w, SelectedUnits>,
+    selected_structures: ResMut<'w, SelectedStructures>,
+    diplomacy: ResMut<'w, Diplomacy>,
+    progress: ResMut<'w, MatchProgress>,
+    ais: Query<'w, 's, &'static mut AIInstructionSets>,
+    nodes: Query<'w, 's, (&'static Transform, &'static mut ResourceNode), Without<Camera2d>>,
+    camera: Query<
+        'w,
+        's,
+        (&'static mut Transform, &'static mut OrthographicProjection),
+        With<Camera2d>,
+    >,
+}
+
+fn restore_match(
+    pending: Res<PendingLoad>,
+    frame: Res<FrameCount>,
+    sources: SpawnSources,
+    starting_units: Query<Entity, With<Unit>>,
+    standing: RestoredStanding,
+    mut commands: Commands,
+) {
+    let SpawnSources {
+        asset_server,
+        unit_registry,
+        structure_registry,
+        players,
+    } = sources;
+    let RestoredStanding {
+        mut energy,
+        mut selected_units,
+        mut selected_structures,
+        mut diplomacy,
+        mut progress,
+        mut ais,
+        mut nodes,
+        mut camera,
+    } = standing;
+
+    //	every other MapLoaded handler has had a frame to run and have its commands applied
+    let Some(loaded_at) = pending.map_loaded_at else {
+        return;
+    };
+    if frame.0.wrapping_sub(loaded_at) < 2 {
+        return;
+    }
+
+    let save = &pending.save;
+    commands.remove_resource::<PendingLoad>();
+
+    //	the starting heroes make way for the saved units
+    for entity in starting_units.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    //	entities are spawned before any references between them are filled in
+    let mut entities: HashMap<SaveId, Entity> = HashMap::default();
+    for unit in save.units.iter() {
+        let Some(archetype) = unit_registry.get(&unit.kind) else {
+            warn!("no archetype for {:?}", unit.kind);
+            continue;
+        };
+
+        let entity = spawn_archetype(
+            &mut commands,
+            &asset_server,
+            archetype,
+            unit.position.extend(0.0),
+            unit.location,
+            unit.team,
+            &players,
+        );
+        entities.insert(unit.id, entity);
+    }
+    for structure in save.structures.iter() {
+        let Some(archetype) = structure_registry.get(&structure.kind) else {
+            warn!("no archetype for {:?}", structure.kind);
+            continue;
+        };
+
+        let entity = spawn_structure_archetype(
+            &mut commands,
+            &asset_server,
+            archetype,
+            &unit_registry,
+            structure.position.extend(0.0),
+            structure.team,
+        );
+        entities.insert(structure.id, entity);
+    }
+
+    let entity = |id: &SaveId| entities.get(id).copied();
+    let all = |ids: &[SaveId]| ids.iter().filter_map(entity).collect::<Vec<_>>();
+
+    let mut site_entities: HashMap<SaveId, Entity> = HashMap::default();
+    for site in save.sites.iter() {
+        let Some(archetype) = structure_registry.get(&site.kind) else {
+            warn!("no archetype for {:?}", site.kind);
+            continue;
+        };
+
+        let spawned = spawn_construction_site(
+            &mut commands,
+            &asset_server,
+            archetype,
+            site.position,
+            ConstructionSite {
+                structure_type: site.kind.clone(),
+                team: site.team,
+                effort: site.effort,
+                assigned_units: all(&site.assigned_units),
+                working_units: all(&site.working_units),
+            },
+        );
+        site_entities.insert(site.id, spawned);
+    }
+
+    //	sites name the units working on them, so they can only be referred to once they exist
+    entities.extend(site_entities);
+    let entity = |id: &SaveId| entities.get(id).copied();
+    let all = |ids: &[SaveId]| ids.iter().filter_map(entity).collect::<Vec<_>>();
+    let order = |order: &SavedOrder| match *order {
+        SavedOrder::Move {
+            location,
+            corridor,
+            attack_move,
+        } => Some(Order::Move {
+            location,
+            corridor,
+            attack_move,
+        }),
+        SavedOrder::Target { kind, target } => {
+            entity(&target).map(|target| Order::Target { kind, target })
+        }
+    };
+
+    for unit in save.units.iter() {
+        let Some(spawned) = entity(&unit.id) else {
+            continue;
+        };
+        let Some(archetype) = unit_registry.get(&unit.kind) else {
+            continue;
+        };
+
+        let mut spawned = commands.entity(spawned);
+        spawned.insert((
+            Health(unit.health),
+            Faith {
+                base: archetype.faith,
+                current: unit.faith,
+                persuader: unit.persuader,
+            },
+            Moveable {
+                location: unit.location,
+                attack_move: unit.attack_move,
+            },
+            Moving(unit.moving),
+            Idle(unit.idle),
+            Orders {
+                current: unit.current_order.as_ref().and_then(order),
+                queued: unit.queued_orders.iter().filter_map(order).collect(),
+            },
+        ));
+
+        if unit.hero {
+            spawned.insert(Hero);
+        }
+        if unit.holding {
+            spawned.insert(Holding);
+        }
+        if let Some(patrol) = &unit.patrol {
+            spawned.insert(patrol.clone());
+        }
+        if let Some(attacking) = &unit.attacking {
+            if let Some(prey) = entity(&attacking.prey) {
+                spawned.insert(AttackPursuit {
+                    cooldown: attacking.cooldown,
+                    prey,
+                });
+            }
+        }
+        if let Some(converting) = &unit.converting {
+            if let Some(prey) = entity(&converting.prey) {
+                spawned.insert(ConvertPursuit {
+                    cooldown: converting.cooldown,
+                    prey,
+                });
+            }
+        }
+
+        let Some(path) = &unit.path else {
+            continue;
+        };
+        let id = spawned.id();
+        let target = commands
+            .spawn((
+                TransformBundle {
+                    local: Transform::from_translation(path.target.extend(1.5)),
+                    ..default()
+                },
+                MatchEntity,
+            ))
+            .id();
+        commands.entity(id).insert(Path {
+            current: path.current,
+            next: path.next.clone(),
+            target,
+            corridor: path.corridor,
+        });
+    }
+
+    for structure in save.structures.iter() {
+        let Some(spawned) = entity(&structure.id) else {
+            continue;
+        };
+
+        commands
+            .entity(spawned)
+            .insert((Health(structure.health), Idle(structure.idle)));
+
+        if let Some(generator) = &structure.generator {
+            commands.entity(spawned).insert(Generator {
+                is_running: generator.is_running,
+                added_rate: generator.added_rate,
+                assigned_workers: all(&generator.assigned_workers),
+                working_workers: all(&generator.working_workers),
+                ..default()
+            });
+        }
+        if let Some(producer) = &structure.producer {
+            let queue = producer.queue.clone();
+            commands.entity(spawned).insert(Producer {
+                current_production: queue.first().cloned().unwrap_or(ProductionType::None),
+                queue: queue.clone(),
+                value: producer.value,
+                rate: producer.rate,
+                post_spawn_location: producer.post_spawn_location,
+            });
+
+            //	the production children only exist once the spawn above has been applied
+            commands.add(move |world: &mut World| {
+                let mut productions = world.query::<(&Parent, &mut Production)>();
+                for (parent, mut production) in productions.iter_mut(world) {
+                    if parent.get() != spawned {
+                        continue;
+                    }
+                    production.queue = queue
+                        .iter()
+                        .filter(|queued| **queued == production.production_type)
+                        .count() as i32;
+                }
+            });
+        }
+    }
+
+    for (team, value) in save.energy.iter() {
+        energy.set(team, *value);
+    }
+
+    if let Some(saved) = &save.diplomacy {
+        *diplomacy = saved.clone();
+    }
+    *progress = save.progress.clone();
+
+    for (transform, mut node) in nodes.iter_mut() {
+        let position = transform.translation.xy();
+        if let Some((_, remaining)) = save
+            .resources
+            .iter()
+            .find(|(saved, _)| saved.distance(position) < 1.0)
+        {
+            node.remaining = *remaining;
+        }
+    }
+
+    for mut ai in ais.iter_mut() {
+        let Some(saved) = save.ais.iter().find(|saved| saved.team == ai.team) else {
+            continue;
+        };
+
+        ai.current_phase = saved.current_phase;
+        ai.cooldown = saved.cooldown;
+        ai.force_forward = saved.force_forward;
+        for (set, saved) in ai.sets.iter_mut().zip(saved.sets.iter()) {
+            set.current_step = saved.current_step;
+            set.complete = saved.complete;
+            set.dependants = saved
+                .dependants
+                .iter()
+                .filter_map(|(id, expectation)| {
+                    Some(Dependancy {
+                        entity: entity(id)?,
+                        expectation: *expectation,
+                    })
+                })
+                .collect();
+        }
+    }
+
+    for (team, ids) in save.selected_units.iter() {
+        for selected in all(ids) {
+            selected_units.entities.push(team, selected);
+        }
+    }
+    for (team, ids) in save.selected_structures.iter() {
+        for selected in all(ids) {
+            selected_structures.entities.push(team, selected);
+        }
+    }
+
+    if let (Some(saved), Ok((mut transform, mut projection))) =
+        (&save.camera, camera.get_single_mut())
+    {
+        transform.translation.x = saved.position.x;
+        transform.translation.y = saved.position.y;
+        projection.scale = saved.scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        diplomacy::Relation,
+        testing::{Scenario, TestApp},
+        warrior::Warrior,
+        worker::Worker,
+    };
+
+    #[test]
+    fn a_saved_match_restores_over_a_fresh_one() {
+        let mut test = TestApp::new(SavePlugin);
+        test.app
+            .add_event::<MapLoaded>()
+            .add_event::<StartMatch>()
+            .insert_resource(MatchSettings::default())
+            .insert_resource(MatchRng::seeded(1))
+            .insert_resource(Map::default())
+            .insert_resource(MatchProgress::default());
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Warrior, TeamType(0), Vec2::new(0.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(100.0, 0.0)),
+            ],
+            structures: vec![(
+                StructureType("Producer".into()),
+                TeamType(0),
+                Vec2::new(300.0, 300.0),
+            )],
+            energy: vec![(TeamType(0), 42.0), (TeamType(1), 7.0)],
+        });
+        let (warrior, worker) = (spawned.units[0], spawned.units[1]);
+
+        let world = test.app.world_mut();
+        world.get_mut::<Health>(worker).unwrap().0 = 10.0;
+        world
+            .get_mut::<Orders>(warrior)
+            .unwrap()
+            .queued
+            .push_back(Order::Target {
+                kind: OrderKind::Attack,
+                target: worker,
+            });
+        world
+            .resource_mut::<Diplomacy>()
+            .set(&TeamType(0), &TeamType(1), Relation::Neutral);
+
+        let path = std::env::temp_dir()
+            .join("animancer_round_trip.save.ron")
+            .to_string_lossy()
+            .into_owned();
+        test.send(SaveMatch { path: path.clone() });
+        test.step(1);
+        let save = SaveFile::load(&path).expect("save file loads");
+
+        //	a fresh match, where only a starting unit stands and nothing else has been decided
+        let world = test.app.world_mut();
+        let saved: Vec<Entity> = world
+            .query_filtered::<Entity, Or<(With<Unit>, With<StructureKind>)>>()
+            .iter(world)
+            .collect();
+        for entity in saved {
+            world.entity_mut(entity).despawn_recursive();
+        }
+        *world.resource_mut::<Energy>() = Energy::default();
+        *world.resource_mut::<Diplomacy>() = Diplomacy::free_for_all();
+        let fresh = test.spawn(&Scenario {
+            units: vec![(ProductionType::Hero, TeamType(0), Vec2::ZERO)],
+            ..default()
+        });
+
+        test.app.insert_resource(PendingLoad {
+            save,
+            map_loaded_at: Some(0),
+        });
+        test.step(3);
+
+        test.assert_despawned(fresh.units[0]);
+        test.assert_energy(TeamType(0), 42.0);
+        test.assert_energy(TeamType(1), 7.0);
+        assert_eq!(
+            test.app
+                .world()
+                .resource::<Diplomacy>()
+                .relation(&TeamType(0), &TeamType(1)),
+            Relation::Neutral
+        );
+        assert_eq!(test.find::<StructureKind>(TeamType(0)).len(), 1);
+
+        let warriors = test.find::<Warrior>(TeamType(0));
+        let workers = test.find::<Worker>(TeamType(1));
+        assert_eq!((warriors.len(), workers.len()), (1, 1));
+        let (warrior, worker) = (warriors[0], workers[0]);
+        assert_eq!(test.health(worker), 10.0);
+
+        //	the order points at the worker that was spawned in place of the saved one
+        let orders = test.app.world().get::<Orders>(warrior).unwrap();
+        assert!(matches!(
+            orders.queued.front(),
+            Some(Order::Target {
+                kind: OrderKind::Attack,
+                target,
+            }) if *target == worker
+        ));
+    }
+}
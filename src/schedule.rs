@@ -1,4 +1,7 @@
-use bevy::prelude::*;
+use bevy::{
+    ecs::schedule::{LogLevel, ScheduleBuildSettings},
+    prelude::*,
+};
 
 use crate::state::GameState;
 
@@ -13,6 +16,7 @@ impl Plugin for SchedulePlugin {
                 InGameSet::UIInput,
                 InGameSet::UserInput,
                 InGameSet::AIInput,
+                InGameSet::Replay,
                 InGameSet::SelectionState,
                 InGameSet::SpawnEntities,
                 InGameSet::EntityUpdates,
@@ -21,7 +25,15 @@ impl Plugin for SchedulePlugin {
             )
                 .chain()
                 .run_if(in_state(GameState::InGame)),
-        );
+        )
+        //	systems that touch the same data without an order between them are reported
+        //	when the schedule is built, as they can play out differently from one run to the next
+        .edit_schedule(Update, |schedule| {
+            schedule.set_build_settings(ScheduleBuildSettings {
+                ambiguity_detection: LogLevel::Warn,
+                ..default()
+            });
+        });
     }
 }
 
@@ -31,6 +43,8 @@ pub enum InGameSet {
     UIInput,
     UserInput,
     AIInput,
+    //	every command of the frame has been sent by now, so this is where they are recorded
+    Replay,
     SelectionState,
     SpawnEntities,
     EntityUpdates,
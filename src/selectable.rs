@@ -4,12 +4,17 @@ use crate::{
     combat::{AssignAttackPursuit, BreakAttackPursuit, Health},
     construction::{AssignConstructionWorkers, ConstructionSite},
     conversion::{AssignConvertPursuit, BreakConvertPursuit, Faith},
+    diplomacy::Diplomacy,
+    fog::FogOfWar,
     generator::{AssignGeneratorWorkers, Generator},
-    inputs::ProducerSelection,
+    inputs::{handle_click, ProducerSelection, UnitAim},
+    orders::{ClearOrders, OrderKind, QueueOrder},
     priest::Priest,
     producer::Producer,
+    schedule::InGameSet,
+    spatial::SpatialIndex,
     structure::Structure,
-    teams::{Team, TeamBasedValues, TeamType},
+    teams::{Players, Team, TeamBasedValues, TeamType},
     unit::{Unit, UnitAction},
     warrior::Warrior,
     worker::Worker,
@@ -23,13 +28,22 @@ impl Plugin for SelectablePlugin {
             (
                 (
                     select_entities,
-                    (set_selected_unit_type, set_selected_structure_type),
+                    (
+                        set_selected_unit_type,
+                        set_selected_structure_type,
+                        order_selection,
+                    ),
                 )
-                    .chain(),
-                unit_action_selection,
+                    .chain()
+                    .in_set(InGameSet::SelectionState),
+                //	what was clicked on is settled before replays record it
+                unit_action_selection
+                    .after(handle_click)
+                    .in_set(InGameSet::UserInput),
             ),
         )
         .add_event::<BoxSelection>()
+        .add_event::<SelectionOrder>()
         .add_event::<SelectionStateChanged>()
         .add_event::<UnitsSelected>()
         .add_event::<StructuresSelected>()
@@ -54,6 +68,19 @@ pub struct BoxSelection {
     pub team: TeamType,
 }
 
+/// What a right click landed on, to be done by whatever the team has selected once it is carried out.
+/// A box selection given just before only takes effect then, so the units are not picked any earlier.
+#[derive(Event)]
+pub struct SelectionOrder {
+    pub team: TeamType,
+    /// whether it is added to the selection's orders instead of replacing them
+    pub queued: bool,
+    pub site: Option<Entity>,
+    pub generator: Option<Entity>,
+    pub prey: Option<Entity>,
+    pub convertee: Option<Entity>,
+}
+
 #[derive(Resource)]
 pub struct SelectedUnits {
     pub entities: TeamBasedValues<Entity>,
@@ -112,8 +139,9 @@ struct StructuresSelected {
 
 fn select_entities(
     mut reader: EventReader<BoxSelection>,
-    mut query_units: Query<(Entity, &Team, &GlobalTransform, &Selectable), With<Unit>>,
-    mut query_structures: Query<(Entity, &Team, &GlobalTransform, &Selectable), With<Structure>>,
+    query_units: Query<(Entity, &Team, &GlobalTransform, &Selectable), With<Unit>>,
+    query_structures: Query<(Entity, &Team, &GlobalTransform, &Selectable), With<Structure>>,
+    spatial: Res<SpatialIndex>,
     mut selected_units: ResMut<SelectedUnits>,
     mut selected_structures: ResMut<SelectedStructures>,
     mut units_selected: EventWriter<UnitsSelected>,
@@ -130,7 +158,10 @@ fn select_entities(
         selected_units.entities.clear(&box_selection.team);
         selected_structures.entities.clear(&box_selection.team);
 
-        for (entity, team, global_transform, selectable) in query_units.iter_mut() {
+        for (entity, team, global_transform, selectable) in spatial
+            .touching(box_selection.rect)
+            .filter_map(|entity| query_units.get(entity).ok())
+        {
             // info!(
             //     "\nunit query ->\n\tentity: {:?}\n\tteam: {:?}\n\tglobal_transform: {:?}\n\tselectable: {:?}",
             //     entity, team, global_transform, selectable
@@ -161,12 +192,15 @@ fn select_entities(
         //  Always prioritize units and never select units AND structures
         if selected_units.entities.len(&box_selection.team) > 0 {
             units_selected.send(UnitsSelected {
-                team: box_selection.team.clone(),
+                team: box_selection.team,
             });
-            return;
+            continue;
         }
 
-        for (entity, team, global_transform, selectable) in query_structures.iter_mut() {
+        for (entity, team, global_transform, selectable) in spatial
+            .touching(box_selection.rect)
+            .filter_map(|entity| query_structures.get(entity).ok())
+        {
             // info!(
             //     "\nstructure query ->\n\tentity: {:?}\n\tteam: {:?}\n\tglobal_transform: {:?}\n\tselectable: {:?}",
             //     entity, team, global_transform, selectable
@@ -198,12 +232,12 @@ fn select_entities(
         // );
         if selected_structures.entities.len(&box_selection.team) > 0 {
             structures_selected.send(StructuresSelected {
-                team: box_selection.team.clone(),
+                team: box_selection.team,
             });
         } else {
             selection_state_changed.send(SelectionStateChanged {
                 new_type: SelectionType::None,
-                team: box_selection.team.clone(),
+                team: box_selection.team,
             });
 
             producer_selection.is_selected = false;
@@ -258,12 +292,12 @@ fn set_selected_unit_type(
         if mismatched_types {
             selection_state_changed.send(SelectionStateChanged {
                 new_type: SelectionType::Unit,
-                team: units_selected.team.clone(),
+                team: units_selected.team,
             });
         } else {
             selection_state_changed.send(SelectionStateChanged {
                 new_type: selected_type,
-                team: units_selected.team.clone(),
+                team: units_selected.team,
             });
         }
     }
@@ -276,6 +310,7 @@ fn set_selected_structure_type(
     producer_query: Query<Entity, With<Producer>>,
     mut selection_state_changed: EventWriter<SelectionStateChanged>,
     mut producer_selection: ResMut<ProducerSelection>,
+    players: Res<Players>,
 ) {
     for structures_selected in selection_event.read() {
         let mut selected_type = SelectionType::None;
@@ -305,15 +340,15 @@ fn set_selected_structure_type(
         if mismatched_types {
             selection_state_changed.send(SelectionStateChanged {
                 new_type: SelectionType::None,
-                team: structures_selected.team.clone(),
+                team: structures_selected.team,
             });
         } else {
             selection_state_changed.send(SelectionStateChanged {
                 new_type: selected_type.clone(),
-                team: structures_selected.team.clone(),
+                team: structures_selected.team,
             });
 
-            if structures_selected.team == TeamType::Human {
+            if players.is_local(&structures_selected.team) {
                 producer_selection.is_selected = selected_type == SelectionType::Producer;
             }
         }
@@ -323,88 +358,186 @@ fn set_selected_structure_type(
 fn unit_action_selection(
     mut unit_action: EventReader<UnitAction>,
     sites: Query<(Entity, &Team, &Transform, &Selectable), With<ConstructionSite>>,
-    mut assign_construction_worker: EventWriter<AssignConstructionWorkers>,
     generators: Query<(Entity, &Team, &Transform, &Selectable), With<Generator>>,
-    mut assign_generator_workers: EventWriter<AssignGeneratorWorkers>,
     attackables: Query<(Entity, &Team, &Transform, &Selectable), With<Health>>,
-    mut assign_attack_pursuit: EventWriter<AssignAttackPursuit>,
-    mut break_attack_pursuit: EventWriter<BreakAttackPursuit>,
     convertables: Query<(Entity, &Team, &Transform, &Selectable), With<Faith>>,
-    mut assign_convert_pursuit: EventWriter<AssignConvertPursuit>,
-    mut break_convert_pursuit: EventWriter<BreakConvertPursuit>,
-    selected_units: Res<SelectedUnits>,
+    mut selection_order: EventWriter<SelectionOrder>,
+    mut unit_aim: ResMut<UnitAim>,
+    players: Res<Players>,
+    diplomacy: Res<Diplomacy>,
+    fog: Res<FogOfWar>,
 ) {
     for action in unit_action.read() {
-        for (entity, team, transform, selectable) in sites.iter() {
-            //  ensure only the players buildings are selected
-            if team.0 != TeamType::Human {
-                continue;
-            }
+        let under_click = |transform: &Transform, selectable: &Selectable| {
+            let position = vec2(transform.translation.x, transform.translation.y);
+            Rect::from_center_size(position, selectable.size).contains(action.position)
+        };
+
+        //  ensure only the players buildings are picked
+        let site = sites
+            .iter()
+            .find(|(_, team, transform, selectable)| {
+                players.is_local(&team.0) && under_click(transform, selectable)
+            })
+            .map(|(entity, ..)| entity);
+        let generator = generators
+            .iter()
+            .find(|(_, team, transform, selectable)| {
+                players.is_local(&team.0) && under_click(transform, selectable)
+            })
+            .map(|(entity, ..)| entity);
+
+        //  only enemies that can be seen are picked out by a click
+        let hostile = |team: &Team, transform: &Transform, selectable: &Selectable| {
+            diplomacy.is_hostile(&players.local, &team.0)
+                && fog.is_visible(&players.local, transform.translation.xy())
+                && under_click(transform, selectable)
+        };
+        let prey = attackables
+            .iter()
+            .find(|(_, team, transform, selectable)| hostile(team, transform, selectable))
+            .map(|(entity, ..)| entity);
+        let convertee = convertables
+            .iter()
+            .find(|(_, team, transform, selectable)| hostile(team, transform, selectable))
+            .map(|(entity, ..)| entity);
+
+        //	a queued order on something takes the selection there itself
+        let targeted = site.or(generator).or(prey).or(convertee).is_some();
+        if action.queued && targeted {
+            unit_aim.aiming = false;
+        }
 
-            let structure_pos = vec2(transform.translation.x, transform.translation.y);
-            let structure_rect = Rect::from_center_size(structure_pos, selectable.size);
-            if structure_rect.contains(action.position) {
+        selection_order.send(SelectionOrder {
+            team: players.local,
+            queued: action.queued,
+            site,
+            generator,
+            prey,
+            convertee,
+        });
+    }
+}
+
+fn order_selection(
+    mut selection_orders: EventReader<SelectionOrder>,
+    selected_units: Res<SelectedUnits>,
+    roles: Query<(Has<Warrior>, Has<Priest>)>,
+    work: (
+        EventWriter<AssignConstructionWorkers>,
+        EventWriter<AssignGeneratorWorkers>,
+    ),
+    attack: (
+        EventWriter<AssignAttackPursuit>,
+        EventWriter<BreakAttackPursuit>,
+    ),
+    convert: (
+        EventWriter<AssignConvertPursuit>,
+        EventWriter<BreakConvertPursuit>,
+    ),
+    orders: (EventWriter<QueueOrder>, EventWriter<ClearOrders>),
+) {
+    let (mut assign_construction_worker, mut assign_generator_workers) = work;
+    let (mut assign_attack_pursuit, mut break_attack_pursuit) = attack;
+    let (mut assign_convert_pursuit, mut break_convert_pursuit) = convert;
+    let (mut queue_order, mut clear_orders) = orders;
+
+    for order in selection_orders.read() {
+        let selected = selected_units.entities.to_vec(&order.team);
+
+        //	an order given outright replaces whatever was queued before it
+        if !order.queued {
+            clear_orders.send(ClearOrders {
+                units: selected.clone(),
+            });
+        }
+
+        match order.site {
+            Some(site) if order.queued => {
+                queue_order.send(QueueOrder {
+                    units: selected.clone(),
+                    kind: OrderKind::Build,
+                    target: site,
+                });
+            }
+            Some(site) => {
                 assign_construction_worker.send(AssignConstructionWorkers {
-                    site: entity,
-                    units: selected_units.entities.human.clone(),
+                    site,
+                    units: selected.clone(),
                 });
             }
+            None => (),
         }
 
-        for (entity, team, transform, selectable) in generators.iter() {
-            //  ensure only the players buildings are selected
-            if team.0 != TeamType::Human {
-                continue;
+        match order.generator {
+            Some(generator) if order.queued => {
+                queue_order.send(QueueOrder {
+                    units: selected.clone(),
+                    kind: OrderKind::Work,
+                    target: generator,
+                });
             }
-
-            let structure_pos = vec2(transform.translation.x, transform.translation.y);
-            let structure_rect = Rect::from_center_size(structure_pos, selectable.size);
-            if structure_rect.contains(action.position) {
+            Some(generator) => {
                 assign_generator_workers.send(AssignGeneratorWorkers {
-                    generator: entity,
-                    workers: selected_units.entities.human.clone(),
+                    generator,
+                    workers: selected.clone(),
                 });
             }
+            None => (),
         }
 
-        for (entity, team, transform, selectable) in attackables.iter() {
-            //  ensure only enemies are selected
-            if team.0 == TeamType::Human {
-                continue;
+        //  warriors go after whatever was clicked on and priests after whoever, the rest just move
+        let (warriors, priests): (Vec<Entity>, Vec<Entity>) = (
+            selected
+                .iter()
+                .copied()
+                .filter(|&entity| roles.get(entity).is_ok_and(|(warrior, _)| warrior))
+                .collect(),
+            selected
+                .iter()
+                .copied()
+                .filter(|&entity| roles.get(entity).is_ok_and(|(_, priest)| priest))
+                .collect(),
+        );
+
+        match order.prey {
+            Some(prey) if order.queued => {
+                queue_order.send(QueueOrder {
+                    units: warriors,
+                    kind: OrderKind::Attack,
+                    target: prey,
+                });
             }
-
-            let unit_pos = vec2(transform.translation.x, transform.translation.y);
-            let unit_rect = Rect::from_center_size(unit_pos, selectable.size);
-            if unit_rect.contains(action.position) {
+            Some(prey) => {
                 assign_attack_pursuit.send(AssignAttackPursuit {
-                    predators: selected_units.entities.human.clone(),
-                    prey: entity,
-                });
-            } else {
-                break_attack_pursuit.send(BreakAttackPursuit {
-                    entities: selected_units.entities.human.clone(),
+                    predators: warriors,
+                    prey,
                 });
             }
+            None if !order.queued => {
+                break_attack_pursuit.send(BreakAttackPursuit { entities: warriors });
+            }
+            None => (),
         }
 
-        for (entity, team, transform, selectable) in convertables.iter() {
-            //  ensure only enemies are selected
-            if team.0 == TeamType::Human {
-                continue;
+        match order.convertee {
+            Some(prey) if order.queued => {
+                queue_order.send(QueueOrder {
+                    units: priests,
+                    kind: OrderKind::Convert,
+                    target: prey,
+                });
             }
-
-            let unit_pos = vec2(transform.translation.x, transform.translation.y);
-            let unit_rect = Rect::from_center_size(unit_pos, selectable.size);
-            if unit_rect.contains(action.position) {
+            Some(prey) => {
                 assign_convert_pursuit.send(AssignConvertPursuit {
-                    predators: selected_units.entities.human.clone(),
-                    prey: entity,
-                });
-            } else {
-                break_convert_pursuit.send(BreakConvertPursuit {
-                    entities: selected_units.entities.human.clone(),
+                    predators: priests,
+                    prey,
                 });
             }
+            None if !order.queued => {
+                break_convert_pursuit.send(BreakConvertPursuit { entities: priests });
+            }
+            None => (),
         }
     }
 }
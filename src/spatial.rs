@@ -0,0 +1,131 @@
+use bevy::{math::vec2, prelude::*, utils::HashMap};
+
+use crate::{schedule::InGameSet, selectable::Selectable, state::GameState, teams::Team};
+
+//	a little over the largest structure, so most footprints only reach into the neighbouring cells
+const CELL_SIZE: f32 = 128.0;
+
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_spatial_index
+                .after(InGameSet::Replay)
+                .before(InGameSet::SelectionState)
+                .run_if(in_state(GameState::InGame)),
+        )
+        .insert_resource(SpatialIndex::default());
+    }
+}
+
+/// Where every team's entities were at the start of the frame, bucketed into square cells
+/// so a query only looks at the cells it overlaps
+#[derive(Resource, Default)]
+pub struct SpatialIndex {
+    cells: HashMap<IVec2, Vec<(Entity, Vec2)>>,
+    /// half the size of the largest selectable footprint, which rect queries reach out by
+    reach: f32,
+}
+
+impl SpatialIndex {
+    fn cell(position: Vec2) -> IVec2 {
+        (position / CELL_SIZE).floor().as_ivec2()
+    }
+
+    fn insert(&mut self, entity: Entity, position: Vec2) {
+        self.cells
+            .entry(Self::cell(position))
+            .or_default()
+            .push((entity, position));
+    }
+
+    fn clear(&mut self) {
+        //	the cells keep their space, since the same ones fill up again next frame
+        for entities in self.cells.values_mut() {
+            entities.clear();
+        }
+        self.reach = 0.0;
+    }
+
+    fn in_cells(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = &(Entity, Vec2)> {
+        let (min, max) = (Self::cell(min), Self::cell(max));
+
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+    }
+
+    /// the entities whose centre is closer than `radius` to `center`
+    pub fn within(&self, center: Vec2, radius: f32) -> impl Iterator<Item = Entity> + '_ {
+        self.in_cells(center - radius, center + radius)
+            .filter(move |(_, position)| position.distance(center) < radius)
+            .map(|(entity, _)| *entity)
+    }
+
+    /// the entities whose selectable footprint could overlap `rect`, to be checked more closely
+    pub fn touching(&self, rect: Rect) -> impl Iterator<Item = Entity> + '_ {
+        let rect = rect.inflate(self.reach);
+
+        self.in_cells(rect.min, rect.max)
+            .filter(move |(_, position)| rect.contains(*position))
+            .map(|(entity, _)| *entity)
+    }
+}
+
+fn update_spatial_index(
+    entities: Query<(Entity, &GlobalTransform, Option<&Selectable>), With<Team>>,
+    mut index: ResMut<SpatialIndex>,
+) {
+    index.clear();
+
+    for (entity, transform, selectable) in entities.iter() {
+        let translation = transform.translation();
+        index.insert(entity, vec2(translation.x, translation.y));
+
+        if let Some(selectable) = selectable {
+            index.reach = index.reach.max(selectable.size.max_element() / 2.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(positions: &[Vec2]) -> (SpatialIndex, Vec<Entity>) {
+        let mut index = SpatialIndex::default();
+        let entities: Vec<_> = (0..positions.len() as u32).map(Entity::from_raw).collect();
+
+        for (entity, position) in entities.iter().zip(positions) {
+            index.insert(*entity, *position);
+        }
+        (index, entities)
+    }
+
+    #[test]
+    fn finds_entities_within_a_radius_across_cells() {
+        let (index, entities) = index(&[
+            Vec2::new(10.0, 10.0),
+            Vec2::new(-150.0, 20.0),
+            Vec2::new(300.0, 300.0),
+        ]);
+
+        let mut found: Vec<_> = index.within(Vec2::ZERO, 200.0).collect();
+        found.sort();
+        assert_eq!(found, vec![entities[0], entities[1]]);
+    }
+
+    #[test]
+    fn reaches_out_by_the_largest_footprint() {
+        let (mut index, entities) = index(&[Vec2::new(40.0, 0.0)]);
+        let click = Rect::from_center_size(Vec2::ZERO, Vec2::ONE);
+
+        assert_eq!(index.touching(click).count(), 0);
+
+        index.reach = 48.0;
+        assert_eq!(index.touching(click).collect::<Vec<_>>(), vec![entities[0]]);
+    }
+}
@@ -3,15 +3,18 @@ use bevy::prelude::*;
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum GameState {
     #[default]
+    MainMenu,
     InGame,
     Paused,
+    MapEditor,
+    GameOver,
 }
 
 pub struct StatePlugin;
 
 impl Plugin for StatePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_state(GameState::InGame)
+        app.insert_state(GameState::MainMenu)
             .add_systems(Update, game_state_input_events);
     }
 }
@@ -25,6 +28,15 @@ pub fn game_state_input_events(
         match state.get() {
             GameState::InGame => next_state.set(GameState::Paused),
             GameState::Paused => next_state.set(GameState::InGame),
+            GameState::MainMenu | GameState::MapEditor | GameState::GameOver => (),
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        match state.get() {
+            GameState::InGame => next_state.set(GameState::MapEditor),
+            GameState::MapEditor => next_state.set(GameState::InGame),
+            GameState::MainMenu | GameState::Paused | GameState::GameOver => (),
         }
     }
 }
@@ -1,32 +1,31 @@
-use bevy::{math::vec2, prelude::*};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use vleue_navigator::prelude::PrimitiveObstacle;
 
 use crate::{
     ai::Idle,
-    combat::Health,
-    generator::Generator,
-    producer::{
-        PostSpawnMarker, Producer, Production, ProductionType, PRIEST_COST, SPAWN_OFFSET,
-        WARRIOR_COST, WORKER_COST,
+    archetypes::{
+        rescale, StructureArchetype, StructureArchetypesChanged, StructureRegistry, UnitRegistry,
     },
+    combat::{Health, Turret},
+    conversion::FaithAura,
+    fog::Sight,
+    generator::Generator,
+    match_setup::MatchEntity,
+    producer::{PostSpawnMarker, Producer, Production, Supply, SPAWN_OFFSET},
     schedule::InGameSet,
     selectable::Selectable,
     teams::{Team, TeamType},
 };
 
-pub const SIMPLE_SHRINE_ASSET_PATH: &str = "generator.png";
-pub const PRODUCER_ASSET_PATH: &str = "producer.png";
 pub const POST_SPAWN_MARKER_PATH: &str = "marker.png";
 
-pub const SELECTION_SIZE: Vec2 = vec2(64., 64.);
-pub const SIMPLE_SHRINE_HEALTH: f32 = 660.0;
-pub const PRODUCER_HEALTH: f32 = 1400.0;
-
 pub struct StructurePlugin;
 
 impl Plugin for StructurePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, spawn_structure.in_set(InGameSet::EntityUpdates))
+            .add_systems(Update, rebalance_structures)
             .add_event::<PlaceStructure>();
     }
 }
@@ -34,24 +33,19 @@ impl Plugin for StructurePlugin {
 #[derive(Component)]
 pub struct Structure;
 
-#[derive(PartialEq, Debug)]
-pub enum StructureType {
-    SimpleShrine,
-    Producer,
-}
+/// The archetype a structure was built from, so it can follow edits to it
+#[derive(Component)]
+pub struct StructureKind(pub StructureType);
 
-impl Default for StructureType {
-    fn default() -> Self {
-        StructureType::SimpleShrine
-    }
-}
+/// The id a structure archetype is given in the structure file,
+/// so new structures only need an entry there
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Debug, Default)]
+#[serde(transparent)]
+pub struct StructureType(pub String);
 
-impl Clone for StructureType {
-    fn clone(&self) -> Self {
-        match self {
-            Self::SimpleShrine => Self::SimpleShrine,
-            Self::Producer => Self::Producer,
-        }
+impl From<&str> for StructureType {
+    fn from(id: &str) -> Self {
+        Self(id.into())
     }
 }
 
@@ -62,94 +56,161 @@ pub struct PlaceStructure {
     pub team: TeamType,
 }
 
+/// the navmesh obstacle covering a structure's footprint
+pub fn footprint_obstacle(size: Vec2) -> PrimitiveObstacle {
+    PrimitiveObstacle::Rectangle(Rectangle::from_corners(-size / 2.0, size / 2.0))
+}
+
+/// Spawns a finished structure with the components its archetype asks for
+pub fn spawn_structure_archetype(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    archetype: &StructureArchetype,
+    units: &UnitRegistry,
+    position: Vec3,
+    team: TeamType,
+) -> Entity {
+    let mut structure = commands.spawn((
+        SpriteBundle {
+            texture: asset_server.load(&archetype.sprite),
+            sprite: Sprite {
+                custom_size: Some(archetype.size),
+                ..default()
+            },
+            transform: Transform::from_translation(position),
+            ..default()
+        },
+        footprint_obstacle(archetype.size),
+        Structure {},
+        StructureKind(archetype.kind.clone()),
+        Health(archetype.health),
+        Selectable {
+            size: archetype.size,
+        },
+        Idle(true),
+        Team(team),
+        MatchEntity,
+        Name::new(archetype.name.clone()),
+    ));
+
+    if let Some(range) = archetype.sight {
+        structure.insert(Sight { range });
+    }
+    if let Some(turret) = &archetype.turret {
+        structure.insert(turret.clone());
+    }
+    if let Some(aura) = &archetype.faith_aura {
+        structure.insert(aura.clone());
+    }
+    if let Some(supply) = &archetype.supply {
+        structure.insert(supply.clone());
+    }
+    if archetype.generator {
+        structure.insert(Generator { ..default() });
+    }
+    if archetype.producer {
+        structure
+            .insert(Producer {
+                post_spawn_location: position + SPAWN_OFFSET,
+                rate: archetype.production_rate,
+                ..default()
+            })
+            .with_children(|builder| {
+                builder.spawn((
+                    SpriteBundle {
+                        texture: asset_server.load(POST_SPAWN_MARKER_PATH),
+                        transform: Transform::from_translation(SPAWN_OFFSET),
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    PostSpawnMarker { not_set: true },
+                    Name::new("PostSpawnMarker"),
+                ));
+
+                for archetype in units.producible() {
+                    builder.spawn(Production {
+                        production_type: archetype.kind.clone(),
+                        cost: archetype.cost,
+                        build_time: archetype.build_time,
+                        queue: 0,
+                    });
+                }
+            });
+    }
+
+    structure.id()
+}
+
 fn spawn_structure(
     mut placement_event: EventReader<PlaceStructure>,
     asset_server: Res<AssetServer>,
+    structures: Res<StructureRegistry>,
+    units: Res<UnitRegistry>,
     mut commands: Commands,
 ) {
     for place in placement_event.read() {
-        let marker_texture: Handle<Image> = asset_server.load(POST_SPAWN_MARKER_PATH);
-        let texture: Handle<Image> = asset_server.load(match place.structure_type {
-            StructureType::SimpleShrine => SIMPLE_SHRINE_ASSET_PATH,
-            StructureType::Producer => PRODUCER_ASSET_PATH,
-        });
-
-        match place.structure_type {
-            StructureType::SimpleShrine => {
-                commands.spawn((
-                    SpriteBundle {
-                        texture,
-                        transform: Transform::from_translation(place.position),
-                        ..default()
-                    },
-                    PrimitiveObstacle::Rectangle(Rectangle::from_corners(
-                        Vec2::new(-SELECTION_SIZE.x, -SELECTION_SIZE.y) / 2.0,
-                        Vec2::new(SELECTION_SIZE.x, SELECTION_SIZE.y) / 2.0,
-                    )),
-                    Structure {},
-                    Health(SIMPLE_SHRINE_HEALTH),
-                    Generator { ..default() },
-                    Selectable {
-                        size: SELECTION_SIZE,
-                    },
-                    Idle(true),
-                    Team(place.team.clone()),
-                    Name::new("SimpleShrine"),
-                ));
+        let Some(archetype) = structures.get(&place.structure_type) else {
+            warn!("no archetype for {:?}", place.structure_type);
+            continue;
+        };
+
+        spawn_structure_archetype(
+            &mut commands,
+            &asset_server,
+            archetype,
+            &units,
+            place.position,
+            place.team,
+        );
+    }
+}
+
+type RebalancedStructureData = (
+    &'static StructureKind,
+    &'static mut Health,
+    Option<&'static mut Producer>,
+    Option<&'static mut Turret>,
+    Option<&'static mut FaithAura>,
+    Option<&'static mut Supply>,
+    Option<&'static mut Sight>,
+);
+
+//	health and production rate scale with the archetype, everything else is replaced.
+//	behaviours are not added or removed, so those edits only reach newly built structures
+fn rebalance_structures(
+    mut changed: EventReader<StructureArchetypesChanged>,
+    registry: Res<StructureRegistry>,
+    mut structures: Query<RebalancedStructureData>,
+) {
+    for change in changed.read() {
+        for (kind, mut health, producer, turret, aura, supply, sight) in structures.iter_mut() {
+            let (Some(previous), Some(archetype)) =
+                (change.previous(&kind.0), registry.get(&kind.0))
+            else {
+                continue;
+            };
+
+            health.0 = rescale(health.0, previous.health, archetype.health);
+
+            if let Some(mut producer) = producer {
+                producer.rate = rescale(
+                    producer.rate,
+                    previous.production_rate,
+                    archetype.production_rate,
+                );
             }
-            StructureType::Producer => {
-                commands
-                    .spawn((
-                        SpriteBundle {
-                            texture,
-                            transform: Transform::from_translation(place.position),
-                            ..default()
-                        },
-                        PrimitiveObstacle::Rectangle(Rectangle::from_corners(
-                            Vec2::new(-SELECTION_SIZE.x, -SELECTION_SIZE.y) / 2.0,
-                            Vec2::new(SELECTION_SIZE.x, SELECTION_SIZE.y) / 2.0,
-                        )),
-                        Structure {},
-                        Health(PRODUCER_HEALTH),
-                        Producer {
-                            post_spawn_location: place.position + SPAWN_OFFSET,
-                            ..default()
-                        },
-                        Selectable {
-                            size: SELECTION_SIZE,
-                        },
-                        Idle(true),
-                        Team(place.team.clone()),
-                        Name::new("Producer"),
-                    ))
-                    .with_children(|builder| {
-                        builder.spawn((
-                            SpriteBundle {
-                                texture: marker_texture,
-                                transform: Transform::from_translation(SPAWN_OFFSET),
-                                visibility: Visibility::Hidden,
-                                ..default()
-                            },
-                            PostSpawnMarker { not_set: true },
-                            Name::new("PostSpawnMarker"),
-                        ));
-
-                        builder.spawn(Production {
-                            production_type: ProductionType::Worker,
-                            cost: WORKER_COST,
-                            queue: 0,
-                        });
-                        builder.spawn(Production {
-                            production_type: ProductionType::Priest,
-                            cost: PRIEST_COST,
-                            queue: 0,
-                        });
-                        builder.spawn(Production {
-                            production_type: ProductionType::Warrior,
-                            cost: WARRIOR_COST,
-                            queue: 0,
-                        });
-                    });
+            if let (Some(mut turret), Some(new)) = (turret, &archetype.turret) {
+                turret.strength = new.strength;
+                turret.range = new.range;
+            }
+            if let (Some(mut aura), Some(new)) = (aura, &archetype.faith_aura) {
+                *aura = new.clone();
+            }
+            if let (Some(mut supply), Some(new)) = (supply, &archetype.supply) {
+                supply.amount = new.amount;
+            }
+            if let (Some(mut sight), Some(range)) = (sight, archetype.sight) {
+                sight.range = range;
             }
         }
     }
@@ -1,71 +1,169 @@
 use std::slice::Iter;
 
-use bevy::prelude::*;
+use bevy::{
+    color::palettes::tailwind::{
+        BLUE_500, CYAN_500, GREEN_500, ORANGE_500, PINK_500, PURPLE_500, RED_500, YELLOW_500,
+    },
+    prelude::*,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+pub const PLAYER_COUNT: usize = 2;
+pub const MAX_PLAYERS: usize = 8;
+
+const TEAM_COLORS: [Srgba; MAX_PLAYERS] = [
+    BLUE_500, RED_500, GREEN_500, YELLOW_500, PURPLE_500, ORANGE_500, CYAN_500, PINK_500,
+];
+
+pub struct TeamPlugin;
+
+impl Plugin for TeamPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Players::new(PLAYER_COUNT, TeamType(0)));
+    }
+}
 
 #[derive(Component, Debug)]
 pub struct Team(pub TeamType);
 
-#[derive(PartialEq, Debug)]
-pub enum TeamType {
+/// The id of the player that owns an entity
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TeamType(pub usize);
+
+impl TeamType {
+    pub fn color(&self) -> Color {
+        Color::Srgba(TEAM_COLORS[self.0 % MAX_PLAYERS])
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Controller {
     Human,
     CPU,
+    /// commands come from a recorded replay
+    Replay,
+    /// a person playing on another machine, whose commands arrive over the network
+    Remote,
 }
 
-impl Default for TeamType {
-    fn default() -> Self {
-        TeamType::Human
-    }
+pub struct Player {
+    pub team: TeamType,
+    pub controller: Controller,
+}
+
+#[derive(Resource)]
+pub struct Players {
+    /// the team controlled by this machine's mouse and keyboard
+    pub local: TeamType,
+    pub list: Vec<Player>,
 }
 
-impl Clone for TeamType {
-    fn clone(&self) -> Self {
-        match self {
-            Self::Human => Self::Human,
-            Self::CPU => Self::CPU,
+impl Players {
+    pub fn new(count: usize, local: TeamType) -> Self {
+        Self {
+            local,
+            list: (0..count.min(MAX_PLAYERS))
+                .map(|id| Player {
+                    team: TeamType(id),
+                    controller: if TeamType(id) == local {
+                        Controller::Human
+                    } else {
+                        Controller::CPU
+                    },
+                })
+                .collect(),
         }
     }
+
+    /// every team has the same controller, while the local team only decides whose view is shown
+    pub fn all(count: usize, local: TeamType, controller: Controller) -> Self {
+        Self {
+            local,
+            list: (0..count.min(MAX_PLAYERS))
+                .map(|id| Player {
+                    team: TeamType(id),
+                    controller,
+                })
+                .collect(),
+        }
+    }
+
+    /// the first teams are people on their own machines, of which this machine plays `local`,
+    /// and the rest are left to the AI
+    pub fn networked(count: usize, humans: usize, local: TeamType) -> Self {
+        Self {
+            local,
+            list: (0..count.min(MAX_PLAYERS))
+                .map(|id| Player {
+                    team: TeamType(id),
+                    controller: if TeamType(id) == local {
+                        Controller::Human
+                    } else if id < humans {
+                        Controller::Remote
+                    } else {
+                        Controller::CPU
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_local(&self, team: &TeamType) -> bool {
+        self.local == *team
+    }
+
+    pub fn contains(&self, team: &TeamType) -> bool {
+        self.list.iter().any(|player| player.team == *team)
+    }
+
+    pub fn cpu_teams(&self) -> Vec<TeamType> {
+        self.list
+            .iter()
+            .filter(|player| player.controller == Controller::CPU)
+            .map(|player| player.team)
+            .collect()
+    }
 }
 
 pub struct TeamBasedValues<T> {
-    pub human: Vec<T>,
-    pub cpu: Vec<T>,
+    values: HashMap<TeamType, Vec<T>>,
 }
 
 impl<T> Default for TeamBasedValues<T> {
     fn default() -> Self {
         Self {
-            human: Default::default(),
-            cpu: Default::default(),
+            values: Default::default(),
         }
     }
 }
 
 impl<T> TeamBasedValues<T> {
     pub fn len(&self, team: &TeamType) -> usize {
-        match team {
-            TeamType::Human => self.human.len(),
-            TeamType::CPU => self.cpu.len(),
-        }
+        self.values.get(team).map_or(0, |values| values.len())
     }
 
     pub fn clear(&mut self, team: &TeamType) {
-        match team {
-            TeamType::Human => self.human.clear(),
-            TeamType::CPU => self.cpu.clear(),
+        if let Some(values) = self.values.get_mut(team) {
+            values.clear();
         }
     }
 
     pub fn push(&mut self, team: &TeamType, value: T) {
-        match team {
-            TeamType::Human => self.human.push(value),
-            TeamType::CPU => self.cpu.push(value),
-        }
+        self.values.entry(*team).or_default().push(value);
     }
 
     pub fn iter(&self, team: &TeamType) -> Iter<'_, T> {
-        match team {
-            TeamType::Human => self.human.iter(),
-            TeamType::CPU => self.cpu.iter(),
-        }
+        self.values
+            .get(team)
+            .map_or(<&[T]>::default(), |values| values.as_slice())
+            .iter()
+    }
+}
+
+impl<T: Clone> TeamBasedValues<T> {
+    pub fn to_vec(&self, team: &TeamType) -> Vec<T> {
+        self.iter(team).cloned().collect()
     }
 }
@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use bevy::{
+    app::Plugins, ecs::world::CommandQueue, hierarchy::HierarchyPlugin, prelude::*,
+    state::app::StatesPlugin, time::TimeUpdateStrategy, transform::TransformPlugin,
+};
+
+use crate::{
+    archetypes::{
+        ArchetypeFile, Balance, StructureArchetypes, StructureArchetypesChanged, StructureRegistry,
+        UnitArchetypes, UnitArchetypesChanged, UnitRegistry,
+    },
+    combat::Health,
+    conversion::Faith,
+    currency::Energy,
+    diplomacy::Diplomacy,
+    headless::NoTextureLoader,
+    inputs::{BuildSelection, MousePosition},
+    movement::SetUnitPosition,
+    nav_agent::{AssignNavigatorPath, PathFailed},
+    producer::ProductionType,
+    schedule::SchedulePlugin,
+    selectable::{SelectedStructures, SelectedUnits, SelectionStateChanged},
+    spatial::SpatialPlugin,
+    state::GameState,
+    structure::{spawn_structure_archetype, StructureType},
+    teams::{Controller, Players, Team, TeamType},
+    unit::spawn_archetype,
+};
+
+pub const TEST_TIMESTEP: Duration = Duration::from_nanos(16_666_667);
+const TOLERANCE: f32 = 0.01;
+
+/// What a test starts with, spawned in the order it is listed
+#[derive(Default)]
+pub struct Scenario {
+    pub units: Vec<(ProductionType, TeamType, Vec2)>,
+    pub structures: Vec<(StructureType, TeamType, Vec2)>,
+    pub energy: Vec<(TeamType, f32)>,
+}
+
+/// The entities spawned for a scenario, in the same order as its lists
+pub struct Spawned {
+    pub units: Vec<Entity>,
+    pub structures: Vec<Entity>,
+}
+
+/// An `App` running the chosen gameplay plugins in a match that has already begun,
+/// stepped one fixed frame at a time
+pub struct TestApp {
+    pub app: App,
+}
+
+impl TestApp {
+    pub fn new<M>(plugins: impl Plugins<M>) -> Self {
+        let mut app = App::new();
+
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            StatesPlugin,
+            AssetPlugin {
+                watch_for_changes_override: Some(false),
+                ..default()
+            },
+        ))
+        .init_asset::<Image>()
+        .init_asset_loader::<NoTextureLoader>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(TEST_TIMESTEP))
+        .insert_state(GameState::InGame)
+        .add_plugins((SchedulePlugin, SpatialPlugin));
+
+        //	what the plugins left out of a test would otherwise provide
+        app.add_event::<SetUnitPosition>()
+            .add_event::<AssignNavigatorPath>()
+            .add_event::<PathFailed>()
+            .add_event::<SelectionStateChanged>()
+            .add_event::<UnitArchetypesChanged>()
+            .add_event::<StructureArchetypesChanged>()
+            .insert_resource(Players::all(2, TeamType(0), Controller::CPU))
+            .insert_resource(Diplomacy::free_for_all())
+            .insert_resource(Energy::default())
+            .insert_resource(Balance::default())
+            .insert_resource(UnitRegistry::new(
+                load_archetypes::<UnitArchetypes>(include_str!(
+                    "../assets/archetypes/default.units.ron"
+                ))
+                .units,
+            ))
+            .insert_resource(StructureRegistry::new(
+                load_archetypes::<StructureArchetypes>(include_str!(
+                    "../assets/archetypes/default.structures.ron"
+                ))
+                .structures,
+            ))
+            .insert_resource(SelectedUnits {
+                entities: Default::default(),
+            })
+            .insert_resource(SelectedStructures {
+                entities: Default::default(),
+            })
+            .insert_resource(BuildSelection {
+                is_selected: false,
+                structure_type: StructureType::default(),
+                cost: 0.,
+            })
+            .insert_resource(MousePosition(Vec2::ZERO));
+
+        app.add_plugins(plugins);
+
+        Self { app }
+    }
+
+    pub fn spawn(&mut self, scenario: &Scenario) -> Spawned {
+        let world = self.app.world_mut();
+        let mut queue = CommandQueue::default();
+
+        //	spawned the same way as in a match, through commands applied straight away
+        let spawned = {
+            let mut commands = Commands::new(&mut queue, world);
+            let asset_server = world.resource::<AssetServer>();
+            let units = world.resource::<UnitRegistry>();
+            let structures = world.resource::<StructureRegistry>();
+            let players = world.resource::<Players>();
+
+            Spawned {
+                units: scenario
+                    .units
+                    .iter()
+                    .map(|(kind, team, position)| {
+                        let archetype = units.get(kind).expect("unit archetype");
+                        let position = position.extend(0.0);
+
+                        spawn_archetype(
+                            &mut commands,
+                            asset_server,
+                            archetype,
+                            position,
+                            position,
+                            *team,
+                            players,
+                        )
+                    })
+                    .collect(),
+                structures: scenario
+                    .structures
+                    .iter()
+                    .map(|(kind, team, position)| {
+                        let archetype = structures.get(kind).expect("structure archetype");
+
+                        spawn_structure_archetype(
+                            &mut commands,
+                            asset_server,
+                            archetype,
+                            units,
+                            position.extend(0.0),
+                            *team,
+                        )
+                    })
+                    .collect(),
+            }
+        };
+
+        queue.apply(world);
+
+        let mut energy = world.resource_mut::<Energy>();
+        for (team, value) in scenario.energy.iter() {
+            energy.set(team, *value);
+        }
+
+        spawned
+    }
+
+    pub fn send<E: Event>(&mut self, event: E) {
+        self.app.world_mut().send_event(event);
+    }
+
+    pub fn select_units(&mut self, team: TeamType, entities: &[Entity]) {
+        let mut selected = self.app.world_mut().resource_mut::<SelectedUnits>();
+        selected.entities.clear(&team);
+        for entity in entities {
+            selected.entities.push(&team, *entity);
+        }
+    }
+
+    pub fn select_structures(&mut self, team: TeamType, entities: &[Entity]) {
+        let mut selected = self.app.world_mut().resource_mut::<SelectedStructures>();
+        selected.entities.clear(&team);
+        for entity in entities {
+            selected.entities.push(&team, *entity);
+        }
+    }
+
+    pub fn step(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    pub fn step_seconds(&mut self, seconds: f32) {
+        self.step((seconds / TEST_TIMESTEP.as_secs_f32()).ceil() as usize);
+    }
+
+    /// every entity of the given team that has the component
+    pub fn find<C: Component>(&mut self, team: TeamType) -> Vec<Entity> {
+        self.app
+            .world_mut()
+            .query_filtered::<(Entity, &Team), With<C>>()
+            .iter(self.app.world())
+            .filter(|(_, t)| t.0 == team)
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    pub fn health(&self, entity: Entity) -> f32 {
+        self.get::<Health>(entity).0
+    }
+
+    fn get<C: Component>(&self, entity: Entity) -> &C {
+        self.app
+            .world()
+            .get::<C>(entity)
+            .unwrap_or_else(|| panic!("{:?} has no {}", entity, std::any::type_name::<C>()))
+    }
+
+    pub fn assert_despawned(&self, entity: Entity) {
+        assert!(
+            self.app.world().get_entity(entity).is_none(),
+            "{:?} is still alive",
+            entity
+        );
+    }
+
+    pub fn assert_health_below(&self, entity: Entity, max: f32) {
+        let health = self.health(entity);
+        assert!(
+            health < max,
+            "{:?} has {} health, expected below {}",
+            entity,
+            health,
+            max
+        );
+    }
+
+    pub fn assert_faith(&self, entity: Entity, expected: f32) {
+        let faith = self.get::<Faith>(entity).current;
+        assert!(
+            (faith - expected).abs() < TOLERANCE,
+            "{:?} has {} faith, expected {}",
+            entity,
+            faith,
+            expected
+        );
+    }
+
+    pub fn assert_team(&self, entity: Entity, expected: TeamType) {
+        assert_eq!(self.get::<Team>(entity).0, expected, "team of {:?}", entity);
+    }
+
+    pub fn assert_energy(&self, team: TeamType, expected: f32) {
+        let energy = self.app.world().resource::<Energy>().get(&team);
+        assert!(
+            (energy - expected).abs() < TOLERANCE,
+            "team {} has {} energy, expected {}",
+            team.0,
+            energy,
+            expected
+        );
+    }
+
+    pub fn assert_near(&self, entity: Entity, position: Vec2, distance: f32) {
+        let actual = self.get::<Transform>(entity).translation.xy();
+        assert!(
+            actual.distance(position) <= distance,
+            "{:?} is at {}, expected within {} of {}",
+            entity,
+            actual,
+            distance,
+            position
+        );
+    }
+}
+
+fn load_archetypes<A: ArchetypeFile>(source: &str) -> A {
+    let archetypes: A = ron::de::from_str(source).expect("archetype file parses");
+    archetypes.validate().expect("archetype file is valid");
+    archetypes
+}
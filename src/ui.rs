@@ -3,20 +3,23 @@ use bevy::{
         css::{BLACK, DARK_GREEN, DARK_SLATE_BLUE},
         tailwind::{GRAY_200, GRAY_800, GREEN_200},
     },
+    ecs::system::SystemParam,
     prelude::*,
 };
 
 use crate::{
-    inputs::{mouse_is_hovered_over, BuildSelection},
+    archetypes::{StructureRegistry, UnitRegistry},
+    inputs::{mouse_is_hovered_over, BuildSelection, PatrolSelection},
+    minimap::spawn_minimap_panel,
+    orders::{HoldPosition, StopUnits},
     producer::{
         AttemptProductionIncrease, DisplayProducerUI, Producer, Production, ProductionType,
-        RemoveProducerUI, PRIEST_COST, WARRIOR_COST, WORKER_COST,
+        RemoveProducerUI,
     },
     schedule::InGameSet,
     selectable::{SelectedStructures, SelectionState, SelectionStateChanged, SelectionType},
-    structure::{StructureType, PRODUCER_ASSET_PATH, SIMPLE_SHRINE_ASSET_PATH},
-    teams::TeamType,
-    unit::{GOOD_PRIEST_ASSET_PATH, GOOD_WARRIOR_ASSET_PATH, GOOD_WORKER_ASSET_PATH},
+    structure::StructureType,
+    teams::Players,
     worker::{DisplayWorkerUI, RemoveWorkerUI},
 };
 
@@ -26,21 +29,29 @@ const MARGIN: Val = Val::Px(12.);
 const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.35, 0.35);
 const COST_TEXT_COLOR: Color = Color::Srgba(BLACK);
 
-pub const SIMPLE_SHRINE_COST: f32 = 60.;
-pub const PRODUCER_COST: f32 = 140.;
-
 pub struct UIPlugin;
 
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         // Only run the app when there is user input. This will significantly reduce CPU/GPU use.
         app.add_systems(Startup, (setup_ui_base, setup_worker_ui, setup_producer_ui))
+            .add_systems(Startup, setup_command_ui)
+            .add_systems(
+                Update,
+                (
+                    build_structure_buttons.run_if(resource_changed::<StructureRegistry>),
+                    build_production_buttons.run_if(
+                        resource_changed::<UnitRegistry>.or_else(resource_changed::<Players>),
+                    ),
+                ),
+            )
             .add_systems(
                 Update,
                 (
                     update_ui,
                     (remove_worker_ui, display_worker_ui).chain(),
                     (remove_producer_ui, display_producer_ui).chain(),
+                    display_command_ui,
                 )
                     .chain()
                     .after(InGameSet::UserInput),
@@ -48,7 +59,11 @@ impl Plugin for UIPlugin {
             .add_systems(
                 Update,
                 (
-                    (build_button_interactions, producer_button_interactions)
+                    (
+                        build_button_interactions,
+                        producer_button_interactions,
+                        command_button_interactions,
+                    )
                         .run_if(mouse_is_hovered_over::<false>),
                     production_queue_display,
                 )
@@ -67,22 +82,43 @@ struct BuildButton {
     cost: f32,
 }
 
+#[derive(Component)]
+struct BuildButtons;
+
 #[derive(Component)]
 struct ProducerButton {
     pub production_type: ProductionType,
 }
 
+#[derive(Component)]
+struct ProductionButtons;
+
 #[derive(Component)]
 struct QueueText {
     pub production_type: ProductionType,
 }
 
+#[derive(Component)]
+struct CommandButton {
+    command: UnitCommand,
+}
+
+#[derive(Clone, Copy)]
+enum UnitCommand {
+    Stop,
+    Hold,
+    Patrol,
+}
+
 #[derive(Component)]
 pub struct WorkerUI {}
 
 #[derive(Component)]
 pub struct ProducerUI {}
 
+#[derive(Component)]
+pub struct CommandUI {}
+
 #[derive(Debug)]
 pub enum UIType {
     None,
@@ -113,24 +149,23 @@ fn setup_ui_base(mut commands: Commands) {
             ..Default::default()
         },))
         .with_children(|builder| {
-            builder.spawn(NodeBundle {
-                style: Style {
-                    flex_direction: FlexDirection::Row,
-                    padding: UiRect::all(MARGIN),
-                    height: Val::Px(UI_BASE_HEIGHT),
-                    width: Val::Percent(100.),
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        padding: UiRect::all(MARGIN),
+                        height: Val::Px(UI_BASE_HEIGHT),
+                        width: Val::Percent(100.),
+                        ..Default::default()
+                    },
+                    background_color: Color::Srgba(DARK_SLATE_BLUE).into(),
                     ..Default::default()
-                },
-                background_color: Color::Srgba(DARK_SLATE_BLUE).into(),
-                ..Default::default()
-            });
+                })
+                .with_children(spawn_minimap_panel);
         });
 }
 
-fn setup_worker_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let simple_shrine_texture: Handle<Image> = asset_server.load(SIMPLE_SHRINE_ASSET_PATH);
-    let producer_texture: Handle<Image> = asset_server.load(PRODUCER_ASSET_PATH);
-
+fn setup_worker_ui(mut commands: Commands) {
     commands
         .spawn((
             NodeBundle {
@@ -150,8 +185,8 @@ fn setup_worker_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
             WorkerUI {},
         ))
         .with_children(|builder| {
-            builder
-                .spawn(NodeBundle {
+            builder.spawn((
+                NodeBundle {
                     style: Style {
                         flex_direction: FlexDirection::Row,
                         padding: UiRect::all(MARGIN),
@@ -159,120 +194,96 @@ fn setup_worker_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     },
                     background_color: Color::Srgba(DARK_GREEN).into(),
                     ..Default::default()
+                },
+                BuildButtons,
+            ));
+        });
+}
+
+//	one button for every structure a worker can place, in the order of the archetype file
+fn build_structure_buttons(
+    registry: Res<StructureRegistry>,
+    rows: Query<Entity, With<BuildButtons>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for row in rows.iter() {
+        commands
+            .entity(row)
+            .despawn_descendants()
+            .with_children(|builder| {
+                for archetype in registry.buildable() {
+                    build_button(
+                        builder,
+                        asset_server.load(&archetype.sprite),
+                        archetype.kind.clone(),
+                        archetype.cost,
+                    );
+                }
+            });
+    }
+}
+
+fn build_button(
+    parent: &mut ChildBuilder,
+    texture: Handle<Image>,
+    structure_type: StructureType,
+    cost: f32,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(64.0),
+                    height: Val::Px(64.0),
+                    border: UiRect::all(Val::Px(2.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: Color::Srgba(GRAY_800).into(),
+                background_color: NORMAL_BUTTON.into(),
+                image: UiImage {
+                    texture,
+                    ..default()
+                },
+                ..default()
+            },
+            BuildButton {
+                structure_type,
+                cost,
+            },
+        ))
+        .with_children(|builder| {
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(100.),
+                        padding: UiRect::all(Val::Px(2.)),
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::FlexEnd,
+                        align_items: AlignItems::FlexStart,
+                        ..default()
+                    },
+                    ..default()
                 })
                 .with_children(|builder| {
-                    builder
-                        .spawn((
-                            ButtonBundle {
-                                style: Style {
-                                    width: Val::Px(64.0),
-                                    height: Val::Px(64.0),
-                                    border: UiRect::all(Val::Px(2.0)),
-                                    justify_content: JustifyContent::Center,
-                                    align_items: AlignItems::Center,
-                                    ..default()
-                                },
-                                border_color: Color::Srgba(GRAY_800).into(),
-                                background_color: NORMAL_BUTTON.into(),
-                                image: UiImage {
-                                    texture: simple_shrine_texture,
-                                    ..default()
-                                },
-                                ..default()
-                            },
-                            BuildButton {
-                                structure_type: StructureType::SimpleShrine,
-                                cost: SIMPLE_SHRINE_COST,
-                            },
-                        ))
-                        .with_children(|builder| {
-                            builder
-                                .spawn(NodeBundle {
-                                    style: Style {
-                                        width: Val::Percent(100.),
-                                        height: Val::Percent(100.),
-                                        padding: UiRect::all(Val::Px(2.)),
-                                        flex_direction: FlexDirection::Column,
-                                        justify_content: JustifyContent::FlexEnd,
-                                        align_items: AlignItems::FlexStart,
-                                        ..default()
-                                    },
-                                    ..default()
-                                })
-                                .with_children(|builder| {
-                                    builder.spawn(TextBundle {
-                                        text: Text::from_section(
-                                            SIMPLE_SHRINE_COST.to_string(),
-                                            TextStyle {
-                                                color: COST_TEXT_COLOR,
-                                                ..default()
-                                            },
-                                        ),
-                                        ..default()
-                                    });
-                                });
-                        });
-
-                    builder
-                        .spawn((
-                            ButtonBundle {
-                                style: Style {
-                                    width: Val::Px(64.0),
-                                    height: Val::Px(64.0),
-                                    border: UiRect::all(Val::Px(2.0)),
-                                    justify_content: JustifyContent::Center,
-                                    align_items: AlignItems::Center,
-                                    ..default()
-                                },
-                                border_color: Color::Srgba(GRAY_800).into(),
-                                background_color: NORMAL_BUTTON.into(),
-                                image: UiImage {
-                                    texture: producer_texture,
-                                    ..default()
-                                },
+                    builder.spawn(TextBundle {
+                        text: Text::from_section(
+                            cost.to_string(),
+                            TextStyle {
+                                color: COST_TEXT_COLOR,
                                 ..default()
                             },
-                            BuildButton {
-                                structure_type: StructureType::Producer,
-                                cost: PRODUCER_COST,
-                            },
-                        ))
-                        .with_children(|builder| {
-                            builder
-                                .spawn(NodeBundle {
-                                    style: Style {
-                                        width: Val::Percent(100.),
-                                        height: Val::Percent(100.),
-                                        padding: UiRect::all(Val::Px(2.)),
-                                        flex_direction: FlexDirection::Column,
-                                        justify_content: JustifyContent::FlexEnd,
-                                        align_items: AlignItems::FlexStart,
-                                        ..default()
-                                    },
-                                    ..default()
-                                })
-                                .with_children(|builder| {
-                                    builder.spawn(TextBundle {
-                                        text: Text::from_section(
-                                            PRODUCER_COST.to_string(),
-                                            TextStyle {
-                                                color: COST_TEXT_COLOR,
-                                                ..default()
-                                            },
-                                        ),
-                                        ..default()
-                                    });
-                                });
-                        });
+                        ),
+                        ..default()
+                    });
                 });
         });
 }
 
-fn setup_producer_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let worker_texture: Handle<Image> = asset_server.load(GOOD_WORKER_ASSET_PATH);
-    let priest_texture: Handle<Image> = asset_server.load(GOOD_PRIEST_ASSET_PATH);
-    let warrior_texture: Handle<Image> = asset_server.load(GOOD_WARRIOR_ASSET_PATH);
-
+fn setup_producer_ui(mut commands: Commands) {
     commands
         .spawn((
             NodeBundle {
@@ -292,8 +303,8 @@ fn setup_producer_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
             ProducerUI {},
         ))
         .with_children(|builder| {
-            builder
-                .spawn(NodeBundle {
+            builder.spawn((
+                NodeBundle {
                     style: Style {
                         flex_direction: FlexDirection::Row,
                         padding: UiRect::all(MARGIN),
@@ -301,28 +312,36 @@ fn setup_producer_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     },
                     background_color: Color::Srgba(DARK_GREEN).into(),
                     ..Default::default()
-                })
-                .with_children(|builder| {
-                    production_button(
-                        builder,
-                        worker_texture,
-                        ProductionType::Worker,
-                        WORKER_COST.to_string(),
-                    );
-                    production_button(
-                        builder,
-                        priest_texture,
-                        ProductionType::Priest,
-                        PRIEST_COST.to_string(),
-                    );
+                },
+                ProductionButtons,
+            ));
+        });
+}
+
+//	one button for every unit a producer can make, in the order of the archetype file,
+//	shown the way the local team's own units look
+fn build_production_buttons(
+    registry: Res<UnitRegistry>,
+    players: Res<Players>,
+    rows: Query<Entity, With<ProductionButtons>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for row in rows.iter() {
+        commands
+            .entity(row)
+            .despawn_descendants()
+            .with_children(|builder| {
+                for archetype in registry.producible() {
                     production_button(
                         builder,
-                        warrior_texture,
-                        ProductionType::Warrior,
-                        WARRIOR_COST.to_string(),
+                        asset_server.load(archetype.sprites.path(&players.local, &players)),
+                        archetype.kind.clone(),
+                        archetype.cost.to_string(),
                     );
-                });
-        });
+                }
+            });
+    }
 }
 
 fn production_button(
@@ -405,17 +424,89 @@ fn production_button(
         });
 }
 
+//	stop, hold and patrol sit on the right, for any selection of units
+fn setup_command_ui(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    // fill the entire window
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexEnd,
+                    align_items: AlignItems::FlexEnd,
+                    row_gap: MARGIN,
+                    display: Display::None,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            CommandUI {},
+        ))
+        .with_children(|builder| {
+            builder
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        padding: UiRect::all(MARGIN),
+                        ..Default::default()
+                    },
+                    background_color: Color::Srgba(DARK_GREEN).into(),
+                    ..Default::default()
+                })
+                .with_children(|builder| {
+                    command_button(builder, UnitCommand::Stop, "Stop\n(X)");
+                    command_button(builder, UnitCommand::Hold, "Hold\n(H)");
+                    command_button(builder, UnitCommand::Patrol, "Patrol\n(P)");
+                });
+        });
+}
+
+fn command_button(parent: &mut ChildBuilder, command: UnitCommand, label: &str) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(64.0),
+                    height: Val::Px(64.0),
+                    border: UiRect::all(Val::Px(2.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                border_color: Color::Srgba(GRAY_800).into(),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            CommandButton { command },
+        ))
+        .with_children(|builder| {
+            builder.spawn(
+                TextBundle::from_section(label, TextStyle { ..default() })
+                    .with_text_justify(JustifyText::Center),
+            );
+        });
+}
+
+/// Shows and hides the worker and producer panels
+#[derive(SystemParam)]
+struct PanelEvents<'w> {
+    remove_worker_ui: EventWriter<'w, RemoveWorkerUI>,
+    display_worker_ui: EventWriter<'w, DisplayWorkerUI>,
+    remove_producer_ui: EventWriter<'w, RemoveProducerUI>,
+    display_producer_ui: EventWriter<'w, DisplayProducerUI>,
+}
+
 fn update_ui(
     mut selection_state_changed: EventReader<SelectionStateChanged>,
     mut selection_state: ResMut<SelectionState>,
     mut current_ui: ResMut<CurrentUI>,
-    mut remove_worker_ui: EventWriter<RemoveWorkerUI>,
-    mut display_worker_ui: EventWriter<DisplayWorkerUI>,
-    mut remove_producer_ui: EventWriter<RemoveProducerUI>,
-    mut display_producer_ui: EventWriter<DisplayProducerUI>,
+    players: Res<Players>,
+    mut panels: PanelEvents,
 ) {
     for selection_change in selection_state_changed.read() {
-        if selection_change.team != TeamType::Human {
+        if !players.is_local(&selection_change.team) {
             continue;
         }
 
@@ -426,11 +517,11 @@ fn update_ui(
                 current_ui.ui_type = UIType::None;
             }
             (SelectionType::None, UIType::Worker) => {
-                remove_worker_ui.send(RemoveWorkerUI {});
+                panels.remove_worker_ui.send(RemoveWorkerUI {});
                 current_ui.ui_type = UIType::None;
             }
             (SelectionType::None, UIType::Producer) => {
-                remove_producer_ui.send(RemoveProducerUI {});
+                panels.remove_producer_ui.send(RemoveProducerUI {});
                 current_ui.ui_type = UIType::None;
             }
             (SelectionType::None, UIType::Generator) => {
@@ -441,17 +532,17 @@ fn update_ui(
             (SelectionType::Unit, UIType::Producer) => (),
             (SelectionType::Unit, UIType::Generator) => (),
             (SelectionType::Worker, UIType::None) => {
-                display_worker_ui.send(DisplayWorkerUI {});
+                panels.display_worker_ui.send(DisplayWorkerUI {});
                 current_ui.ui_type = UIType::Worker;
             }
             (SelectionType::Worker, UIType::Worker) => (),
             (SelectionType::Worker, UIType::Producer) => {
-                remove_producer_ui.send(RemoveProducerUI {});
-                display_worker_ui.send(DisplayWorkerUI {});
+                panels.remove_producer_ui.send(RemoveProducerUI {});
+                panels.display_worker_ui.send(DisplayWorkerUI {});
                 current_ui.ui_type = UIType::Worker;
             }
             (SelectionType::Worker, UIType::Generator) => {
-                display_worker_ui.send(DisplayWorkerUI {});
+                panels.display_worker_ui.send(DisplayWorkerUI {});
                 current_ui.ui_type = UIType::Worker;
             }
             (SelectionType::Priest, UIType::None) => (),
@@ -470,26 +561,26 @@ fn update_ui(
                 current_ui.ui_type = UIType::Generator;
             }
             (SelectionType::Generator, UIType::Worker) => {
-                remove_worker_ui.send(RemoveWorkerUI {});
+                panels.remove_worker_ui.send(RemoveWorkerUI {});
                 current_ui.ui_type = UIType::Generator;
             }
             (SelectionType::Generator, UIType::Producer) => {
-                remove_producer_ui.send(RemoveProducerUI {});
+                panels.remove_producer_ui.send(RemoveProducerUI {});
                 current_ui.ui_type = UIType::Generator;
             }
             (SelectionType::Generator, UIType::Generator) => (),
             (SelectionType::Producer, UIType::None) => {
-                display_producer_ui.send(DisplayProducerUI {});
+                panels.display_producer_ui.send(DisplayProducerUI {});
                 current_ui.ui_type = UIType::Producer;
             }
             (SelectionType::Producer, UIType::Worker) => {
-                remove_worker_ui.send(RemoveWorkerUI {});
-                display_producer_ui.send(DisplayProducerUI {});
+                panels.remove_worker_ui.send(RemoveWorkerUI {});
+                panels.display_producer_ui.send(DisplayProducerUI {});
                 current_ui.ui_type = UIType::Producer;
             }
             (SelectionType::Producer, UIType::Producer) => (),
             (SelectionType::Producer, UIType::Generator) => {
-                display_producer_ui.send(DisplayProducerUI {});
+                panels.display_producer_ui.send(DisplayProducerUI {});
                 current_ui.ui_type = UIType::Producer;
             }
         }
@@ -540,13 +631,39 @@ fn remove_producer_ui(
     }
 }
 
+fn display_command_ui(
+    selection_state: Res<SelectionState>,
+    mut command_ui_query: Query<&mut Style, With<CommandUI>>,
+) {
+    if !selection_state.is_changed() {
+        return;
+    }
+
+    let display = match selection_state.0 {
+        SelectionType::Unit
+        | SelectionType::Worker
+        | SelectionType::Priest
+        | SelectionType::Warrior => Display::Flex,
+        _ => Display::None,
+    };
+    for mut style in &mut command_ui_query {
+        style.display = display;
+    }
+}
+
+/// The buttons of one kind that were just pressed, hovered or left
+type ButtonInteractions<'w, 's, B> = Query<
+    'w,
+    's,
+    (&'static Interaction, &'static mut BorderColor, &'static B),
+    (Changed<Interaction>, With<B>),
+>;
+
 fn build_button_interactions(
-    mut interaction_query: Query<
-        (&Interaction, &mut BorderColor, &BuildButton),
-        (Changed<Interaction>, With<BuildButton>),
-    >,
+    mut interaction_query: ButtonInteractions<BuildButton>,
     mut build_selection: ResMut<BuildSelection>,
     mut update_selection_state: EventWriter<SelectionStateChanged>,
+    players: Res<Players>,
 ) {
     for (interaction, mut border_color, button) in &mut interaction_query {
         match *interaction {
@@ -559,7 +676,7 @@ fn build_button_interactions(
 
                 update_selection_state.send(SelectionStateChanged {
                     new_type: SelectionType::Construction,
-                    team: TeamType::Human,
+                    team: players.local,
                 });
             }
             Interaction::Hovered => {
@@ -573,11 +690,9 @@ fn build_button_interactions(
 }
 
 fn producer_button_interactions(
-    mut interaction_query: Query<
-        (&Interaction, &mut BorderColor, &ProducerButton),
-        (Changed<Interaction>, With<ProducerButton>),
-    >,
+    mut interaction_query: ButtonInteractions<ProducerButton>,
     mut attempt_production_event: EventWriter<AttemptProductionIncrease>,
+    players: Res<Players>,
 ) {
     for (interaction, mut border_color, button) in &mut interaction_query {
         match *interaction {
@@ -586,7 +701,7 @@ fn producer_button_interactions(
 
                 attempt_production_event.send(AttemptProductionIncrease {
                     production_type: button.production_type.clone(),
-                    team: TeamType::Human,
+                    team: players.local,
                 });
             }
             Interaction::Hovered => {
@@ -599,13 +714,51 @@ fn producer_button_interactions(
     }
 }
 
+fn command_button_interactions(
+    mut interaction_query: ButtonInteractions<CommandButton>,
+    mut patrol_selection: ResMut<PatrolSelection>,
+    mut stop_units: EventWriter<StopUnits>,
+    mut hold_position: EventWriter<HoldPosition>,
+    players: Res<Players>,
+) {
+    for (interaction, mut border_color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                border_color.0 = Color::Srgba(GREEN_200);
+
+                let team = players.local;
+                match button.command {
+                    UnitCommand::Stop => {
+                        stop_units.send(StopUnits { team });
+                    }
+                    UnitCommand::Hold => {
+                        hold_position.send(HoldPosition { team });
+                    }
+                    //	the points are set with right clicks in the world
+                    UnitCommand::Patrol => {
+                        patrol_selection.is_selected = true;
+                        patrol_selection.points.clear();
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                border_color.0 = Color::Srgba(GRAY_200);
+            }
+            Interaction::None => {
+                border_color.0 = Color::Srgba(GRAY_800);
+            }
+        }
+    }
+}
+
 fn production_queue_display(
     mut text_query: Query<(&mut Text, &QueueText)>,
     selected_structures: Res<SelectedStructures>,
     producer_query: Query<&Children, With<Producer>>,
     production_query: Query<&Production>,
+    players: Res<Players>,
 ) {
-    for entity in selected_structures.entities.human.clone() {
+    for &entity in selected_structures.entities.iter(&players.local) {
         if let Ok(children) = producer_query.get(entity) {
             for &child in children.iter() {
                 if let Ok(production) = production_query.get(child) {
@@ -1,44 +1,31 @@
-use bevy::{math::vec2, prelude::*};
+use bevy::prelude::*;
 
 use crate::{
     ai::Idle,
+    archetypes::{rescale, UnitArchetype, UnitArchetypesChanged, UnitRegistry},
     combat::Health,
     conversion::Faith,
     detection::Detector,
+    fog::Sight,
+    match_setup::MatchEntity,
     movement::{Moveable, Moving},
     nav_agent::{AssignNavigatorPath, Navigator},
+    orders::Orders,
     priest::Priest,
     producer::{Produce, ProductionType},
     schedule::InGameSet,
     selectable::Selectable,
-    teams::{Team, TeamType},
+    teams::{Players, Team, TeamType},
     warrior::Warrior,
     worker::Worker,
 };
 
-const GOOD_HERO_ASSET_PATH: &str = "hero_good.png";
-pub const GOOD_WORKER_ASSET_PATH: &str = "worker_good.png";
-pub const GOOD_PRIEST_ASSET_PATH: &str = "priest_good.png";
-pub const GOOD_WARRIOR_ASSET_PATH: &str = "warrior_good.png";
-
-const EVIL_HERO_ASSET_PATH: &str = "hero_evil.png";
-pub const EVIL_WORKER_ASSET_PATH: &str = "worker_evil.png";
-pub const EVIL_PRIEST_ASSET_PATH: &str = "priest_evil.png";
-pub const EVIL_WARRIOR_ASSET_PATH: &str = "warrior_evil.png";
-
-const WORKER_SPEED: f32 = 100.0;
-const PRIEST_SPEED: f32 = 85.0;
-const WARRIOR_SPEED: f32 = 120.0;
-
-const HERO_DETECTION_RANGE: f32 = 500.0;
-const WARRIOR_DETECTION_RANGE: f32 = 380.0;
-const PRIEST_DETECTION_RANGE: f32 = 440.0;
-
 pub struct UnitPlugin;
 
 impl Plugin for UnitPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, spawn_unit.in_set(InGameSet::SpawnEntities))
+            .add_systems(Update, rebalance_units)
             .add_event::<UnitAction>();
     }
 }
@@ -46,51 +33,106 @@ impl Plugin for UnitPlugin {
 #[derive(Component, Default)]
 pub struct Unit {}
 
+#[derive(Component)]
+pub struct Hero;
+
+/// The archetype a unit was spawned from, so it can follow edits to it
+#[derive(Component)]
+pub struct UnitKind(pub ProductionType);
+
 #[derive(Event)]
 pub struct UnitAction {
     pub position: Vec2,
+    /// whether the action is added to the selection's orders instead of replacing them
+    pub queued: bool,
 }
 
-pub fn spawn_hero(
+/// Spawns a unit with the stats of its archetype, returning it so it can be given orders
+pub fn spawn_archetype(
     commands: &mut Commands,
     asset_server: &AssetServer,
-    position: Vec2,
+    archetype: &UnitArchetype,
+    position: Vec3,
+    location: Vec3,
     team: TeamType,
-) {
-    let texture: Handle<Image> = asset_server.load(match &team {
-        TeamType::Human => GOOD_HERO_ASSET_PATH,
-        TeamType::CPU => EVIL_HERO_ASSET_PATH,
-    });
+    players: &Players,
+) -> Entity {
+    let texture: Handle<Image> = asset_server.load(archetype.sprites.path(&team, players));
 
-    commands.spawn((
+    let mut unit = commands.spawn((
         SpriteBundle {
             texture,
-            transform: Transform::from_translation(position.extend(0.0)),
+            transform: Transform::from_translation(position),
             ..default()
         },
         Unit {},
-        Worker { effort: 4.5 },
-        Warrior { strength: 7.0 },
-        Detector {
-            range: HERO_DETECTION_RANGE,
-        },
-        Health(120.0),
+        UnitKind(archetype.kind.clone()),
+        Health(archetype.health),
         Faith {
-            base: 160.0,
-            current: 160.0,
+            base: archetype.faith,
+            current: archetype.faith,
+            persuader: None,
         },
         Moving(false),
+        //	new units fight their way to the rally point
         Moveable {
-            location: Vec3::ZERO,
+            location,
+            attack_move: true,
         },
         Selectable {
-            size: vec2(32., 32.),
+            size: archetype.size,
         },
-        Navigator { speed: 120.0 },
-        Team(team),
+        Navigator::new(archetype.speed, archetype.size),
+        Orders::default(),
         Idle(true),
-        Name::new("Hero"),
+        Team(team),
+        MatchEntity,
+        Name::new(archetype.name.clone()),
     ));
+
+    if let Some(effort) = archetype.effort {
+        unit.insert(Worker { effort });
+    }
+    if let Some(strength) = archetype.strength {
+        unit.insert(Warrior { strength });
+    }
+    if let Some(persuation) = archetype.persuasion {
+        unit.insert(Priest { persuation });
+    }
+    if let Some(range) = archetype.detection {
+        unit.insert(Detector { range });
+    }
+    if let Some(range) = archetype.sight {
+        unit.insert(Sight { range });
+    }
+
+    unit.id()
+}
+
+pub fn spawn_hero(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    registry: &UnitRegistry,
+    position: Vec2,
+    team: TeamType,
+    players: &Players,
+) {
+    let Some(archetype) = registry.get(&ProductionType::Hero) else {
+        warn!("no hero archetype has been loaded");
+        return;
+    };
+
+    let position = position.extend(0.0);
+    let hero = spawn_archetype(
+        commands,
+        asset_server,
+        archetype,
+        position,
+        position,
+        team,
+        players,
+    );
+    commands.entity(hero).insert(Hero);
 }
 
 fn spawn_unit(
@@ -98,128 +140,91 @@ fn spawn_unit(
     mut nav_path_assigner: EventWriter<AssignNavigatorPath>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    registry: Res<UnitRegistry>,
+    players: Res<Players>,
 ) {
     for event in production_event.read() {
-        let (texture_path, speed, name) = match (&event.production_type, &event.team) {
-            (ProductionType::Worker, TeamType::Human) => {
-                (GOOD_WORKER_ASSET_PATH, WORKER_SPEED, "Worker")
-            }
-            (ProductionType::Priest, TeamType::Human) => {
-                (GOOD_PRIEST_ASSET_PATH, PRIEST_SPEED, "Priest")
-            }
-            (ProductionType::Warrior, TeamType::Human) => {
-                (GOOD_WARRIOR_ASSET_PATH, WARRIOR_SPEED, "Warrior")
-            }
-            (ProductionType::None, TeamType::Human) => ("", 0.0, "None"),
-            (ProductionType::Worker, TeamType::CPU) => {
-                (EVIL_WORKER_ASSET_PATH, WORKER_SPEED, "Worker")
-            }
-            (ProductionType::Priest, TeamType::CPU) => {
-                (EVIL_PRIEST_ASSET_PATH, PRIEST_SPEED, "Priest")
-            }
-            (ProductionType::Warrior, TeamType::CPU) => {
-                (EVIL_WARRIOR_ASSET_PATH, WARRIOR_SPEED, "Warrior")
-            }
-            (ProductionType::None, TeamType::CPU) => ("", 0.0, "None"),
-        };
-        let texture = asset_server.load(texture_path);
-
-        let id = match event.production_type {
-            ProductionType::Worker => commands
-                .spawn((
-                    SpriteBundle {
-                        texture,
-                        transform: Transform::from_translation(event.position),
-                        ..default()
-                    },
-                    Unit {},
-                    Worker { effort: 1.5 },
-                    Health(24.0),
-                    Faith {
-                        base: 44.0,
-                        current: 44.0,
-                    },
-                    Moving(false),
-                    Moveable {
-                        location: event.location,
-                    },
-                    Selectable {
-                        size: vec2(32., 32.),
-                    },
-                    Navigator { speed },
-                    Idle(true),
-                    Team(event.team.clone()),
-                    Name::new(name),
-                ))
-                .id(),
-            ProductionType::Priest => commands
-                .spawn((
-                    SpriteBundle {
-                        texture,
-                        transform: Transform::from_translation(event.position),
-                        ..default()
-                    },
-                    Unit {},
-                    Priest { persuation: 3.0 },
-                    Detector {
-                        range: PRIEST_DETECTION_RANGE,
-                    },
-                    Health(16.0),
-                    Faith {
-                        base: 76.0,
-                        current: 76.0,
-                    },
-                    Moving(false),
-                    Moveable {
-                        location: event.location,
-                    },
-                    Selectable {
-                        size: vec2(32., 32.),
-                    },
-                    Navigator { speed },
-                    Idle(true),
-                    Team(event.team.clone()),
-                    Name::new(name),
-                ))
-                .id(),
-            ProductionType::Warrior => commands
-                .spawn((
-                    SpriteBundle {
-                        texture,
-                        transform: Transform::from_translation(event.position),
-                        ..default()
-                    },
-                    Unit {},
-                    Warrior { strength: 2.5 },
-                    Detector {
-                        range: WARRIOR_DETECTION_RANGE,
-                    },
-                    Health(42.0),
-                    Faith {
-                        base: 32.0,
-                        current: 32.0,
-                    },
-                    Moving(false),
-                    Moveable {
-                        location: event.location,
-                    },
-                    Selectable {
-                        size: vec2(32., 32.),
-                    },
-                    Navigator { speed },
-                    Idle(true),
-                    Team(event.team.clone()),
-                    Name::new(name),
-                ))
-                .id(),
-            ProductionType::None => {
-                todo!();
-            }
+        let Some(archetype) = registry.get(&event.production_type) else {
+            warn!("no archetype for {:?}", event.production_type);
+            continue;
         };
 
+        let id = spawn_archetype(
+            &mut commands,
+            &asset_server,
+            archetype,
+            event.position,
+            event.location,
+            event.team,
+            &players,
+        );
+
         nav_path_assigner.send(AssignNavigatorPath {
             entity: id,
             location: event.location + Vec3::new(0.0, -1.0, 0.0),
+            corridor: None,
         });
     }
 }
+
+type RebalancedUnitData = (
+    &'static UnitKind,
+    &'static mut Health,
+    &'static mut Faith,
+    &'static mut Navigator,
+    Option<&'static mut Worker>,
+    Option<&'static mut Warrior>,
+    Option<&'static mut Priest>,
+    Option<&'static mut Detector>,
+    Option<&'static mut Sight>,
+);
+
+//	health, faith and speed scale with the archetype so damage and persuasion carry over,
+//	while the role values are simply replaced
+fn rebalance_units(
+    mut changed: EventReader<UnitArchetypesChanged>,
+    registry: Res<UnitRegistry>,
+    mut units: Query<RebalancedUnitData>,
+) {
+    for change in changed.read() {
+        for (
+            kind,
+            mut health,
+            mut faith,
+            mut navigator,
+            worker,
+            warrior,
+            priest,
+            detector,
+            sight,
+        ) in units.iter_mut()
+        {
+            let (Some(previous), Some(archetype)) =
+                (change.previous(&kind.0), registry.get(&kind.0))
+            else {
+                continue;
+            };
+
+            health.0 = rescale(health.0, previous.health, archetype.health);
+            faith.base = archetype.faith;
+            faith.current = rescale(faith.current, previous.faith, archetype.faith);
+            navigator.speed = rescale(navigator.speed, previous.speed, archetype.speed);
+
+            if let (Some(mut worker), Some(effort)) = (worker, archetype.effort) {
+                worker.effort = effort;
+            }
+            if let (Some(mut warrior), Some(strength)) = (warrior, archetype.strength) {
+                warrior.strength = strength;
+            }
+            if let (Some(mut priest), Some(persuation)) = (priest, archetype.persuasion) {
+                priest.persuation = persuation;
+            }
+            if let (Some(mut detector), Some(range)) = (detector, archetype.detection) {
+                detector.range = range;
+            }
+            if let (Some(mut sight), Some(range)) = (sight, archetype.sight) {
+                sight.range = range;
+            }
+        }
+    }
+}
@@ -0,0 +1,666 @@
+use bevy::{
+    color::palettes::tailwind::GRAY_400,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    diplomacy::Diplomacy,
+    producer::Producer,
+    schedule::InGameSet,
+    state::GameState,
+    teams::{Players, Team, TeamType},
+    unit::{Hero, Unit},
+};
+
+const UNCLAIMED_COLOR: Color = Color::Srgba(GRAY_400);
+
+pub struct VictoryPlugin;
+
+impl Plugin for VictoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                (
+                    check_obliteration,
+                    check_regicide,
+                    check_king_of_the_hill,
+                    check_capture_points,
+                ),
+                decide_winner,
+            )
+                .chain()
+                .in_set(InGameSet::DespawnEntities),
+        )
+        .add_systems(
+            Update,
+            draw_objectives
+                //	test apps have nothing to draw with
+                .run_if(resource_exists::<GizmoConfigStore>)
+                .in_set(InGameSet::EntityUpdates),
+        )
+        .add_systems(OnEnter(GameState::GameOver), show_match_result)
+        .add_systems(OnExit(GameState::GameOver), hide_match_result)
+        .insert_resource(MatchRules::default())
+        .insert_resource(MatchProgress::default())
+        .insert_resource(MatchResult::default());
+    }
+}
+
+/// A way for a match to be decided, several of which can be active at once
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum WinCondition {
+    /// a team is out once it has no units and no producer
+    Obliteration,
+    /// a team is out once its hero is gone
+    Regicide,
+    /// a team wins by being the only one inside the region for the hold time
+    KingOfTheHill { region: Rect, hold_time: f32 },
+    /// a team wins by owning the required number of points for the hold time
+    CapturePoints {
+        points: Vec<Vec2>,
+        radius: f32,
+        required: usize,
+        hold_time: f32,
+    },
+}
+
+/// The win conditions for the current match
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct MatchRules {
+    pub conditions: Vec<WinCondition>,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            conditions: vec![WinCondition::Obliteration, WinCondition::Regicide],
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Default, Debug)]
+pub struct MatchProgress {
+    /// teams that have had units or a hero, so nobody loses before they have spawned
+    pub fielded: HashSet<TeamType>,
+    pub crowned: HashSet<TeamType>,
+    pub eliminated: HashSet<TeamType>,
+    /// keyed by the index of the condition in the rules, so several hills keep their own clocks
+    pub hill_timers: HashMap<(usize, TeamType), f32>,
+    /// keyed by the index of the condition and then of the point within it
+    pub point_owners: HashMap<(usize, usize), TeamType>,
+    pub point_timers: HashMap<(usize, TeamType), f32>,
+    /// a team that has met a hold condition, winning alongside its allies
+    pub holder_won: Option<TeamType>,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct MatchResult {
+    /// an empty list is a draw
+    pub winners: Vec<TeamType>,
+}
+
+#[derive(Component)]
+struct MatchResultText;
+
+fn has_condition(rules: &MatchRules, matches: impl Fn(&WinCondition) -> bool) -> bool {
+    rules.conditions.iter().any(matches)
+}
+
+/// the team holding an area, as long as everyone standing in it is allied
+fn holder(present: &[TeamType], diplomacy: &Diplomacy) -> Option<TeamType> {
+    let first = present.first()?;
+
+    present
+        .iter()
+        .all(|team| diplomacy.is_allied(first, team))
+        .then_some(*first)
+}
+
+fn check_obliteration(
+    rules: Res<MatchRules>,
+    units: Query<&Team, With<Unit>>,
+    producers: Query<&Team, With<Producer>>,
+    players: Res<Players>,
+    mut progress: ResMut<MatchProgress>,
+) {
+    if !has_condition(&rules, |c| matches!(c, WinCondition::Obliteration)) {
+        return;
+    }
+
+    let standing: HashSet<TeamType> = units.iter().chain(producers.iter()).map(|t| t.0).collect();
+
+    for player in players.list.iter() {
+        if standing.contains(&player.team) {
+            progress.fielded.insert(player.team);
+        } else if progress.fielded.contains(&player.team) {
+            progress.eliminated.insert(player.team);
+        }
+    }
+}
+
+fn check_regicide(
+    rules: Res<MatchRules>,
+    heroes: Query<&Team, With<Hero>>,
+    players: Res<Players>,
+    mut progress: ResMut<MatchProgress>,
+) {
+    if !has_condition(&rules, |c| matches!(c, WinCondition::Regicide)) {
+        return;
+    }
+
+    let crowned: HashSet<TeamType> = heroes.iter().map(|t| t.0).collect();
+
+    for player in players.list.iter() {
+        if crowned.contains(&player.team) {
+            progress.crowned.insert(player.team);
+        } else if progress.crowned.contains(&player.team) {
+            progress.eliminated.insert(player.team);
+        }
+    }
+}
+
+fn check_king_of_the_hill(
+    rules: Res<MatchRules>,
+    units: Query<(&Transform, &Team), With<Unit>>,
+    diplomacy: Res<Diplomacy>,
+    time: Res<Time>,
+    mut progress: ResMut<MatchProgress>,
+) {
+    for (condition_index, condition) in rules.conditions.iter().enumerate() {
+        let WinCondition::KingOfTheHill { region, hold_time } = condition else {
+            continue;
+        };
+
+        let mut present: Vec<TeamType> = units
+            .iter()
+            .filter(|(transform, _)| region.contains(transform.translation.xy()))
+            .map(|(_, team)| team.0)
+            .collect();
+        present.sort_by_key(|team| team.0);
+        present.dedup();
+
+        //	losing the hill resets its clock, leaving the other hills alone
+        let holding = holder(&present, &diplomacy);
+        progress
+            .hill_timers
+            .retain(|(index, held_by), _| *index != condition_index || Some(*held_by) == holding);
+        let Some(team) = holding else {
+            continue;
+        };
+
+        let timer = progress
+            .hill_timers
+            .entry((condition_index, team))
+            .or_default();
+        *timer += time.delta_seconds();
+        if *timer >= *hold_time {
+            progress.holder_won = Some(team);
+        }
+    }
+}
+
+fn check_capture_points(
+    rules: Res<MatchRules>,
+    units: Query<(&Transform, &Team), With<Unit>>,
+    diplomacy: Res<Diplomacy>,
+    time: Res<Time>,
+    mut progress: ResMut<MatchProgress>,
+) {
+    for (condition_index, condition) in rules.conditions.iter().enumerate() {
+        let WinCondition::CapturePoints {
+            points,
+            radius,
+            required,
+            hold_time,
+        } = condition
+        else {
+            continue;
+        };
+
+        //	points change hands as soon as one side stands on them uncontested
+        for (index, point) in points.iter().enumerate() {
+            let mut present: Vec<TeamType> = units
+                .iter()
+                .filter(|(transform, _)| transform.translation.xy().distance(*point) <= *radius)
+                .map(|(_, team)| team.0)
+                .collect();
+            present.sort_by_key(|team| team.0);
+            present.dedup();
+
+            if let Some(team) = holder(&present, &diplomacy) {
+                if !progress
+                    .point_owners
+                    .get(&(condition_index, index))
+                    .is_some_and(|owner| diplomacy.is_allied(owner, &team))
+                {
+                    progress.point_owners.insert((condition_index, index), team);
+                }
+            }
+        }
+
+        let owners: Vec<TeamType> = progress
+            .point_owners
+            .iter()
+            .filter(|((index, _), _)| *index == condition_index)
+            .map(|(_, owner)| *owner)
+            .collect();
+        let mut leaders: Vec<TeamType> = Vec::new();
+        for team in owners.iter() {
+            let owned = owners
+                .iter()
+                .filter(|owner| diplomacy.is_allied(team, owner))
+                .count();
+            if owned >= *required && !leaders.iter().any(|l| diplomacy.is_allied(l, team)) {
+                leaders.push(*team);
+            }
+        }
+
+        progress
+            .point_timers
+            .retain(|(index, team), _| *index != condition_index || leaders.contains(team));
+        for team in leaders {
+            let timer = progress
+                .point_timers
+                .entry((condition_index, team))
+                .or_default();
+            *timer += time.delta_seconds();
+            if *timer >= *hold_time {
+                progress.holder_won = Some(team);
+            }
+        }
+    }
+}
+
+fn decide_winner(
+    progress: Res<MatchProgress>,
+    players: Res<Players>,
+    diplomacy: Res<Diplomacy>,
+    mut result: ResMut<MatchResult>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let winners: Vec<TeamType> = if let Some(holder) = progress.holder_won {
+        players
+            .list
+            .iter()
+            .map(|player| player.team)
+            .filter(|team| diplomacy.is_allied(&holder, team))
+            .collect()
+    } else {
+        let remaining: Vec<TeamType> = players
+            .list
+            .iter()
+            .map(|player| player.team)
+            .filter(|team| !progress.eliminated.contains(team))
+            .collect();
+
+        //	the match goes on while any two teams still standing are enemies
+        if remaining
+            .iter()
+            .any(|a| remaining.iter().any(|b| !diplomacy.is_allied(a, b)))
+        {
+            return;
+        }
+
+        remaining
+    };
+
+    result.winners = winners;
+    next_state.set(GameState::GameOver);
+}
+
+fn draw_objectives(rules: Res<MatchRules>, progress: Res<MatchProgress>, mut gizmos: Gizmos) {
+    for (condition_index, condition) in rules.conditions.iter().enumerate() {
+        match condition {
+            WinCondition::KingOfTheHill { region, .. } => {
+                let color = progress
+                    .hill_timers
+                    .keys()
+                    .find(|(index, _)| *index == condition_index)
+                    .map_or(UNCLAIMED_COLOR, |(_, team)| team.color());
+                gizmos.rect_2d(region.center(), 0.0, region.size(), color);
+            }
+            WinCondition::CapturePoints { points, radius, .. } => {
+                for (index, point) in points.iter().enumerate() {
+                    let color = progress
+                        .point_owners
+                        .get(&(condition_index, index))
+                        .map_or(UNCLAIMED_COLOR, |team| team.color());
+                    gizmos.circle_2d(*point, *radius, color);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn show_match_result(result: Res<MatchResult>, players: Res<Players>, mut commands: Commands) {
+    let headline = if result.winners.is_empty() {
+        "Draw"
+    } else if result.winners.contains(&players.local) {
+        "Victory"
+    } else {
+        "Defeat"
+    };
+    let winners = result
+        .winners
+        .iter()
+        .map(|team| format!("Team {}", team.0))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::linear_rgba(0.0, 0.0, 0.0, 0.6).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            MatchResultText,
+        ))
+        .with_children(|builder| {
+            builder.spawn(TextBundle::from_section(
+                headline,
+                TextStyle {
+                    font_size: 64.0,
+                    ..default()
+                },
+            ));
+            if !winners.is_empty() {
+                builder.spawn(TextBundle::from_section(
+                    format!("Winners: {}", winners),
+                    TextStyle { ..default() },
+                ));
+            }
+            builder.spawn(TextBundle::from_section(
+                "Press Escape to return to the menu",
+                TextStyle { ..default() },
+            ));
+        });
+}
+
+fn hide_match_result(texts: Query<Entity, With<MatchResultText>>, mut commands: Commands) {
+    for entity in texts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        producer::ProductionType,
+        teams::Controller,
+        testing::{Scenario, TestApp},
+    };
+
+    fn with_rules(conditions: Vec<WinCondition>) -> TestApp {
+        let mut test = TestApp::new(VictoryPlugin);
+        test.app.insert_resource(MatchRules { conditions });
+        test
+    }
+
+    fn hill(center: Vec2) -> WinCondition {
+        WinCondition::KingOfTheHill {
+            region: Rect::from_center_size(center, Vec2::splat(100.0)),
+            hold_time: 1.0,
+        }
+    }
+
+    fn move_to(test: &mut TestApp, entity: Entity, position: Vec2) {
+        let mut transform = test
+            .app
+            .world_mut()
+            .get_mut::<Transform>(entity)
+            .expect("unit has a transform");
+        transform.translation = position.extend(0.0);
+    }
+
+    fn winner(test: &TestApp) -> Option<TeamType> {
+        test.app.world().resource::<MatchProgress>().holder_won
+    }
+
+    fn three_teams(conditions: Vec<WinCondition>) -> TestApp {
+        let mut test = with_rules(conditions);
+        test.app
+            .insert_resource(Players::all(3, TeamType(0), Controller::CPU));
+        test
+    }
+
+    fn still_playing(test: &TestApp) -> bool {
+        let world = test.app.world();
+        world.resource::<MatchResult>().winners.is_empty()
+            && *world.resource::<State<GameState>>().get() == GameState::InGame
+    }
+
+    fn eliminated(test: &TestApp) -> Vec<TeamType> {
+        let mut teams: Vec<TeamType> = test
+            .app
+            .world()
+            .resource::<MatchProgress>()
+            .eliminated
+            .iter()
+            .copied()
+            .collect();
+        teams.sort_by_key(|team| team.0);
+        teams
+    }
+
+    #[test]
+    fn holding_the_hill_wins_after_the_hold_time() {
+        let mut test = with_rules(vec![hill(Vec2::ZERO)]);
+        test.spawn(&Scenario {
+            units: vec![(ProductionType::Worker, TeamType(0), Vec2::ZERO)],
+            ..default()
+        });
+
+        test.step_seconds(0.5);
+        assert_eq!(winner(&test), None);
+
+        test.step_seconds(0.6);
+        assert_eq!(winner(&test), Some(TeamType(0)));
+        assert_eq!(
+            test.app.world().resource::<MatchResult>().winners,
+            vec![TeamType(0)]
+        );
+    }
+
+    #[test]
+    fn contesting_the_hill_resets_the_clock() {
+        let mut test = with_rules(vec![hill(Vec2::ZERO)]);
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Worker, TeamType(0), Vec2::ZERO),
+                (ProductionType::Worker, TeamType(1), Vec2::new(500.0, 0.0)),
+            ],
+            ..default()
+        });
+        let enemy = spawned.units[1];
+
+        test.step_seconds(0.6);
+        move_to(&mut test, enemy, Vec2::new(10.0, 0.0));
+        test.step(1);
+        assert!(test
+            .app
+            .world()
+            .resource::<MatchProgress>()
+            .hill_timers
+            .is_empty());
+
+        //	the clock starts over once the enemy leaves, rather than carrying on from before
+        move_to(&mut test, enemy, Vec2::new(500.0, 0.0));
+        test.step_seconds(0.6);
+        assert_eq!(winner(&test), None);
+
+        test.step_seconds(0.5);
+        assert_eq!(winner(&test), Some(TeamType(0)));
+    }
+
+    #[test]
+    fn capture_points_change_hands_and_win_once_enough_are_held() {
+        let mut test = with_rules(vec![WinCondition::CapturePoints {
+            points: vec![Vec2::ZERO, Vec2::new(300.0, 0.0)],
+            radius: 50.0,
+            required: 2,
+            hold_time: 1.0,
+        }]);
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Worker, TeamType(0), Vec2::ZERO),
+                (ProductionType::Worker, TeamType(1), Vec2::new(300.0, 0.0)),
+                (ProductionType::Worker, TeamType(0), Vec2::new(600.0, 0.0)),
+            ],
+            ..default()
+        });
+        let (enemy, runner) = (spawned.units[1], spawned.units[2]);
+
+        test.step(2);
+        let owners = test
+            .app
+            .world()
+            .resource::<MatchProgress>()
+            .point_owners
+            .clone();
+        assert_eq!(owners.get(&(0, 0)), Some(&TeamType(0)));
+        assert_eq!(owners.get(&(0, 1)), Some(&TeamType(1)));
+
+        //	a point stays with its owner until someone else stands on it alone
+        move_to(&mut test, enemy, Vec2::new(600.0, 0.0));
+        test.step(2);
+        let owners = test
+            .app
+            .world()
+            .resource::<MatchProgress>()
+            .point_owners
+            .clone();
+        assert_eq!(owners.get(&(0, 1)), Some(&TeamType(1)));
+
+        move_to(&mut test, runner, Vec2::new(300.0, 0.0));
+        test.step(2);
+        let owners = test
+            .app
+            .world()
+            .resource::<MatchProgress>()
+            .point_owners
+            .clone();
+        assert_eq!(owners.get(&(0, 1)), Some(&TeamType(0)));
+
+        test.step_seconds(1.1);
+        assert_eq!(winner(&test), Some(TeamType(0)));
+    }
+
+    #[test]
+    fn conditions_of_the_same_kind_keep_their_own_progress() {
+        let mut test = with_rules(vec![
+            hill(Vec2::ZERO),
+            hill(Vec2::new(1000.0, 0.0)),
+            WinCondition::CapturePoints {
+                points: vec![Vec2::new(0.0, 500.0)],
+                radius: 50.0,
+                required: 1,
+                hold_time: 10.0,
+            },
+            WinCondition::CapturePoints {
+                points: vec![Vec2::new(0.0, -500.0)],
+                radius: 50.0,
+                required: 1,
+                hold_time: 10.0,
+            },
+        ]);
+        test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Worker, TeamType(0), Vec2::ZERO),
+                (ProductionType::Worker, TeamType(0), Vec2::new(0.0, 500.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(0.0, -500.0)),
+            ],
+            ..default()
+        });
+
+        test.step(2);
+        let owners = test
+            .app
+            .world()
+            .resource::<MatchProgress>()
+            .point_owners
+            .clone();
+        assert_eq!(owners.get(&(2, 0)), Some(&TeamType(0)));
+        assert_eq!(owners.get(&(3, 0)), Some(&TeamType(1)));
+
+        //	the empty second hill leaves the first hill's clock running
+        test.step_seconds(1.1);
+        assert_eq!(winner(&test), Some(TeamType(0)));
+    }
+
+    #[test]
+    fn a_team_without_units_or_producers_is_out() {
+        let mut test = three_teams(vec![WinCondition::Obliteration]);
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Worker, TeamType(0), Vec2::ZERO),
+                (ProductionType::Worker, TeamType(1), Vec2::new(500.0, 0.0)),
+                (ProductionType::Worker, TeamType(2), Vec2::new(-500.0, 0.0)),
+            ],
+            structures: vec![("Producer".into(), TeamType(2), Vec2::new(-800.0, 0.0))],
+            ..default()
+        });
+        test.step(2);
+
+        test.app.world_mut().despawn(spawned.units[1]);
+        test.app.world_mut().despawn(spawned.units[2]);
+        test.step(2);
+
+        //	team 2 still has its producer, so it stays in alongside team 0
+        assert_eq!(eliminated(&test), vec![TeamType(1)]);
+        assert!(still_playing(&test));
+
+        test.app.world_mut().despawn(spawned.structures[0]);
+        test.step(2);
+        assert_eq!(eliminated(&test), vec![TeamType(1), TeamType(2)]);
+        assert_eq!(
+            test.app.world().resource::<MatchResult>().winners,
+            vec![TeamType(0)]
+        );
+    }
+
+    #[test]
+    fn a_team_whose_hero_dies_is_out() {
+        let mut test = three_teams(vec![WinCondition::Regicide]);
+        let spawned = test.spawn(&Scenario {
+            units: vec![
+                (ProductionType::Hero, TeamType(0), Vec2::ZERO),
+                (ProductionType::Hero, TeamType(1), Vec2::new(500.0, 0.0)),
+                (ProductionType::Hero, TeamType(2), Vec2::new(-500.0, 0.0)),
+                (ProductionType::Worker, TeamType(1), Vec2::new(500.0, 100.0)),
+            ],
+            ..default()
+        });
+        for hero in spawned.units.iter().take(3) {
+            test.app.world_mut().entity_mut(*hero).insert(Hero);
+        }
+        test.step(2);
+
+        //	the worker left behind does not keep team 1 in
+        test.app.world_mut().despawn(spawned.units[1]);
+        test.step(2);
+        assert_eq!(eliminated(&test), vec![TeamType(1)]);
+        assert!(still_playing(&test));
+
+        test.app.world_mut().despawn(spawned.units[0]);
+        test.step(2);
+        assert_eq!(eliminated(&test), vec![TeamType(0), TeamType(1)]);
+        assert_eq!(
+            test.app.world().resource::<MatchResult>().winners,
+            vec![TeamType(2)]
+        );
+    }
+}
@@ -3,8 +3,10 @@ use bevy::prelude::*;
 use crate::{
     combat::AttackPursuit,
     conversion::ConvertPursuit,
+    nav_agent::PathFailed,
     selectable::{SelectedStructures, SelectedUnits},
     structure::Structure,
+    teams::{Players, Team},
     unit::Unit,
 };
 
@@ -13,10 +15,13 @@ const ATTACKED_UNIT_RADIUS: f32 = 22.0;
 const CONVERTING_UNIT_RADIUS: f32 = 24.0;
 const SELECTED_STRUCTURE_RADIUS: f32 = 36.0;
 const ATTACKED_STRUCTURE_RADIUS: f32 = 38.0;
+const FAILED_MOVE_SIZE: f32 = 12.0;
+const FAILED_MOVE_DURATION: f32 = 1.0;
 
 const SELECTION_COLOR: Color = Color::linear_rgba(0.4, 0.4, 0.0, 0.4);
 const ATTACKED_COLOR: Color = Color::linear_rgba(0.7, 0.0, 0.2, 0.4);
 const CONVERTING_COLOR: Color = Color::linear_rgba(0.0, 0.3, 0.8, 0.4);
+const FAILED_MOVE_COLOR: Color = Color::linear_rgba(0.8, 0.1, 0.0, 0.8);
 
 pub struct VisualFeedbackPlugin;
 
@@ -30,17 +35,26 @@ impl Plugin for VisualFeedbackPlugin {
                 highlight_converting_units,
                 highlight_selected_structures,
                 highlight_attacked_structures,
+                (mark_failed_moves, show_failed_moves).chain(),
             ),
-        );
+        )
+        .insert_resource(FailedMoves::default());
     }
 }
 
+/// Where the local player's units were sent but could not get to, for a moment after
+#[derive(Resource, Default)]
+struct FailedMoves {
+    marks: Vec<(Vec2, f32)>,
+}
+
 fn highlight_selected_units(
     selected_units: Res<SelectedUnits>,
+    players: Res<Players>,
     units: Query<&Transform, With<Unit>>,
     mut gizmos: Gizmos,
 ) {
-    for &entity in selected_units.entities.human.iter() {
+    for &entity in selected_units.entities.iter(&players.local) {
         if let Ok(transform) = units.get(entity) {
             gizmos.circle_2d(
                 transform.translation.xy(),
@@ -85,10 +99,11 @@ fn highlight_converting_units(
 
 fn highlight_selected_structures(
     selected_structures: Res<SelectedStructures>,
+    players: Res<Players>,
     structures: Query<&Transform, With<Structure>>,
     mut gizmos: Gizmos,
 ) {
-    for &entity in selected_structures.entities.human.iter() {
+    for &entity in selected_structures.entities.iter(&players.local) {
         if let Ok(transform) = structures.get(entity) {
             gizmos.circle_2d(
                 transform.translation.xy(),
@@ -114,3 +129,44 @@ fn highlight_attacked_structures(
         }
     }
 }
+
+fn mark_failed_moves(
+    mut failed: EventReader<PathFailed>,
+    units: Query<&Team>,
+    players: Res<Players>,
+    mut failed_moves: ResMut<FailedMoves>,
+) {
+    for failure in failed.read() {
+        let Ok(team) = units.get(failure.entity) else {
+            continue;
+        };
+
+        //	a group order fails for every unit in it, which only needs the one mark
+        if players.is_local(&team.0)
+            && !failed_moves
+                .marks
+                .iter()
+                .any(|(location, _)| *location == failure.location)
+        {
+            failed_moves
+                .marks
+                .push((failure.location, FAILED_MOVE_DURATION));
+        }
+    }
+}
+
+fn show_failed_moves(mut failed_moves: ResMut<FailedMoves>, time: Res<Time>, mut gizmos: Gizmos) {
+    for (location, remaining) in failed_moves.marks.iter_mut() {
+        *remaining -= time.delta_seconds();
+
+        let corner = Vec2::splat(FAILED_MOVE_SIZE / 2.0);
+        gizmos.line_2d(*location - corner, *location + corner, FAILED_MOVE_COLOR);
+        gizmos.line_2d(
+            *location + corner.with_x(-corner.x),
+            *location - corner.with_x(-corner.x),
+            FAILED_MOVE_COLOR,
+        );
+    }
+
+    failed_moves.marks.retain(|(_, remaining)| *remaining > 0.0);
+}